@@ -0,0 +1,128 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Name of the file placed beside the executable to enable portable mode
+const PORTABLE_FLAG_FILE: &str = "portable.flag";
+/// Folder beside the executable storing all data in portable mode
+const PORTABLE_DATA_DIR: &str = "spell_bubble_mod_tool_data";
+const APP_DIR_NAME: &str = "spell_bubble_mod_tool";
+
+static PORTABLE: AtomicBool = AtomicBool::new(false);
+
+/// Forces portable mode, used by the `--portable` argument
+pub fn enable_portable() {
+    PORTABLE.store(true, Ordering::Relaxed)
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    exe.parent().map(|p| p.to_owned())
+}
+
+/// In portable mode, config and other data are stored beside the executable
+/// instead of OS specific directories
+pub fn is_portable() -> bool {
+    PORTABLE.load(Ordering::Relaxed)
+        || exe_dir().is_some_and(|mut dir| {
+            dir.push(PORTABLE_FLAG_FILE);
+            dir.is_file()
+        })
+}
+
+fn portable_dir() -> Option<PathBuf> {
+    let mut dir = exe_dir()?;
+    dir.push(PORTABLE_DATA_DIR);
+    Some(dir)
+}
+
+/// Directory for `maps.toml` and other settings
+pub fn config_dir() -> Option<PathBuf> {
+    if is_portable() {
+        return portable_dir();
+    }
+
+    let mut dir = dirs::config_local_dir()?;
+    dir.push(APP_DIR_NAME);
+    Some(dir)
+}
+
+/// Directory for data computed from game files, which can be regenerated
+pub fn cache_dir() -> Option<PathBuf> {
+    if is_portable() {
+        let mut dir = portable_dir()?;
+        dir.push("cache");
+        return Some(dir);
+    }
+
+    let mut dir = dirs::cache_dir()?;
+    dir.push(APP_DIR_NAME);
+    Some(dir)
+}
+
+/// Directory for records of previous runs and logs, which are neither settings
+/// nor safe to drop like caches. Only Linux has a dedicated state directory,
+/// local data directory is used on other platforms.
+pub fn state_dir() -> Option<PathBuf> {
+    if is_portable() {
+        let mut dir = portable_dir()?;
+        dir.push("state");
+        return Some(dir);
+    }
+
+    let mut dir = dirs::state_dir().or_else(dirs::data_local_dir)?;
+    dir.push(APP_DIR_NAME);
+    Some(dir)
+}
+
+/// Total size of files under `path`
+fn dir_size(path: &Path) -> u64 {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => std::fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| dir_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or_default(),
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    }
+}
+
+/// Entries in the cache directory with their sizes, sorted by name
+pub fn cache_entries() -> std::io::Result<Vec<(PathBuf, u64)>> {
+    let Some(dir) = cache_dir().filter(|dir| dir.is_dir()) else {
+        return Ok(vec![]);
+    };
+
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+
+    Ok(entries
+        .into_iter()
+        .map(|path| {
+            let size = dir_size(&path);
+            (path, size)
+        })
+        .collect())
+}
+
+/// Removes everything in the cache directory, returns the bytes freed
+pub fn clear_cache() -> std::io::Result<u64> {
+    let mut freed = 0;
+    for (path, size) in cache_entries()? {
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+        freed += size;
+    }
+
+    Ok(freed)
+}
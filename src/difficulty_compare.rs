@@ -0,0 +1,181 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+
+use crate::{
+    app_dirs, input_check,
+    map::{ChartStats, Difficulty, Map},
+    platform,
+};
+
+const CACHE_FILE_NAME: &str = "official_chart_stats.toml";
+
+/// Chart statistics of official songs, cached as analyzing all official charts
+/// takes a while
+#[derive(Default, Serialize, Deserialize)]
+pub struct OfficialStats {
+    /// Hash of share_data the statistics are computed from
+    share_data_hash: String,
+    /// Statistics of official charts keyed by difficulty names
+    stats:           BTreeMap<String, Vec<ChartStats>>,
+}
+
+impl OfficialStats {
+    fn from_maps(maps: &[Map], share_data_hash: String) -> Self {
+        let stats = Difficulty::iter()
+            .map(|difficulty| {
+                let stats = maps
+                    .iter()
+                    .filter_map(|map| map.chart_stats(difficulty))
+                    .collect();
+                (difficulty.to_string(), stats)
+            })
+            .collect();
+
+        Self {
+            share_data_hash,
+            stats,
+        }
+    }
+
+    /// Compares statistics of custom chart against official charts of the same
+    /// difficulty
+    pub fn compare(&self, difficulty: Difficulty, stats: ChartStats) -> Comparison {
+        let official = self
+            .stats
+            .get(&difficulty.to_string())
+            .map(|stats| stats.as_slice())
+            .unwrap_or_default();
+
+        Comparison {
+            difficulty,
+            stats,
+            density_percentile: percentile(official.iter().map(|s| s.density), stats.density),
+            peak_percentile: percentile(
+                official.iter().map(|s| s.peak_density),
+                stats.peak_density,
+            ),
+        }
+    }
+}
+
+/// Percentage of `values` lower than `value`
+fn percentile(values: impl Iterator<Item = f32>, value: f32) -> Option<f32> {
+    let (lower, total) = values.fold((0, 0), |(lower, total), v| {
+        (lower + (v < value) as usize, total + 1)
+    });
+    (total > 0).then(|| lower as f32 / total as f32 * 100.0)
+}
+
+fn cache_path() -> Option<PathBuf> {
+    let mut path = app_dirs::cache_dir()?;
+    path.push(CACHE_FILE_NAME);
+    Some(path)
+}
+
+/// Loads statistics of official charts in `romfs_root`, from cache if it is
+/// computed from the same share_data
+pub fn load_official_stats(romfs_root: &Path) -> anyhow::Result<OfficialStats> {
+    let share_data = romfs_root.join(platform::current().share_data());
+    let share_data_hash = input_check::hash_file(&share_data)?;
+
+    let cached = cache_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<OfficialStats>(&content).ok())
+        .filter(|stats| stats.share_data_hash == share_data_hash);
+    if let Some(cached) = cached {
+        return Ok(cached);
+    }
+
+    let maps = crate::map::get_song_info(romfs_root)?
+        .into_iter()
+        .map(|(map, ..)| map)
+        .collect::<Vec<_>>();
+    let stats = OfficialStats::from_maps(&maps, share_data_hash);
+
+    // Failing to write cache only costs time on the next run
+    if let Some(path) = cache_path() {
+        let _ = path
+            .parent()
+            .map(std::fs::create_dir_all)
+            .transpose()
+            .and_then(|_| std::fs::write(&path, toml::to_string(&stats).unwrap_or_default()));
+    }
+
+    Ok(stats)
+}
+
+/// Statistics of a custom chart with its percentiles among official charts
+pub struct Comparison {
+    pub difficulty:         Difficulty,
+    pub stats:              ChartStats,
+    pub density_percentile: Option<f32>,
+    pub peak_percentile:    Option<f32>,
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let difficulty = self.difficulty;
+        write!(f, "{difficulty}: {:.2} notes/s", self.stats.density)?;
+        if let Some(percentile) = self.density_percentile {
+            write!(
+                f,
+                ", denser than {percentile:.0}% of official {difficulty} charts"
+            )?;
+        }
+
+        write!(f, "; peak {:.2} notes/s", self.stats.peak_density)?;
+        if let Some(percentile) = self.peak_percentile {
+            write!(f, ", higher than {percentile:.0}%")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares all charts of `map` against official charts
+pub fn compare_map(map: &Map, official: &OfficialStats) -> Vec<Comparison> {
+    Difficulty::iter()
+        .filter_map(|difficulty| {
+            map.chart_stats(difficulty)
+                .map(|stats| official.compare(difficulty, stats))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_compare() {
+        let stats = |density: f32| ChartStats {
+            density,
+            peak_density: density * 2.0,
+        };
+        let official = OfficialStats {
+            share_data_hash: Default::default(),
+            stats:           BTreeMap::from([(
+                Difficulty::Hard.to_string(),
+                (1..=10).map(|d| stats(d as f32)).collect(),
+            )]),
+        };
+
+        let comparison = official.compare(Difficulty::Hard, stats(9.5));
+        assert_eq!(comparison.density_percentile, Some(90.0));
+        assert_eq!(comparison.peak_percentile, Some(90.0));
+        assert!(
+            comparison
+                .to_string()
+                .contains("denser than 90% of official Hard")
+        );
+
+        let comparison = official.compare(Difficulty::Easy, stats(1.0));
+        assert_eq!(comparison.density_percentile, None);
+    }
+}
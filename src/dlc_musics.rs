@@ -0,0 +1,120 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{mod_merge::UnlockOptions, song_info::SongInfos};
+
+/// Music kept locked by UnlockFeatures to keep the shop functioning normally
+pub const KEPT_LOCKED_MUSIC: &str = "Lostword";
+
+/// A song sold as DLC
+pub struct DlcMusic {
+    pub id:        String,
+    /// DLC ID, as accepted by `UnlockFeatures --exclude`
+    pub dlc_index: u16,
+}
+
+/// Songs sold as DLC, which UnlockFeatures unlocks whether they are bought or
+/// not. The helper library can't read the lock flags of share_data, so this
+/// doesn't tell which songs are locked, and characters and special rules are
+/// only previewed by the options given.
+pub struct DlcMusics {
+    pub musics: Vec<DlcMusic>,
+}
+
+impl DlcMusics {
+    pub fn from_song_infos(infos: &SongInfos) -> Self {
+        let musics = infos
+            .maps
+            .iter()
+            .map(|map_info| &map_info.map.song_info)
+            .filter(|song_info| song_info.dlc_index != 0)
+            .map(|song_info| DlcMusic {
+                id:        song_info.id.to_string(),
+                dlc_index: song_info.dlc_index,
+            })
+            .collect();
+
+        Self { musics }
+    }
+
+    /// DLC musics UnlockFeatures would unlock with `options`
+    pub fn unlocked_musics(&self, options: &UnlockOptions) -> Vec<&DlcMusic> {
+        if !options.musics {
+            return vec![];
+        }
+
+        self.musics
+            .iter()
+            .filter(|music| {
+                music.id != KEPT_LOCKED_MUSIC && !options.exclude.contains(&music.dlc_index)
+            })
+            .collect()
+    }
+
+    /// Lists what UnlockFeatures would change with `options`
+    pub fn preview(&self, options: &UnlockOptions) -> String {
+        let mut lines = vec![];
+
+        if options.special_rules {
+            lines.push("Special challenge rules".to_owned());
+        }
+        lines.extend(
+            self.unlocked_musics(options)
+                .iter()
+                .map(|music| format!("Song {} (DLC {})", music.id, music.dlc_index)),
+        );
+        if options.characters {
+            lines.push("DLC characters".to_owned());
+        }
+
+        if lines.is_empty() {
+            "Nothing would be unlocked".to_owned()
+        } else {
+            format!("Would unlock:\n  {}", lines.join("\n  "))
+        }
+    }
+}
+
+impl Display for DlcMusics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Songs sold as DLC ({}):", self.musics.len())?;
+        for music in &self.musics {
+            writeln!(f, "  {} (DLC {})", music.id, music.dlc_index)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unlocked_musics() {
+        let music = |id: &str, dlc_index| DlcMusic {
+            id: id.to_owned(),
+            dlc_index,
+        };
+        let dlc_musics = DlcMusics {
+            musics: vec![music(KEPT_LOCKED_MUSIC, 0), music("A", 1), music("B", 2)],
+        };
+
+        let options = UnlockOptions {
+            musics: true,
+            exclude: vec![2],
+            ..Default::default()
+        };
+        let unlocked = dlc_musics.unlocked_musics(&options);
+        assert_eq!(unlocked.len(), 1);
+        assert_eq!(unlocked[0].id, "A");
+        assert_eq!(
+            dlc_musics.preview(&options),
+            "Would unlock:\n  Song A (DLC 1)"
+        );
+
+        assert_eq!(
+            dlc_musics.preview(&UnlockOptions::default()),
+            "Nothing would be unlocked"
+        );
+    }
+}
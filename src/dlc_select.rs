@@ -0,0 +1,120 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+/// A DLC given on the command line, by its ID as listed by ListDlcs or by its
+/// name. Names match case-insensitively as substrings, or as a whole with `*`
+/// wildcards if the pattern has any.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DlcSelector {
+    Index(u16),
+    Pattern(String),
+}
+
+impl FromStr for DlcSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("Empty DLC name".to_owned());
+        }
+
+        Ok(match s.parse() {
+            Ok(index) => Self::Index(index),
+            Err(_) => Self::Pattern(s.to_owned()),
+        })
+    }
+}
+
+impl Display for DlcSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{index}"),
+            Self::Pattern(pattern) => write!(f, "{pattern}"),
+        }
+    }
+}
+
+/// Matches `name` against `pattern` with `*` matching any run of characters
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_match(rest, &name[skip..])),
+        Some((c, rest)) => name
+            .split_first()
+            .is_some_and(|(n, name_rest)| n == c && glob_match(rest, name_rest)),
+    }
+}
+
+impl DlcSelector {
+    /// Whether the DLC with 1-based `index` and `name` is selected
+    pub fn matches(&self, index: u16, name: &str) -> bool {
+        match self {
+            Self::Index(i) => *i == index,
+            Self::Pattern(pattern) => {
+                let pattern = pattern.to_lowercase();
+                let name = name.to_lowercase();
+                if pattern.contains('*') {
+                    let pattern = pattern.chars().collect::<Vec<_>>();
+                    glob_match(&pattern, &name.chars().collect::<Vec<_>>())
+                } else {
+                    name.contains(&pattern)
+                }
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("No DLC matches {0}, use ListDlcs to list DLCs")]
+pub struct UnknownDlcError(pub DlcSelector);
+
+/// IDs of DLCs in `dlcs` (in index order) selected by any of `selectors`.
+/// Selectors matching no DLC are errors, as they are most likely typos.
+pub fn resolve_dlcs(
+    selectors: &[DlcSelector],
+    dlcs: &[String],
+) -> Result<Vec<u16>, UnknownDlcError> {
+    let indexed = || (1..).zip(dlcs);
+
+    if let Some(selector) = selectors
+        .iter()
+        .find(|selector| !indexed().any(|(index, name)| selector.matches(index, name)))
+    {
+        return Err(UnknownDlcError(selector.clone()));
+    }
+
+    Ok(indexed()
+        .filter(|(index, name)| selectors.iter().any(|s| s.matches(*index, name)))
+        .map(|(index, _)| index)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_resolve_dlcs() {
+        let dlcs = [
+            "Touhou Arrange Pack 1",
+            "Touhou Arrange Pack 2",
+            "Vocal Pack",
+        ]
+        .map(str::to_owned)
+        .to_vec();
+        let select = |s: &[&str]| {
+            let selectors = s.iter().map(|s| s.parse().unwrap()).collect::<Vec<_>>();
+            resolve_dlcs(&selectors, &dlcs)
+        };
+
+        assert_eq!(select(&["vocal"]).unwrap(), vec![3]);
+        assert!(select(&["touhou*pack"]).is_err());
+        assert_eq!(select(&["touhou*pack*", "3"]).unwrap(), vec![1, 2, 3]);
+        assert_eq!(select(&["2", "Pack 2"]).unwrap(), vec![2]);
+        assert!(select(&["Jazz"]).is_err());
+        assert!(select(&["4"]).is_err());
+    }
+}
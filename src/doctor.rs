@@ -0,0 +1,174 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+use crate::{exefs, ffmpeg_helper, platform, romfs_detect};
+
+/// Game files a RomFS root must contain, relative to it
+pub fn required_files() -> [String; 2] {
+    let platform = platform::current();
+    [platform.share_data(), platform.global_metadata().to_owned()]
+}
+
+/// How deep to look below a wrong RomFS root for the right one
+const ROMFS_SEARCH_DEPTH: usize = 2;
+
+/// Outcome of one environment check, problems tell how to fix them
+pub struct Diagnostic {
+    pub subject: &'static str,
+    pub result:  Result<String, String>,
+}
+
+impl Diagnostic {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(detail) => write!(f, "[ OK ] {}: {detail}", self.subject),
+            Err(problem) => write!(f, "[FAIL] {}: {problem}", self.subject),
+        }
+    }
+}
+
+pub fn check_ffmpeg() -> Diagnostic {
+    Diagnostic {
+        subject: "ffmpeg",
+        result:  ffmpeg_helper::version().map_err(|e| {
+            format!(
+                "ffmpeg cannot be run ({e}), install ffmpeg and make sure the folder containing \
+                 it is in PATH, or place it beside mod_tool"
+            )
+        }),
+    }
+}
+
+/// ffprobe ships with ffmpeg, but is needed separately for reading music
+/// durations
+pub fn check_ffprobe() -> Diagnostic {
+    Diagnostic {
+        subject: "ffprobe",
+        result:  ffmpeg_helper::ffprobe_version().map_err(|e| {
+            format!(
+                "ffprobe cannot be run ({e}), install a full ffmpeg build including ffprobe and \
+                 make sure the folder containing it is in PATH, or place it beside mod_tool"
+            )
+        }),
+    }
+}
+
+pub fn missing_files(romfs_root: &Path) -> Vec<String> {
+    required_files()
+        .into_iter()
+        .filter(|file| !romfs_root.join(file).is_file())
+        .collect()
+}
+
+/// Finds a folder below `dir` with the RomFS layout, for when a parent of the
+/// RomFS root (e.g. the whole RomFS dump) is given
+fn find_romfs_root(dir: &Path, depth: usize) -> Option<PathBuf> {
+    if depth == 0 {
+        return None;
+    }
+
+    let mut subdirs = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect::<Vec<_>>();
+    subdirs.sort();
+
+    subdirs.iter().find_map(|subdir| {
+        if missing_files(subdir).is_empty() {
+            Some(subdir.clone())
+        } else {
+            find_romfs_root(subdir, depth - 1)
+        }
+    })
+}
+
+pub fn check_romfs(romfs_root: &Path) -> Diagnostic {
+    let result = if !romfs_root.is_dir() {
+        Err(format!(
+            "{} is not a folder, pass the folder of dumped game RomFS files",
+            romfs_root.display()
+        ))
+    } else {
+        let missing = missing_files(romfs_root);
+        if missing.is_empty() {
+            Ok(format!("{} has the expected layout", romfs_root.display()))
+        } else {
+            let hint = match find_romfs_root(romfs_root, ROMFS_SEARCH_DEPTH) {
+                Some(found) => format!("use {} as the RomFS root instead", found.display()),
+                None => match romfs_detect::suggestion() {
+                    Some(suggestion) => suggestion,
+                    None => "pass the folder containing StreamingAssets and Managed of a complete \
+                             RomFS dump"
+                        .to_owned(),
+                },
+            };
+            Err(format!(
+                "{} is missing from {}, {hint}",
+                missing.join(" and "),
+                romfs_root.display()
+            ))
+        }
+    };
+
+    Diagnostic {
+        subject: "RomFS",
+        result,
+    }
+}
+
+pub fn check_main_exe(main_exe: &Path) -> Diagnostic {
+    let result = match exefs::check_nso(main_exe) {
+        Ok(build_id) => exefs::check_build(main_exe)
+            .map(|_| format!("NSO with build ID {build_id}"))
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(format!(
+            "{} is not a readable NSO file ({e}), pass the \"main\" file from the dumped ExeFS",
+            main_exe.display()
+        )),
+    };
+
+    Diagnostic {
+        subject: "main executable",
+        result,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_romfs() {
+        let root = std::env::temp_dir().join("spell_bubble_doctor_test");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let romfs_root = root.join("romfs/Data");
+        for file in required_files() {
+            let path = romfs_root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"data").unwrap();
+        }
+
+        assert!(check_romfs(&romfs_root).is_ok());
+        assert_eq!(find_romfs_root(&root, ROMFS_SEARCH_DEPTH), Some(romfs_root));
+        assert!(
+            check_romfs(&root)
+                .result
+                .unwrap_err()
+                .contains("as the RomFS root instead")
+        );
+        assert!(!check_romfs(&root.join("missing")).is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use crate::{
+    ffmpeg_helper,
+    map::{DEFAULT_MIN_LEAD_IN, Map, ScoreData, ScoreEntry},
+};
+
+/// Sample rate audio is decoded at for onset detection, high enough to keep
+/// hi-hats and snares
+const ANALYSIS_SAMPLE_RATE: u32 = 11025;
+/// Hop between analysis frames in seconds
+const FRAME_DURATION: f32 = 0.01;
+/// Onsets this far in seconds from a beat are attributed to it
+const BEAT_TOLERANCE: f32 = 0.05;
+/// Share of notes among beats with onsets made heavy notes, the strongest ones
+const HEAVY_RATIO: f32 = 0.15;
+/// Longest run of notes without blanks the game accepts
+const MAX_SEGMENT: usize = 9;
+
+/// Onset strength of each `FRAME_DURATION` frame, the rise of log energy from
+/// the previous frame. Percussive hits rise sharply while sustained sounds do
+/// not, which is why drum stems give much cleaner onsets than full mixes.
+pub fn onset_strengths(samples: &[i16], sample_rate: u32) -> Vec<f32> {
+    let frame_size = ((sample_rate as f32 * FRAME_DURATION) as usize).max(1);
+    let log_energies = samples
+        .chunks(frame_size)
+        .map(|frame| {
+            let energy = frame.iter().map(|s| (*s as f32).powi(2)).sum::<f32>();
+            (energy / frame.len() as f32 + 1.0).ln()
+        })
+        .collect::<Vec<_>>();
+
+    std::iter::once(0.0)
+        .chain(log_energies.windows(2).map(|w| (w[1] - w[0]).max(0.0)))
+        .collect()
+}
+
+/// Draft score of `length` entries from onset strengths, a beat gets a note
+/// if an onset near it stands out from the average. `density` scales the
+/// threshold, higher values keep weaker onsets.
+pub fn draft_score(map: &Map, strengths: &[f32], length: usize, density: f32) -> ScoreData {
+    let tolerance_frames = (BEAT_TOLERANCE / FRAME_DURATION).round() as isize;
+    let beat_strengths = (0..length)
+        .map(|idx| {
+            let frame = (map.beat_time(idx) / FRAME_DURATION).round() as isize;
+            (frame - tolerance_frames..=frame + tolerance_frames)
+                .filter_map(|f| usize::try_from(f).ok())
+                .filter_map(|f| strengths.get(f))
+                .cloned()
+                .fold(0.0, f32::max)
+        })
+        .collect::<Vec<_>>();
+
+    let mean = beat_strengths.iter().sum::<f32>() / length.max(1) as f32;
+    let threshold = mean / density.max(f32::EPSILON);
+
+    let mut noted = beat_strengths
+        .iter()
+        .filter(|s| **s > threshold)
+        .cloned()
+        .collect::<Vec<_>>();
+    noted.sort_by(|a, b| b.total_cmp(a));
+    let heavy_count = (noted.len() as f32 * HEAVY_RATIO) as usize;
+    let heavy_threshold = noted.get(heavy_count).cloned().unwrap_or(f32::MAX);
+
+    let mut entries = Vec::with_capacity(length);
+    let mut segment = 0;
+    for (idx, strength) in beat_strengths.into_iter().enumerate() {
+        let entry = if idx < DEFAULT_MIN_LEAD_IN || strength <= threshold || segment == MAX_SEGMENT
+        {
+            ScoreEntry::B
+        } else if strength > heavy_threshold {
+            ScoreEntry::S
+        } else {
+            ScoreEntry::O
+        };
+
+        segment = if entry == ScoreEntry::B {
+            0
+        } else {
+            segment + 1
+        };
+        entries.push(entry);
+    }
+
+    ScoreData(entries)
+}
+
+/// Count of score entries covering audio of `duration` seconds
+fn entries_in(map: &Map, duration: f32) -> usize {
+    (0..)
+        .take_while(|idx| map.beat_time(*idx) < duration)
+        .count()
+}
+
+/// Drafts a score for `map` from onsets of its audio. With `stem` (e.g. a
+/// drum stem aligned with `music_file`), onsets are detected in it instead,
+/// while the map still plays `music_file`.
+pub fn draft_map_score(map: &Map, stem: Option<&Path>, density: f32) -> std::io::Result<ScoreData> {
+    let song_info = &map.song_info;
+    let audio_files = song_info
+        .intro_file
+        .iter()
+        .map(Path::new)
+        .chain([stem.unwrap_or(Path::new(&song_info.music_file))])
+        .collect::<Vec<_>>();
+
+    let samples = ffmpeg_helper::decode_mono(&audio_files, ANALYSIS_SAMPLE_RATE)?;
+    if samples.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Unable to decode {}", audio_files.last().unwrap().display()),
+        ));
+    }
+
+    let length = match song_info.length {
+        0 => entries_in(map, samples.len() as f32 / ANALYSIS_SAMPLE_RATE as f32),
+        length => length as usize,
+    };
+    let strengths = onset_strengths(&samples, ANALYSIS_SAMPLE_RATE);
+    Ok(draft_score(map, &strengths, length, density))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_draft_score() {
+        // Clicks on every other beat at 120 BPM, 100Hz sample rate
+        let mut samples = vec![0i16; 1000];
+        for beat in (0..20).step_by(2) {
+            samples[beat * 50] = 20000;
+        }
+
+        let mut map = Map::default();
+        map.song_info.bpm = 120.0;
+
+        let strengths = onset_strengths(&samples, 100);
+        let score = draft_score(&map, &strengths, 20, 1.0);
+        assert_eq!(score.to_string(), "----O-O-O-O-O-O-O-O-");
+    }
+}
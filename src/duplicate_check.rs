@@ -0,0 +1,163 @@
+use std::fmt::{Display, Formatter};
+
+use itertools::Itertools;
+
+use crate::map::Map;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SongSource {
+    Official,
+    Pack,
+}
+
+impl Display for SongSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Official => write!(f, "official song"),
+            Self::Pack => write!(f, "pack song"),
+        }
+    }
+}
+
+/// A custom song whose title nearly duplicates another song
+pub struct DuplicateEntry {
+    pub id:           String,
+    pub title:        String,
+    pub other_id:     String,
+    pub other_title:  String,
+    pub other_source: SongSource,
+    /// If the artists are also (nearly) the same, which is most likely the same
+    /// song being added twice
+    pub same_artist:  bool,
+}
+
+impl Display for DuplicateEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}) is similar to {} {} ({})",
+            self.id, self.title, self.other_source, self.other_id, self.other_title
+        )?;
+
+        if self.same_artist {
+            write!(f, ", with the same artist")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Normalizes a title or artist for comparison: full-width ASCII is folded into
+/// half-width, case is ignored and only alphanumeric characters are kept
+fn normalize(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+            _ => c,
+        })
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+pub fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev_row[j] + if ca == cb { 0 } else { 1 };
+            row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        prev_row = row;
+    }
+
+    prev_row[b.len()]
+}
+
+/// Two strings are similar if they are the same after normalization, or only
+/// differ in a few characters for longer strings (1 edit per 8 characters)
+fn is_similar(a: &str, b: &str) -> bool {
+    let a = normalize(a).chars().collect::<Vec<_>>();
+    let b = normalize(b).chars().collect::<Vec<_>>();
+
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+
+    let max_distance = a.len().min(b.len()) / 8;
+    levenshtein(&a, &b) <= max_distance
+}
+
+fn titles(map: &Map) -> Vec<&str> {
+    map.song_info
+        .info_text
+        .values()
+        .map(|t| t.title.as_str())
+        .filter(|t| !t.is_empty())
+        .unique()
+        .collect()
+}
+
+fn artists(map: &Map) -> Vec<&str> {
+    map.song_info
+        .info_text
+        .values()
+        .map(|t| t.artist.as_str())
+        .filter(|a| !a.is_empty())
+        .unique()
+        .collect()
+}
+
+fn find_duplicate(map: &Map, other: &Map, other_source: SongSource) -> Option<DuplicateEntry> {
+    let (title, other_title) = titles(map)
+        .into_iter()
+        .cartesian_product(titles(other))
+        .find(|(a, b)| is_similar(a, b))?;
+
+    let same_artist = artists(map)
+        .into_iter()
+        .cartesian_product(artists(other))
+        .any(|(a, b)| is_similar(a, b));
+
+    Some(DuplicateEntry {
+        id: map.song_info.id.to_string(),
+        title: title.to_owned(),
+        other_id: other.song_info.id.to_string(),
+        other_title: other_title.to_owned(),
+        other_source,
+        same_artist,
+    })
+}
+
+/// Finds custom maps with titles nearly duplicating official songs or other
+/// maps in the same pack
+pub fn find_duplicates(maps: &[Map], official: &[Map]) -> Vec<DuplicateEntry> {
+    let official_duplicates = maps.iter().flat_map(|map| {
+        official
+            .iter()
+            .filter_map(move |other| find_duplicate(map, other, SongSource::Official))
+    });
+
+    let pack_duplicates = maps
+        .iter()
+        .tuple_combinations()
+        .filter_map(|(map, other)| find_duplicate(map, other, SongSource::Pack));
+
+    official_duplicates.chain(pack_duplicates).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_similar() {
+        assert!(is_similar("Bad Apple!!", "bad apple"));
+        assert!(is_similar("Ｂａｄ　Ａｐｐｌｅ！！", "Bad Apple!!"));
+        assert!(is_similar("ナイト・オブ・ナイツ", "ナイトオブナイツ"));
+        assert!(is_similar("Necro Fantasia", "Necro Fantasla"));
+        assert!(!is_similar("Alice", "Alive"));
+        assert!(!is_similar("", ""));
+    }
+}
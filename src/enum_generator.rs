@@ -1,12 +1,8 @@
 mod interop;
-use std::{
-    ffi::{CStr, CString},
-    os::raw::c_char,
-    path::PathBuf,
-};
+use std::{ffi::CStr, os::raw::c_char, path::PathBuf};
 
 use clap::Parser;
-use interop::{DualArrayWrapper, StringWrapper};
+use interop::{DualArrayWrapper, StringWrapper, path_to_cstring};
 
 extern "C" {
     pub fn get_area_music_list(share_data_path: *const c_char) -> DualArrayWrapper;
@@ -22,7 +18,7 @@ struct Args {
 fn main() {
     let args = Args::parse();
 
-    let share_data_path = CString::new(args.share_data_path.to_str().unwrap()).unwrap();
+    let share_data_path = path_to_cstring(&args.share_data_path).unwrap();
 
     let (_result, _musics, _areas, music_array, area_array) = unsafe {
         let result = get_area_music_list(share_data_path.as_ptr());
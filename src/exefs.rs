@@ -2,37 +2,165 @@
 use std::os::unix::prelude::FileExt;
 #[cfg(windows)]
 use std::os::windows::prelude::FileExt;
-use std::{fs::File, path::Path, str::FromStr};
-
+use std::{
+    fs::File,
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub use interop::AssetBundlePatch;
 use interop::patch_main_asset_bundle;
+use log::debug;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    output_sink::{EXEFS_PATCHES_DIR, OutputSink, cheats_dir, romfs_path},
+    platform,
+};
+
 mod interop;
+mod unity_fs;
 
-fn get_build_id(main_exe: &Path) -> [u8; 16] {
-    let mut build_id = [0; 16];
+/// Size of the NSO header, IPS offsets are relative to the start of the file
+/// and thus include it
+const NSO_HEADER_SIZE: u32 = 0x100;
 
-    let main_exe = File::open(main_exe).unwrap();
+fn read_exact_at(main_exe: &Path, buf: &mut [u8], offset: u64) -> std::io::Result<()> {
+    let main_exe = File::open(main_exe)?;
     #[cfg(unix)]
-    main_exe.read_exact_at(&mut build_id, 0x40).unwrap();
+    main_exe.read_exact_at(buf, offset)?;
     #[cfg(windows)]
     {
         let mut bytes_read = 0;
-        while bytes_read < 16 {
-            bytes_read += main_exe
-                .seek_read(&mut build_id[bytes_read..], 0x40 + bytes_read as u64)
-                .unwrap();
+        while bytes_read < buf.len() {
+            let read = main_exe.seek_read(&mut buf[bytes_read..], offset + bytes_read as u64)?;
+            if read == 0 {
+                return Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            bytes_read += read;
         }
     }
 
-    build_id
+    Ok(())
+}
+
+fn get_build_id(main_exe: &Path) -> std::io::Result<[u8; 16]> {
+    let mut build_id = [0; 16];
+    read_exact_at(main_exe, &mut build_id, 0x40)?;
+    Ok(build_id)
+}
+
+/// Memory range of the decompressed text segment, as in the segment header at
+/// 0x10 of the NSO header: file offset, memory offset and decompressed size
+fn text_segment(header: &[u8; 0x20]) -> std::io::Result<Range<u32>> {
+    if &header[0..4] != b"NSO0" {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "The main executable is not an NSO file",
+        ));
+    }
+
+    let read_u32 =
+        |offset: usize| u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap());
+    let memory_offset = read_u32(0x14);
+    let size = read_u32(0x18);
+
+    Ok(memory_offset..memory_offset + size)
+}
+
+/// Checks that `main_exe` is an NSO file with a text segment, returning its
+/// build ID in uppercase hex
+pub fn check_nso(main_exe: &Path) -> std::io::Result<String> {
+    let mut header = [0; 0x20];
+    read_exact_at(main_exe, &mut header, 0)?;
+    text_segment(&header)?;
+    Ok(hex::encode_upper(get_build_id(main_exe)?))
+}
+
+/// Checks that every patched instruction lies in the text segment, so offset
+/// mistakes in the patch config are caught instead of corrupting other data
+fn check_patch_offsets(patches: &IPConfig, text: &Range<u32>) -> std::io::Result<()> {
+    match patches
+        .patches
+        .iter()
+        .find(|p| p.offset < text.start || p.offset.saturating_add(4) > text.end)
+    {
+        Some(patch) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "ExeFS patch offset {:#X} is outside of the text segment {:#X}..{:#X}, the patch \
+                 config does not match this game build",
+                patch.offset, text.start, text.end
+            ),
+        )),
+        None => Ok(()),
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 struct IPConfig {
+    /// Game builds the patch offsets are found in, the build of the given
+    /// main executable is only warned about as unverified if this is empty
+    #[serde(default)]
+    builds:  Vec<SupportedBuild>,
     patches: Vec<InstructionPatch>,
 }
 
+#[derive(Serialize, Deserialize)]
+struct SupportedBuild {
+    /// Game version shown to users, e.g. 1.2.0
+    version:  String,
+    /// Build ID of the main executable in uppercase hex
+    build_id: String,
+}
+
+fn patch_config() -> IPConfig {
+    toml::from_str(include_str!("exefs_patches.toml")).unwrap()
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error(
+    "The game build {build_id} is not supported, the ExeFS patch would not apply to it and new \
+     songs would not appear in game. Supported game versions: {supported}. Dump ExeFS and RomFS \
+     from a supported version (install the base game with the matching update), or wait for a \
+     mod_tool release supporting this game version."
+)]
+pub struct UnsupportedBuildError {
+    build_id:  String,
+    supported: String,
+}
+
+/// Checks that the patch config is made for the build of `main_exe`, so that
+/// generating mods for an updated game fails early instead of producing an
+/// IPS patch the game ignores
+pub fn check_build(main_exe: &Path) -> anyhow::Result<()> {
+    let build_id = hex::encode_upper(get_build_id(main_exe)?);
+    Ok(check_config_build(&patch_config(), build_id)?)
+}
+
+fn check_config_build(patches: &IPConfig, build_id: String) -> Result<(), UnsupportedBuildError> {
+    let builds = &patches.builds;
+
+    if builds.iter().any(|b| b.build_id == build_id) {
+        Ok(())
+    } else {
+        let supported = if builds.is_empty() {
+            "none recorded".to_owned()
+        } else {
+            builds
+                .iter()
+                .map(|b| format!("{} ({})", b.version, b.build_id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        Err(UnsupportedBuildError {
+            build_id,
+            supported,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct InstructionPatch {
     /// IPS32 file format only allows 4-bytes offset
@@ -94,8 +222,11 @@ impl TryFrom<&str> for AArch64Instruction {
             .flat_map(|s| s.trim().split(' '))
             .filter(|s| !s.is_empty())
             .collect::<Vec<_>>();
+        let [op_code, register, immediate] = split[..] else {
+            return Err(format!("{value} is not of the form \"OP Wn, #imm\""));
+        };
 
-        let immediate = split[2].strip_prefix('#').unwrap();
+        let immediate = immediate.strip_prefix('#').unwrap_or(immediate);
         let immediate = if immediate.starts_with("0X") {
             u16::from_str_radix(immediate.strip_prefix("0X").unwrap(), 16)
                 .map_err(|e| format!("{:?}", e))?
@@ -104,10 +235,10 @@ impl TryFrom<&str> for AArch64Instruction {
         };
 
         Ok(Self {
-            op_code: AArch64AssemblyOpCode::from_str(split[0]).map_err(|e| format!("{:?}", e))?,
-            w_register_id: split[1]
+            op_code: AArch64AssemblyOpCode::from_str(op_code).map_err(|e| format!("{:?}", e))?,
+            w_register_id: register
                 .strip_prefix('W')
-                .unwrap()
+                .ok_or(format!("Register {register} is not a W register"))?
                 .parse()
                 .map_err(|e| format!("{:?}", e))?,
             immediate,
@@ -235,18 +366,26 @@ impl InstructionPatch {
     }
 }
 
-fn generate_ips_file(main_exe: &Path, out_dir: &Path, immediate_offset: i16) {
-    let mod_name = out_dir.file_name().unwrap().to_string_lossy().to_string();
-    let mut out_ips_path = out_dir.to_owned();
-    out_ips_path.push("exefs_patches");
-    out_ips_path.push(mod_name);
-    std::fs::create_dir_all(&out_ips_path).unwrap();
-
-    let build_id = get_build_id(main_exe);
-    out_ips_path.push(format!("{}.ips", hex::encode_upper(build_id)));
+/// The mod name used when none is provided, which is the name of output folder
+pub fn default_mod_name(out_dir: &Path) -> String {
+    out_dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or("spell_bubble_mod".to_owned())
+}
 
-    let patches: IPConfig = toml::from_str(include_str!("exefs_patches.toml")).unwrap();
+/// Form of the generated ExeFS instruction overrides
+#[derive(strum::EnumString, strum::Display, Clone, Copy, Debug, Default)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
+pub enum ExefsPatchFormat {
+    /// IPS32 patch applied by the loader on every launch
+    #[default]
+    Ips,
+    /// Atmosphère cheat, which can be toggled at runtime
+    Cheat,
+}
 
+fn ips_content(patches: &IPConfig, immediate_offset: i16) -> Vec<u8> {
     let mut ips_content = "IPS32".as_bytes().to_vec();
 
     let mut ips_patch_bytes = patches
@@ -255,7 +394,7 @@ fn generate_ips_file(main_exe: &Path, out_dir: &Path, immediate_offset: i16) {
         .flat_map(|p| {
             let mut out_bytes = [0; 10];
 
-            let offset = p.offset + 0x100;
+            let offset = p.offset + NSO_HEADER_SIZE;
             out_bytes[0..4].copy_from_slice(&offset.to_be_bytes());
             out_bytes[5] = 0x04;
 
@@ -269,33 +408,126 @@ fn generate_ips_file(main_exe: &Path, out_dir: &Path, immediate_offset: i16) {
     ips_content.append(&mut ips_patch_bytes);
     ips_content.extend_from_slice("EEOF".as_bytes());
 
-    std::fs::write(out_ips_path, ips_content).unwrap();
+    ips_content
 }
 
-pub fn patch_files(
-    romfs_root: &Path,
-    main_exe_path: &Path,
-    outdir: &Path,
-    names: &[impl AsRef<str>],
-) {
-    let mut metadata_path = romfs_root.to_owned();
-    metadata_path.push("Managed/Metadata/global-metadata.dat");
+/// Cheat with a 4-byte static store into the main module (code type 0) for
+/// each patched instruction
+fn cheat_content(patches: &IPConfig, mod_name: &str, immediate_offset: i16) -> String {
+    let mut content = format!("[{mod_name}]\n");
+
+    for p in &patches.patches {
+        let offset = p.offset as u64;
+        content.push_str(&format!(
+            "040000{:02X} {:08X} {:08X}\n",
+            offset >> 32,
+            offset & 0xFFFF_FFFF,
+            p.patch_immediate(immediate_offset)
+        ));
+    }
 
-    let mut out_base_path = outdir.to_owned();
-    out_base_path.push("contents/0100E9D00D6C2000/romfs/Data");
-    let mut out_metadata_path = out_base_path.to_owned();
-    out_metadata_path.push("Managed/Metadata");
-    std::fs::create_dir_all(&out_metadata_path).unwrap();
-    out_metadata_path.push("global-metadata.dat");
+    content
+}
+
+fn generate_exefs_patch(
+    main_exe: &Path,
+    patches: &IPConfig,
+    sink: &dyn OutputSink,
+    mod_name: &str,
+    immediate_offset: i16,
+    format: ExefsPatchFormat,
+) -> std::io::Result<()> {
+    let build_id = hex::encode_upper(get_build_id(main_exe)?);
+    debug!(
+        "Generating {format} ExeFS patch for build {build_id} with immediate offset \
+         {immediate_offset}"
+    );
+
+    let mut header = [0; 0x20];
+    read_exact_at(main_exe, &mut header, 0)?;
+    check_patch_offsets(patches, &text_segment(&header)?)?;
+
+    match format {
+        ExefsPatchFormat::Ips => {
+            let mut out_ips_path = PathBuf::from(EXEFS_PATCHES_DIR);
+            out_ips_path.push(mod_name);
+            out_ips_path.push(format!("{build_id}.ips"));
+
+            sink.write(&out_ips_path, &ips_content(patches, immediate_offset))
+        }
+        ExefsPatchFormat::Cheat => {
+            // Cheat files are named by the first 8 bytes of build ID
+            let mut out_cheat_path = cheats_dir();
+            out_cheat_path.push(format!("{}.txt", &build_id[..16]));
 
-    let entries_count = interop::add_emusic_id_enums(&metadata_path, &out_metadata_path, names);
-    generate_ips_file(main_exe_path, outdir, entries_count as i16);
+            let content = cheat_content(patches, mod_name, immediate_offset);
+            sink.write(&out_cheat_path, content.as_bytes())
+        }
+    }
+}
+
+/// Generates an ExeFS patch of `main_exe` from a user patch config in the
+/// format of the built-in one, without patching other game files. Immediates
+/// of patches not marked `override_patch` are offset by `immediate_offset`.
+/// Returns the build ID the patch is generated for.
+pub fn generate_user_patch(
+    main_exe: &Path,
+    patch_config_path: &Path,
+    sink: &dyn OutputSink,
+    mod_name: &str,
+    immediate_offset: i16,
+    format: ExefsPatchFormat,
+) -> anyhow::Result<String> {
+    let patches: IPConfig = toml::from_str(&std::fs::read_to_string(patch_config_path)?)?;
+    if patches.patches.is_empty() {
+        anyhow::bail!("The patch config has no patches");
+    }
 
-    let mut main_ab_path = romfs_root.to_owned();
-    main_ab_path.push("StreamingAssets/Switch/Switch");
-    let mut out_ab_path = out_base_path.to_owned();
-    out_ab_path.push("StreamingAssets/Switch/Switch");
+    let build_id = hex::encode_upper(get_build_id(main_exe)?);
+    check_config_build(&patches, build_id.clone())?;
+    generate_exefs_patch(main_exe, &patches, sink, mod_name, immediate_offset, format)?;
 
+    Ok(build_id)
+}
+
+pub fn patch_files(
+    romfs_root: &Path,
+    main_exe_path: &Path,
+    sink: &dyn OutputSink,
+    mod_name: &str,
+    names: &[impl AsRef<str>],
+    format: ExefsPatchFormat,
+) -> std::io::Result<AssetBundlePatch> {
+    let platform = platform::current();
+
+    let metadata_path = romfs_root.join(platform.global_metadata());
+    let out_metadata_path = sink.file_path(&romfs_path(platform.global_metadata()))?;
+
+    debug!(
+        "Adding {} music IDs to {} into {}",
+        names.len(),
+        metadata_path.display(),
+        out_metadata_path.display()
+    );
+    let entries_count = interop::add_emusic_id_enums(&metadata_path, &out_metadata_path, names)?;
+    debug!("EMusicID has {entries_count} entries after patching");
+    generate_exefs_patch(
+        main_exe_path,
+        &patch_config(),
+        sink,
+        mod_name,
+        entries_count as i16,
+        format,
+    )?;
+
+    let main_ab_path = romfs_root.join(platform.main_bundle());
+    let out_ab_path = sink.file_path(&romfs_path(platform.main_bundle()))?;
+
+    debug!(
+        "Patching asset bundle {} into {}",
+        main_ab_path.display(),
+        out_ab_path.display()
+    );
     patch_main_asset_bundle(&main_ab_path, &out_ab_path, names)
 }
 
@@ -306,6 +538,10 @@ mod test {
     #[test]
     fn generate_example_config() {
         let config = IPConfig {
+            builds:  vec![SupportedBuild {
+                version:  "1.0.0".to_owned(),
+                build_id: "0".repeat(32),
+            }],
             patches: vec![InstructionPatch {
                 offset:         0,
                 instruction:    AArch64Instruction::default(),
@@ -316,6 +552,64 @@ mod test {
         println!("{}", toml::to_string_pretty(&config).unwrap());
     }
 
+    #[test]
+    fn test_check_config_build() {
+        let mut config = IPConfig {
+            builds:  vec![],
+            patches: vec![],
+        };
+        assert!(check_config_build(&config, "A".repeat(32)).is_err());
+
+        config.builds.push(SupportedBuild {
+            version:  "1.0.0".to_owned(),
+            build_id: "0".repeat(32),
+        });
+        assert!(check_config_build(&config, "0".repeat(32)).is_ok());
+        assert!(check_config_build(&config, "A".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_check_patch_offsets() {
+        let mut header = [0; 0x20];
+        header[0..4].copy_from_slice(b"NSO0");
+        header[0x18..0x1C].copy_from_slice(&0x1000u32.to_le_bytes());
+        let text = text_segment(&header).unwrap();
+        assert_eq!(text, 0..0x1000);
+
+        let config = |offset| IPConfig {
+            builds:  vec![],
+            patches: vec![InstructionPatch {
+                offset,
+                instruction: AArch64Instruction::default(),
+                override_patch: false,
+            }],
+        };
+        assert!(check_patch_offsets(&config(0xFFC), &text).is_ok());
+        assert!(check_patch_offsets(&config(0xFFE), &text).is_err());
+        assert!(check_patch_offsets(&config(0x2000), &text).is_err());
+
+        header[0] = b'X';
+        assert!(text_segment(&header).is_err());
+    }
+
+    #[test]
+    fn test_cheat_content() {
+        let config = IPConfig {
+            builds:  vec![],
+            patches: vec![InstructionPatch {
+                offset:         0x017D9838,
+                instruction:    "mov w0, #0x134".try_into().unwrap(),
+                override_patch: false,
+            }],
+        };
+
+        let expected = config.patches[0].patch_immediate(2);
+        assert_eq!(
+            cheat_content(&config, "mod", 2),
+            format!("[mod]\n04000000 017D9838 {expected:08X}\n")
+        );
+    }
+
     #[test]
     fn test_patch_instruction() {
         let ip = InstructionPatch {
@@ -328,6 +622,15 @@ mod test {
         assert_eq!(ip.patch_immediate(16), 0x7104829F);
     }
 
+    #[test]
+    fn test_parse_instruction() {
+        assert!(AArch64Instruction::try_from("MOV W0, #0x134").is_ok());
+        assert!(AArch64Instruction::try_from("MOV W0").is_err());
+        assert!(AArch64Instruction::try_from("MOV X0, #0x134").is_err());
+        assert!(AArch64Instruction::try_from("MOV W0, 0x134").is_ok());
+        assert!(AArch64Instruction::try_from("ADD W0, #0x134").is_err());
+    }
+
     #[test]
     fn test_b_instruction() {
         let ip = InstructionPatch {
@@ -1,9 +1,16 @@
 use std::{
     ffi::{CString, c_char, c_int, c_void},
-    path::Path,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
 };
 
-use crate::interop::ArrayWrapper;
+use log::warn;
+
+use super::unity_fs;
+use crate::{
+    interop::{ArrayWrapper, path_to_cstring},
+    map::lock_helper,
+};
 
 #[allow(non_snake_case)]
 #[repr(C)]
@@ -116,7 +123,7 @@ pub fn add_emusic_id_enums<T, U>(
     global_metadata_path: &Path,
     out_metadata_path: &Path,
     names: T,
-) -> usize
+) -> std::io::Result<usize>
 where
     T: IntoIterator<Item = U>,
     U: AsRef<str>,
@@ -124,11 +131,11 @@ where
     let enums_to_add = names.into_iter().collect::<Vec<_>>();
     let enums_to_add = enums_to_add.iter().map(|s| s.as_ref()).collect::<Vec<_>>();
 
-    let global_metadata_path_c =
-        CString::new(global_metadata_path.to_string_lossy().as_ref()).unwrap();
+    let global_metadata_path_c = path_to_cstring(global_metadata_path)?;
+    let _helper = lock_helper();
     let metadata_info = unsafe { get_metadata_regions(global_metadata_path_c.as_ptr()) };
 
-    let mut metadata_file = std::fs::read(global_metadata_path).unwrap();
+    let mut metadata_file = std::fs::read(global_metadata_path)?;
     let mut string_table = metadata_file[metadata_info.string_table_offset as usize
         ..metadata_info.string_table_offset as usize + metadata_info.string_table_length as usize]
         .to_vec();
@@ -318,9 +325,9 @@ where
             value_data_slice.copy_from_slice(&value_data_data.to_le_bytes());
         });
 
-    std::fs::write(out_metadata_path, metadata_file).unwrap();
+    std::fs::write(out_metadata_path, metadata_file)?;
 
-    enums_to_add.len()
+    Ok(enums_to_add.len())
 }
 
 extern "C" {
@@ -331,22 +338,71 @@ extern "C" {
     );
 }
 
-pub fn patch_main_asset_bundle<T, U>(main_ab_path: &Path, out_ab_path: &Path, added_song_ids: T)
+/// The patched main asset bundle, shown to users to verify that the bundle is
+/// actually written. The helper library reports nothing back, so CAB and
+/// container names are read from the written bundle.
+#[derive(Debug)]
+pub struct AssetBundlePatch {
+    pub path:       PathBuf,
+    pub size:       u64,
+    pub song_ids:   Vec<String>,
+    pub cab_names:  Vec<String>,
+    pub containers: Vec<String>,
+}
+
+impl Display for AssetBundlePatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Patched asset bundle {} ({} bytes) for {} new songs",
+            self.path.display(),
+            self.size,
+            self.song_ids.len()
+        )?;
+        for song_id in &self.song_ids {
+            write!(f, "\n  {song_id}")?;
+        }
+        write!(f, "\nCAB: {}", self.cab_names.join(", "))?;
+        write!(f, "\nContainer ({} assets):", self.containers.len())?;
+        for container in &self.containers {
+            write!(f, "\n  {container}")?;
+        }
+        Ok(())
+    }
+}
+
+pub fn patch_main_asset_bundle<T, U>(
+    main_ab_path: &Path,
+    out_ab_path: &Path,
+    added_song_ids: T,
+) -> std::io::Result<AssetBundlePatch>
 where
     T: IntoIterator<Item = U>,
     U: AsRef<str>,
 {
-    let main_ab_path = CString::new(main_ab_path.to_string_lossy().to_string()).unwrap();
-    let out_ab_path = CString::new(out_ab_path.to_string_lossy().to_string()).unwrap();
-    let added_song_ids = added_song_ids
+    let song_ids = added_song_ids
         .into_iter()
-        .map(|s| CString::new(s.as_ref()).unwrap())
+        .map(|s| s.as_ref().to_owned())
+        .collect::<Vec<_>>();
+
+    let main_ab_path_c = path_to_cstring(main_ab_path)?;
+    let out_ab_path_c = path_to_cstring(out_ab_path)?;
+    let added_song_ids = song_ids
+        .iter()
+        .map(|s| CString::new(s.as_str()).unwrap())
         .collect::<Vec<_>>();
     let added_song_ids = added_song_ids
         .iter()
         .map(|cs| cs.as_ptr())
         .collect::<Vec<_>>();
 
+    // The output may be left from an earlier run in the same folder, remove it
+    // so that a bundle the helper failed to write is not taken as patched
+    if out_ab_path.exists() {
+        std::fs::remove_file(out_ab_path)?;
+    }
+
+    let helper = lock_helper();
     unsafe {
         let added_song_ids = ArrayWrapper {
             managed: 0,
@@ -354,9 +410,33 @@ where
             array:   std::mem::transmute::<*const *const i8, *mut c_void>(added_song_ids.as_ptr()),
         };
         patch_main_asset_bundle_internal(
-            main_ab_path.as_ptr(),
-            out_ab_path.as_ptr(),
+            main_ab_path_c.as_ptr(),
+            out_ab_path_c.as_ptr(),
             added_song_ids,
         )
     }
+    drop(helper);
+
+    let size = match std::fs::metadata(out_ab_path) {
+        Ok(metadata) if metadata.len() > 0 => metadata.len(),
+        _ => {
+            return Err(std::io::Error::other(format!(
+                "Failed to patch the main asset bundle, {} is not written",
+                out_ab_path.display()
+            )));
+        }
+    };
+
+    let contents = unity_fs::read_bundle_contents(out_ab_path).unwrap_or_else(|e| {
+        warn!("Unable to read CAB names of {}: {e}", out_ab_path.display());
+        Default::default()
+    });
+
+    Ok(AssetBundlePatch {
+        path: out_ab_path.to_owned(),
+        size,
+        song_ids,
+        cab_names: contents.cab_names,
+        containers: contents.containers,
+    })
 }
@@ -0,0 +1,463 @@
+use std::{
+    io::{Error, ErrorKind},
+    path::Path,
+};
+
+/// Files in a bundle and the asset paths it exposes, read from a written
+/// bundle as the helper library reports nothing about it
+#[derive(Debug, Default, PartialEq)]
+pub struct BundleContents {
+    /// Names of the files in the bundle, `CAB-` followed by a hash
+    pub cab_names:  Vec<String>,
+    /// Asset paths in the container of the AssetBundle objects
+    pub containers: Vec<String>,
+}
+
+/// Compression of blocks info and blocks, LZMA is not supported
+const COMPRESSION_MASK: u32 = 0x3F;
+const COMPRESSION_NONE: u32 = 0;
+const COMPRESSION_LZ4: u32 = 2;
+const COMPRESSION_LZ4HC: u32 = 3;
+const BLOCKS_INFO_AT_THE_END: u32 = 0x80;
+const BLOCK_INFO_NEED_PADDING_AT_START: u32 = 0x200;
+/// Node flag of serialized files, other nodes are resources
+const NODE_SERIALIZED_FILE: u32 = 4;
+
+const CLASS_ID_MONO_BEHAVIOUR: i32 = 114;
+const CLASS_ID_ASSET_BUNDLE: i32 = 142;
+
+fn invalid_data(message: impl Into<String>) -> Error {
+    Error::new(ErrorKind::InvalidData, message.into())
+}
+
+struct Reader<'a> {
+    data:          &'a [u8],
+    pos:           usize,
+    little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            little_endian: false,
+        }
+    }
+
+    fn bytes(&mut self, len: usize) -> std::io::Result<&'a [u8]> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| invalid_data("Unexpected end of asset bundle"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> std::io::Result<[u8; N]> {
+        let mut bytes: [u8; N] = self.bytes(N)?.try_into().unwrap();
+        if !self.little_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> std::io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> std::io::Result<u16> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> std::io::Result<u32> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> std::io::Result<i32> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> std::io::Result<i64> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn count(&mut self) -> std::io::Result<usize> {
+        usize::try_from(self.i32()?).map_err(|_| invalid_data("Negative count in asset bundle"))
+    }
+
+    fn offset(&mut self) -> std::io::Result<usize> {
+        usize::try_from(self.i64()?).map_err(|_| invalid_data("Negative offset in asset bundle"))
+    }
+
+    fn null_terminated_string(&mut self) -> std::io::Result<String> {
+        let len = self.data[self.pos.min(self.data.len())..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| invalid_data("Unterminated string in asset bundle"))?;
+        let string = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.pos += 1;
+        Ok(string)
+    }
+
+    /// String of serialized objects, prefixed by its length and padded to 4
+    /// bytes
+    fn aligned_string(&mut self) -> std::io::Result<String> {
+        let len = self.count()?;
+        let string = String::from_utf8_lossy(self.bytes(len)?).into_owned();
+        self.align(4);
+        Ok(string)
+    }
+
+    fn align(&mut self, alignment: usize) {
+        self.pos = self.pos.next_multiple_of(alignment);
+    }
+}
+
+/// Decompresses an LZ4 block, which has no frame header and is as large as
+/// recorded in the blocks info
+fn lz4_decompress(input: &[u8], uncompressed_size: usize) -> std::io::Result<Vec<u8>> {
+    // Lengths of 15 in the token continue in the following bytes
+    fn length(reader: &mut Reader, nibble: u8) -> std::io::Result<usize> {
+        let mut len = nibble as usize;
+        if nibble == 0xF {
+            loop {
+                let byte = reader.u8()?;
+                len += byte as usize;
+                if byte != 0xFF {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    let mut reader = Reader::new(input);
+    let mut output = Vec::with_capacity(uncompressed_size);
+
+    loop {
+        let token = reader.u8()?;
+        let literal_len = length(&mut reader, token >> 4)?;
+        output.extend_from_slice(reader.bytes(literal_len)?);
+        if reader.pos == input.len() {
+            break;
+        }
+
+        let offset = u16::from_le_bytes(reader.bytes(2)?.try_into().unwrap()) as usize;
+        let match_len = length(&mut reader, token & 0xF)? + 4;
+        if offset == 0 || offset > output.len() {
+            return Err(invalid_data("Invalid LZ4 match offset in asset bundle"));
+        }
+        // Matches may overlap the bytes they produce, so copy one by one
+        let start = output.len() - offset;
+        for i in 0..match_len {
+            output.push(output[start + i]);
+        }
+    }
+
+    if output.len() == uncompressed_size {
+        Ok(output)
+    } else {
+        Err(invalid_data("LZ4 block of asset bundle has wrong size"))
+    }
+}
+
+fn decompress(data: &[u8], flags: u32, uncompressed_size: usize) -> std::io::Result<Vec<u8>> {
+    match flags & COMPRESSION_MASK {
+        COMPRESSION_NONE => Ok(data.to_vec()),
+        COMPRESSION_LZ4 | COMPRESSION_LZ4HC => lz4_decompress(data, uncompressed_size),
+        compression => Err(invalid_data(format!(
+            "Asset bundle compression {compression} is not supported"
+        ))),
+    }
+}
+
+/// Reads the names of files in a UnityFS bundle, and the container of the
+/// AssetBundle objects in them
+pub fn read_bundle_contents(path: &Path) -> std::io::Result<BundleContents> {
+    parse_bundle(&std::fs::read(path)?)
+}
+
+fn parse_bundle(bundle: &[u8]) -> std::io::Result<BundleContents> {
+    let mut reader = Reader::new(bundle);
+
+    if reader.null_terminated_string()? != "UnityFS" {
+        return Err(invalid_data("Not a UnityFS asset bundle"));
+    }
+    let version = reader.u32()?;
+    reader.null_terminated_string()?;
+    reader.null_terminated_string()?;
+    reader.i64()?;
+    let compressed_blocks_info_size = reader.u32()? as usize;
+    let uncompressed_blocks_info_size = reader.u32()? as usize;
+    let flags = reader.u32()?;
+
+    if version >= 7 {
+        reader.align(16);
+    }
+    let blocks_info = if flags & BLOCKS_INFO_AT_THE_END != 0 {
+        let start = bundle
+            .len()
+            .checked_sub(compressed_blocks_info_size)
+            .ok_or_else(|| invalid_data("Unexpected end of asset bundle"))?;
+        &bundle[start..]
+    } else {
+        reader.bytes(compressed_blocks_info_size)?
+    };
+    let blocks_info = decompress(blocks_info, flags, uncompressed_blocks_info_size)?;
+    if flags & BLOCK_INFO_NEED_PADDING_AT_START != 0 {
+        reader.align(16);
+    }
+
+    let mut info_reader = Reader::new(&blocks_info);
+    info_reader.bytes(16)?;
+    let mut data = vec![];
+    for _ in 0..info_reader.count()? {
+        let uncompressed_size = info_reader.u32()? as usize;
+        let compressed_size = info_reader.u32()? as usize;
+        let block_flags = info_reader.u16()? as u32;
+        data.extend(decompress(
+            reader.bytes(compressed_size)?,
+            block_flags,
+            uncompressed_size,
+        )?);
+    }
+
+    let mut contents = BundleContents::default();
+    for _ in 0..info_reader.count()? {
+        let offset = info_reader.offset()?;
+        let size = info_reader.offset()?;
+        let node_flags = info_reader.u32()?;
+        let name = info_reader.null_terminated_string()?;
+
+        if node_flags & NODE_SERIALIZED_FILE != 0 {
+            let file = offset
+                .checked_add(size)
+                .and_then(|end| data.get(offset..end))
+                .ok_or_else(|| invalid_data(format!("File {name} exceeds the asset bundle")))?;
+            contents.containers.extend(read_containers(file)?);
+        }
+        contents.cab_names.push(name);
+    }
+
+    Ok(contents)
+}
+
+/// Reads the container of AssetBundle objects in a serialized file of Unity
+/// 5.5 or later
+fn read_containers(file: &[u8]) -> std::io::Result<Vec<String>> {
+    let mut reader = Reader::new(file);
+
+    reader.u32()?;
+    reader.u32()?;
+    let version = reader.u32()?;
+    let mut data_offset = reader.u32()? as usize;
+    if version < 14 {
+        return Err(invalid_data(format!(
+            "Serialized file version {version} is not supported"
+        )));
+    }
+    let little_endian = reader.u8()? == 0;
+    reader.bytes(3)?;
+    if version >= 22 {
+        reader.u32()?;
+        reader.i64()?;
+        data_offset = reader.offset()?;
+        reader.i64()?;
+    }
+
+    reader.little_endian = little_endian;
+    reader.null_terminated_string()?;
+    reader.i32()?;
+    let enable_type_tree = reader.u8()? != 0;
+
+    let mut class_ids = vec![];
+    for _ in 0..reader.count()? {
+        let class_id = reader.i32()?;
+        if version >= 16 {
+            reader.u8()?;
+        }
+        if version >= 17 {
+            reader.u16()?;
+        }
+        if (version < 16 && class_id < 0) || (version >= 16 && class_id == CLASS_ID_MONO_BEHAVIOUR)
+        {
+            reader.bytes(16)?;
+        }
+        reader.bytes(16)?;
+        if enable_type_tree {
+            let node_count = reader.count()?;
+            let string_buffer_size = reader.count()?;
+            let node_size = if version >= 19 { 32 } else { 24 };
+            reader.bytes(node_count * node_size + string_buffer_size)?;
+            if version >= 21 {
+                let dependency_count = reader.count()?;
+                reader.bytes(dependency_count * 4)?;
+            }
+        }
+        class_ids.push(class_id);
+    }
+
+    let mut containers = vec![];
+    for _ in 0..reader.count()? {
+        reader.align(4);
+        reader.i64()?;
+        let start = if version >= 22 {
+            reader.offset()?
+        } else {
+            reader.u32()? as usize
+        };
+        reader.u32()?;
+        let type_index = reader.count()?;
+        // Before version 16, objects carry their class ID instead of a type
+        let old_class_id = if version < 16 {
+            Some(reader.u16()? as i32)
+        } else {
+            None
+        };
+        if version < 17 {
+            reader.u16()?;
+        }
+        if version == 15 || version == 16 {
+            reader.u8()?;
+        }
+
+        let class_id = match old_class_id {
+            Some(class_id) => class_id,
+            None => *class_ids
+                .get(type_index)
+                .ok_or_else(|| invalid_data("Object of unknown type in asset bundle"))?,
+        };
+        if class_id == CLASS_ID_ASSET_BUNDLE {
+            let mut object_reader = Reader {
+                data:          file,
+                pos:           data_offset + start,
+                little_endian: reader.little_endian,
+            };
+            containers.extend(read_asset_bundle_container(&mut object_reader)?);
+        }
+    }
+
+    Ok(containers)
+}
+
+fn read_asset_bundle_container(reader: &mut Reader) -> std::io::Result<Vec<String>> {
+    // m_Name
+    reader.aligned_string()?;
+    // m_PreloadTable of PPtr, file ID and path ID
+    let preload_count = reader.count()?;
+    reader.bytes(preload_count * 12)?;
+
+    // m_Container of asset paths and AssetInfo, two indices and a PPtr
+    (0..reader.count()?)
+        .map(|_| {
+            let path = reader.aligned_string()?;
+            reader.bytes(20)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A serialized file of version 22 without type trees, holding an
+    /// AssetBundle object
+    fn serialized_file(containers: &[&str]) -> Vec<u8> {
+        let aligned_string = |out: &mut Vec<u8>, s: &str| {
+            out.extend((s.len() as i32).to_le_bytes());
+            out.extend(s.as_bytes());
+            out.resize(out.len().next_multiple_of(4), 0);
+        };
+
+        let mut object = vec![];
+        aligned_string(&mut object, "main");
+        object.extend(0i32.to_le_bytes());
+        object.extend((containers.len() as i32).to_le_bytes());
+        for container in containers {
+            aligned_string(&mut object, container);
+            object.extend([0; 20]);
+        }
+
+        let mut metadata = b"2019.4.0f1\0".to_vec();
+        metadata.extend(0i32.to_le_bytes());
+        metadata.push(0);
+        metadata.extend(1i32.to_le_bytes());
+        metadata.extend(CLASS_ID_ASSET_BUNDLE.to_le_bytes());
+        metadata.push(0);
+        metadata.extend((-1i16).to_le_bytes());
+        metadata.extend([0; 16]);
+        metadata.extend(1i32.to_le_bytes());
+
+        // Objects are aligned to 4 bytes from the start of the file, which has a
+        // header of 48 bytes
+        let header_size = 48;
+        metadata.resize(metadata.len().next_multiple_of(4), 0);
+        metadata.extend(1i64.to_le_bytes());
+        metadata.extend(0i64.to_le_bytes());
+        metadata.extend((object.len() as u32).to_le_bytes());
+        metadata.extend(0i32.to_le_bytes());
+
+        let data_offset = (header_size + metadata.len()) as i64;
+        let mut file = vec![0; 8];
+        file.extend(22u32.to_be_bytes());
+        file.extend([0; 4]);
+        file.extend([0; 4]);
+        file.extend((metadata.len() as u32).to_be_bytes());
+        file.extend(0i64.to_be_bytes());
+        file.extend(data_offset.to_be_bytes());
+        file.extend(0i64.to_be_bytes());
+        file.extend(metadata);
+        file.extend(object);
+        file
+    }
+
+    fn bundle(file: &[u8]) -> Vec<u8> {
+        let mut blocks_info = vec![0; 16];
+        blocks_info.extend(1i32.to_be_bytes());
+        blocks_info.extend((file.len() as u32).to_be_bytes());
+        blocks_info.extend((file.len() as u32).to_be_bytes());
+        blocks_info.extend(0u16.to_be_bytes());
+        blocks_info.extend(1i32.to_be_bytes());
+        blocks_info.extend(0i64.to_be_bytes());
+        blocks_info.extend((file.len() as i64).to_be_bytes());
+        blocks_info.extend(NODE_SERIALIZED_FILE.to_be_bytes());
+        blocks_info.extend(b"CAB-0123456789abcdef\0");
+
+        let mut bundle = b"UnityFS\0".to_vec();
+        bundle.extend(6u32.to_be_bytes());
+        bundle.extend(b"5.x.x\0");
+        bundle.extend(b"2019.4.0f1\0");
+        bundle.extend(0i64.to_be_bytes());
+        bundle.extend((blocks_info.len() as u32).to_be_bytes());
+        bundle.extend((blocks_info.len() as u32).to_be_bytes());
+        bundle.extend(0u32.to_be_bytes());
+        bundle.extend(blocks_info);
+        bundle.extend(file);
+        bundle
+    }
+
+    #[test]
+    fn test_parse_bundle() {
+        let file = serialized_file(&["assets/music/karisuma.asset", "assets/music/new.asset"]);
+        let contents = parse_bundle(&bundle(&file)).unwrap();
+
+        assert_eq!(contents.cab_names, ["CAB-0123456789abcdef"]);
+        assert_eq!(
+            contents.containers,
+            ["assets/music/karisuma.asset", "assets/music/new.asset"]
+        );
+        assert!(parse_bundle(b"UnityWeb\0").is_err());
+    }
+
+    #[test]
+    fn test_lz4_decompress() {
+        // Literal "ab", then a match of 6 bytes at offset 2
+        let compressed = [0x22, b'a', b'b', 0x02, 0x00, 0x10, b'c'];
+        assert_eq!(lz4_decompress(&compressed, 9).unwrap(), b"ababababc");
+        assert!(lz4_decompress(&compressed, 10).is_err());
+        assert!(lz4_decompress(&[0x10, b'a', 0x05, 0x00], 5).is_err());
+    }
+}
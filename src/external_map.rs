@@ -1,5 +1,9 @@
 pub mod adofai;
 mod osu;
+mod paste;
+mod tempo_map;
 
 pub use adofai::*;
 pub use osu::*;
+pub use paste::*;
+pub use tempo_map::*;
@@ -17,8 +17,14 @@ pub struct ADoFaIMap {
 
 #[derive(Deserialize)]
 struct MapSettings {
-    bpm:    f32,
-    offset: i32,
+    bpm:           f32,
+    offset:        i32,
+    #[serde(default)]
+    song:          String,
+    #[serde(default)]
+    artist:        String,
+    #[serde(default, alias = "songFilename")]
+    song_filename: String,
 }
 
 #[derive(Deserialize)]
@@ -83,6 +89,31 @@ impl MapAction {
     }
 }
 
+/// Scores of a bare `actions` array copied from a level, ending at the floor of
+/// the last note as the track is not known
+pub fn action_scores(actions_json: &str) -> serde_json::Result<Vec<ScoreEntry>> {
+    let actions: Vec<MapAction> = serde_json::from_str(actions_json)?;
+    let notes = actions
+        .iter()
+        .filter_map(MapAction::to_parsed)
+        .filter_map(|parsed| match parsed.action {
+            ActionType::Note(entry) if parsed.id > 0 => Some((parsed.id as usize, entry)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let len = notes
+        .iter()
+        .map(|(floor, _)| *floor)
+        .max()
+        .unwrap_or_default();
+    let mut scores = vec![ScoreEntry::B; len];
+    for (floor, entry) in notes {
+        scores[floor - 1] = entry;
+    }
+    Ok(scores)
+}
+
 impl ADoFaIMap {
     fn parse_actions(&mut self) {
         self.parsed_actions = self
@@ -105,6 +136,21 @@ impl ADoFaIMap {
         self.settings.offset as f32 / 1000.0
     }
 
+    /// Song title in map settings, with rich text tags removed
+    pub fn title(&self) -> Option<String> {
+        Some(strip_rich_text(&self.settings.song)).filter(|title| !title.is_empty())
+    }
+
+    /// Song artist in map settings, with rich text tags removed
+    pub fn artist(&self) -> Option<String> {
+        Some(strip_rich_text(&self.settings.artist)).filter(|artist| !artist.is_empty())
+    }
+
+    /// Music file name in map settings, relative to the map file
+    pub fn song_filename(&self) -> Option<&str> {
+        Some(self.settings.song_filename.as_str()).filter(|name| !name.is_empty())
+    }
+
     pub fn scores(&mut self) -> Vec<ScoreEntry> {
         if self.parsed_actions.is_none() {
             self.parse_actions()
@@ -150,18 +196,23 @@ impl ADoFaIMap {
             .collect()
     }
 
-    // This function is only intended to be used by tests for debug purposes
-    #[allow(dead_code)]
-    fn convert_from_map(
+    /// Writes the score of `difficulty` in `map` as an ADoFaI level, notes and
+    /// BPM changes become PlaySound and SetSpeed events on a straight track
+    pub fn convert_from_map(
         map: &crate::map::Map,
         difficulty: crate::map::Difficulty,
         out_path: &Path,
     ) -> anyhow::Result<()> {
+        let score = map
+            .map_scores
+            .get(&difficulty)
+            .ok_or_else(|| anyhow::anyhow!("Map has no {difficulty} score"))?;
+
         let template_json = include_str!("template.adofai");
         let mut template_json: serde_json::Value =
             serde_json::from_str(template_json.trim_start_matches('\u{feff}')).unwrap();
 
-        let score_len = map.map_scores.get(&difficulty).unwrap().scores.0.len();
+        let score_len = score.scores.0.len();
         let angle_data = vec![0.into(); score_len];
         *template_json
             .pointer_mut("/angleData")
@@ -187,10 +238,7 @@ impl ADoFaIMap {
             }
         );
 
-        let mut actions = map
-            .map_scores
-            .get(&difficulty)
-            .unwrap()
+        let mut actions = score
             .scores
             .0
             .iter()
@@ -249,6 +297,25 @@ impl ADoFaIMap {
     }
 }
 
+/// Removes Unity rich text tags such as `<color=#ff0000>`, which ADoFaI allows
+/// in song and artist names
+fn strip_rich_text(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        stripped.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped.trim().to_owned()
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
@@ -278,4 +345,14 @@ mod tests {
             .unwrap();
         }
     }
+
+    #[test]
+    fn test_strip_rich_text() {
+        assert_eq!(
+            strip_rich_text("<color=#FF0000>Bad</color> Apple!!"),
+            "Bad Apple!!"
+        );
+        assert_eq!(strip_rich_text(" Plain "), "Plain");
+        assert_eq!(strip_rich_text("a < b"), "a < b");
+    }
 }
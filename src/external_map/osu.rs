@@ -184,15 +184,21 @@ impl Osu {
         ScoreData(score)
     }
 
-    #[allow(dead_code)]
-    fn convert_from_map(
+    /// Writes the score of `difficulty` in `map` as an osu beatmap, S notes are
+    /// circles with finish hitsound. `id` is put in the romanized title field.
+    pub fn convert_from_map(
         map: &crate::map::Map,
         difficulty: crate::map::Difficulty,
         title: &str,
         artist: &str,
         id: &str,
         out_path: &Path,
-    ) {
+    ) -> anyhow::Result<()> {
+        let score = map
+            .map_scores
+            .get(&difficulty)
+            .ok_or_else(|| anyhow::anyhow!("Map has no {difficulty} score"))?;
+
         let offset = map.song_info.offset * 1000.0;
         let initial_bpm = map.song_info.bpm;
         let initial_entry = BpmEntry {
@@ -242,7 +248,6 @@ impl Osu {
         *metadata.title_unicode.as_mut().unwrap() = title.to_owned().into();
         *metadata.title.as_mut().unwrap() = id.to_owned().into();
 
-        let score = map.map_scores.get(&difficulty).unwrap();
         let hit_objs = score
             .scores
             .0
@@ -264,7 +269,9 @@ impl Osu {
         osu.osu_file.timing_points = Some(TimingPoints(timing_points));
         osu.osu_file.hitobjects = Some(HitObjects(hit_objs));
 
-        std::fs::write(out_path, osu.osu_file.to_string()).unwrap();
+        std::fs::write(out_path, osu.osu_file.to_string())?;
+
+        Ok(())
     }
 }
 
@@ -302,6 +309,7 @@ mod tests {
                     title,
                 )),
             )
+            .unwrap();
         }
     }
 }
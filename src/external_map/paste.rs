@@ -0,0 +1,176 @@
+use std::str::FromStr;
+
+use rust_decimal::prelude::ToPrimitive;
+
+use super::{ADoFaIMap, Osu, action_scores};
+use crate::map::{BpmChanges, ScoreData};
+
+const OSU_HEADER: &str = "osu file format v14";
+
+/// Chart parsed from pasted text, timing is only available for osu and ADoFaI
+/// maps
+pub struct PastedChart {
+    pub score:       ScoreData,
+    pub bpm:         Option<f32>,
+    /// Offset in seconds
+    pub offset:      Option<f32>,
+    pub bpm_changes: Option<BpmChanges>,
+}
+
+/// Whether every line is an osu hit object, starting with x, y, time and type
+fn is_bare_hit_objects(text: &str) -> bool {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .all(|line| {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            fields.len() >= 5 && fields[..4].iter().all(|field| field.parse::<f64>().is_ok())
+        })
+}
+
+/// Parses pasted chart text, detecting whether it is a raw score string, osu
+/// map sections (timing points and hit objects), bare osu hit objects, ADoFaI
+/// map JSON or a bare ADoFaI `actions` array. Bare hit objects carry no timing,
+/// they are placed by `current_timing` (BPM and offset in seconds) of the map
+/// pasted into.
+pub fn parse_pasted_chart(
+    text: &str,
+    current_timing: Option<(f32, f32)>,
+) -> anyhow::Result<PastedChart> {
+    let text = text.trim().trim_start_matches('\u{feff}');
+
+    // osu sections start with '[' as well, but are not JSON
+    if let Some(Ok(scores)) = text.starts_with('[').then(|| action_scores(text)) {
+        return Ok(PastedChart {
+            score:       ScoreData(scores),
+            bpm:         None,
+            offset:      None,
+            bpm_changes: None,
+        });
+    }
+
+    if is_bare_hit_objects(text) && text.contains(',') {
+        let Some((bpm, offset)) = current_timing.filter(|(bpm, _)| bpm.is_finite() && *bpm > 0.0)
+        else {
+            anyhow::bail!(
+                "Pasted osu hit objects have no timing, set BPM and offset of the map first or \
+                 copy the [TimingPoints] section as well"
+            );
+        };
+        let osu_file = format!(
+            "{OSU_HEADER}\n\n[TimingPoints]\n{},{},4,1,0,100,1,0\n\n[HitObjects]\n{text}",
+            offset * 1000.0,
+            60_000.0 / bpm
+        );
+        let osu = Osu::new(&osu_file)?;
+        return Ok(PastedChart {
+            score:       osu.score(),
+            bpm:         None,
+            offset:      None,
+            bpm_changes: None,
+        });
+    }
+
+    if text.starts_with('{') {
+        let mut adofai: ADoFaIMap = serde_json::from_str(text)?;
+        let bpm_changes = adofai.bpm_changes();
+        return Ok(PastedChart {
+            score:       ScoreData(adofai.scores()),
+            bpm:         Some(adofai.bpm()),
+            offset:      Some(adofai.offset()),
+            bpm_changes: (!bpm_changes.is_empty()).then_some(BpmChanges(bpm_changes)),
+        });
+    }
+
+    if text.contains("[HitObjects]") {
+        // Copied sections usually lack the header required by the parser
+        let osu_file = if text.starts_with("osu file format") {
+            text.to_owned()
+        } else {
+            format!("{OSU_HEADER}\n\n{text}")
+        };
+        let osu = Osu::new(&osu_file)?;
+        return Ok(PastedChart {
+            score:       osu.score(),
+            bpm:         osu.initial_bpm().to_f32(),
+            offset:      osu.offset().to_f32().map(|offset| offset / 1000.0),
+            bpm_changes: osu.bpm_changes(),
+        });
+    }
+
+    // Score strings may be copied from scripts with separators and line breaks
+    let score = text
+        .chars()
+        .filter(|c| !(c.is_whitespace() || *c == ','))
+        .map(|c| c.to_ascii_uppercase())
+        .collect::<String>();
+    if score.is_empty() {
+        anyhow::bail!("Clipboard has no chart");
+    }
+
+    let score = ScoreData::from_str(&score).map_err(|_| {
+        anyhow::anyhow!(
+            "Unrecognized chart, expecting a score string (O, S and -), osu map sections or \
+             ADoFaI map JSON"
+        )
+    })?;
+    Ok(PastedChart {
+        score,
+        bpm: None,
+        offset: None,
+        bpm_changes: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_pasted_score() {
+        let chart = parse_pasted_chart("O,-,S,-,\no,o,-,-,\n", None).unwrap();
+        assert_eq!(chart.score.to_string(), "O-S-OO--");
+        assert!(chart.bpm.is_none());
+
+        assert!(parse_pasted_chart("O-X", None).is_err());
+        assert!(parse_pasted_chart("  ", None).is_err());
+    }
+
+    #[test]
+    fn test_parse_pasted_adofai() {
+        let level = r#"{
+            "angleData": [0, 0, 0, 0],
+            "settings": { "bpm": 120, "offset": 500 },
+            "actions": [
+                { "floor": 2, "eventType": "PlaySound", "hitsound": "Hat" },
+                { "floor": 4, "eventType": "PlaySound", "hitsound": "Hammer" }
+            ]
+        }"#;
+        let chart = parse_pasted_chart(level, None).unwrap();
+        assert_eq!(chart.score.to_string(), "-O-S");
+        assert_eq!(chart.bpm, Some(120.0));
+        assert_eq!(chart.offset, Some(0.5));
+
+        let actions = r#"[
+            { "floor": 3, "eventType": "PlaySound", "hitsound": "Hammer" },
+            { "floor": 1, "eventType": "PlaySound", "hitsound": "Hat" }
+        ]"#;
+        let chart = parse_pasted_chart(actions, None).unwrap();
+        assert_eq!(chart.score.to_string(), "O-S");
+        assert!(chart.bpm.is_none());
+    }
+
+    #[test]
+    fn test_parse_pasted_osu() {
+        let sections = "[TimingPoints]\n0,500,4,1,0,100,1,0\n\n[HitObjects]\n256,192,0,1,0,0:0:0:\
+                        0:\n256,192,1000,1,2,0:0:0:0:\n";
+        let chart = parse_pasted_chart(sections, None).unwrap();
+        assert_eq!(chart.bpm, Some(120.0));
+        assert_eq!(chart.offset, Some(0.0));
+
+        let hit_objects = "256,192,0,1,0,0:0:0:0:\n256,192,1000,1,2,0:0:0:0:\n";
+        let bare = parse_pasted_chart(hit_objects, Some((120.0, 0.0))).unwrap();
+        assert_eq!(bare.score.to_string(), chart.score.to_string());
+        assert!(bare.bpm.is_none());
+        assert!(parse_pasted_chart(hit_objects, None).is_err());
+    }
+}
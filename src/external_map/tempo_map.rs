@@ -0,0 +1,363 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail};
+
+use crate::map::BpmChanges;
+
+/// Tempo used by MIDI files before the first tempo event (120 BPM)
+const MIDI_DEFAULT_TEMPO: u32 = 500_000;
+
+#[derive(Debug, Clone, Copy)]
+struct TempoEntry {
+    /// Time from the start of the music, in milliseconds
+    time_ms: f64,
+    bpm:     f64,
+}
+
+/// A tempo map exported from DAWs, either a MIDI file with tempo track or a
+/// Reaper marker/region CSV
+pub struct TempoMap {
+    entries: Vec<TempoEntry>,
+}
+
+struct MidiReader<'a> {
+    data: &'a [u8],
+    pos:  usize,
+}
+
+impl<'a> MidiReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(anyhow!("Unexpected end of MIDI file"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn peek_u8(&self) -> anyhow::Result<u8> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or(anyhow!("Unexpected end of MIDI file"))
+    }
+
+    fn read_u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into()?))
+    }
+
+    fn read_var_len(&mut self) -> anyhow::Result<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        bail!("Invalid variable length value in MIDI file")
+    }
+}
+
+/// Returns tempo events (absolute tick, microseconds per quarter note) in a
+/// MIDI track
+fn read_midi_track(track: &[u8]) -> anyhow::Result<Vec<(u64, u32)>> {
+    let mut reader = MidiReader::new(track);
+    let mut tempos = vec![];
+    let mut tick = 0;
+    let mut running_status = None;
+
+    while !reader.is_end() {
+        tick += reader.read_var_len()? as u64;
+
+        let status = if reader.peek_u8()? & 0x80 != 0 {
+            reader.read_u8()?
+        } else {
+            running_status.ok_or(anyhow!("Invalid running status in MIDI file"))?
+        };
+
+        match status {
+            0xFF => {
+                let meta_type = reader.read_u8()?;
+                let len = reader.read_var_len()? as usize;
+                let data = reader.read_bytes(len)?;
+                match meta_type {
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        tempos.push((tick, tempo));
+                    }
+                    0x2F => break,
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = reader.read_var_len()? as usize;
+                reader.read_bytes(len)?;
+            }
+            _ => {
+                running_status = Some(status);
+                let data_len = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                reader.read_bytes(data_len)?;
+            }
+        }
+    }
+
+    Ok(tempos)
+}
+
+/// Parses time in seconds, `m:ss.fff` or `h:mm:ss.fff` into milliseconds
+fn parse_time_ms(time: &str) -> Option<f64> {
+    let seconds = time.trim().split(':').try_fold(0.0, |acc, part| {
+        Some(acc * 60.0 + part.trim().parse::<f64>().ok()?)
+    })?;
+    Some(seconds * 1000.0)
+}
+
+/// Range of BPMs taken from marker names, other numbers are more likely part of
+/// a name like "Verse 2" than a tempo
+const MARKER_BPM_RANGE: std::ops::RangeInclusive<f64> = 20.0..=999.0;
+
+/// Extracts the BPM from marker names like "180", "BPM 180" or "180bpm",
+/// markers named otherwise are not tempo markers
+fn parse_bpm(name: &str) -> Option<f64> {
+    let name = name.trim().to_ascii_lowercase();
+    let number = name
+        .strip_prefix("bpm")
+        .or_else(|| name.strip_suffix("bpm"))
+        .unwrap_or(&name)
+        .trim();
+
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, "0"));
+    let is_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !(is_digits(integer) && is_digits(fraction)) {
+        return None;
+    }
+
+    number
+        .parse()
+        .ok()
+        .filter(|bpm| MARKER_BPM_RANGE.contains(bpm))
+}
+
+impl TempoMap {
+    /// Reads the tempo map from a file, the format is chosen by file extension
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("mid" | "midi") => Self::from_midi(&std::fs::read(path)?),
+            Some("csv") => Self::from_reaper_csv(&std::fs::read_to_string(path)?),
+            _ => bail!("Unsupported tempo map format, expected .mid or .csv file"),
+        }
+    }
+
+    /// Reads tempo events from all tracks of a standard MIDI file, one quarter
+    /// note is treated as one beat
+    pub fn from_midi(data: &[u8]) -> anyhow::Result<Self> {
+        let mut reader = MidiReader::new(data);
+
+        if reader.read_bytes(4)? != b"MThd" {
+            bail!("Not a MIDI file");
+        }
+        let header_len = reader.read_u32()? as usize;
+        let header = reader.read_bytes(header_len)?;
+        let division = header
+            .get(4..6)
+            .map(|d| u16::from_be_bytes([d[0], d[1]]))
+            .ok_or(anyhow!("Invalid MIDI header"))?;
+        if division & 0x8000 != 0 || division == 0 {
+            bail!("SMPTE time division in MIDI file is not supported");
+        }
+
+        let mut tempos = vec![];
+        while !reader.is_end() {
+            let chunk_type = reader.read_bytes(4)?;
+            let chunk_len = reader.read_u32()? as usize;
+            let chunk = reader.read_bytes(chunk_len)?;
+
+            if chunk_type == b"MTrk" {
+                tempos.append(&mut read_midi_track(chunk)?);
+            }
+        }
+
+        // Later events on the same tick override former ones
+        tempos.sort_by_key(|(tick, _)| *tick);
+        tempos.reverse();
+        tempos.dedup_by_key(|(tick, _)| *tick);
+        tempos.reverse();
+
+        if tempos.first().is_none_or(|(tick, _)| *tick != 0) {
+            tempos.insert(0, (0, MIDI_DEFAULT_TEMPO));
+        }
+
+        let mut time_ms = 0.0;
+        let mut last_tick = 0;
+        let mut last_tempo = MIDI_DEFAULT_TEMPO;
+        let entries = tempos
+            .into_iter()
+            .map(|(tick, tempo)| {
+                time_ms += (tick - last_tick) as f64 / division as f64 * last_tempo as f64 / 1000.0;
+                last_tick = tick;
+                last_tempo = tempo;
+
+                TempoEntry {
+                    time_ms,
+                    bpm: 60_000_000.0 / tempo as f64,
+                }
+            })
+            .collect();
+
+        Ok(Self { entries })
+    }
+
+    /// Reads markers or regions exported from Reaper's region/marker manager,
+    /// the name of each marker is the BPM starting from it. Start time must be
+    /// exported in seconds or minutes:seconds format, rows without a BPM in
+    /// name are ignored
+    pub fn from_reaper_csv(content: &str) -> anyhow::Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(content.trim_start_matches('\u{feff}').as_bytes());
+
+        let headers = reader.headers()?.clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+                .ok_or(anyhow!("Missing \"{name}\" column in tempo map CSV"))
+        };
+        let name_column = column("Name")?;
+        let start_column = column("Start")?;
+
+        let mut entries = vec![];
+        for record in reader.records() {
+            let record = record?;
+
+            let Some(bpm) = record.get(name_column).and_then(parse_bpm) else {
+                continue;
+            };
+            let start = record.get(start_column).unwrap_or_default();
+            let time_ms = parse_time_ms(start).ok_or(anyhow!(
+                "Invalid start time \"{start}\" in tempo map CSV, export with seconds or \
+                 minutes:seconds time format"
+            ))?;
+
+            entries.push(TempoEntry { time_ms, bpm });
+        }
+
+        if entries.is_empty() {
+            bail!("No tempo markers found in tempo map CSV");
+        }
+        entries.sort_by(|a, b| a.time_ms.total_cmp(&b.time_ms));
+
+        Ok(Self { entries })
+    }
+
+    pub fn initial_bpm(&self) -> f32 {
+        self.entries[0].bpm as f32
+    }
+
+    /// Offset of the first tempo entry, in seconds
+    pub fn offset(&self) -> f32 {
+        (self.entries[0].time_ms / 1000.0) as f32
+    }
+
+    /// Beat indices of tempo changes, counted with the tempo before the change
+    pub fn bpm_changes(&self) -> Option<BpmChanges> {
+        if self.entries.len() == 1 {
+            return None;
+        }
+
+        let mut beat = 0.0;
+        let bpm_changes = self
+            .entries
+            .windows(2)
+            .map(|entries| {
+                let [prev, entry] = entries else {
+                    unreachable!()
+                };
+                beat += (entry.time_ms - prev.time_ms) * prev.bpm / 60_000.0;
+
+                (beat.round() as u16, entry.bpm as f32)
+            })
+            .collect();
+
+        Some(BpmChanges(bpm_changes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_tempo_map() {
+        #[rustfmt::skip]
+        let track = [
+            // 120 BPM at tick 0
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+            // Note on with running status
+            0x00, 0x90, 0x3C, 0x40,
+            0x60, 0x3C, 0x00,
+            // 180 BPM at tick 4 * 96, after 4 beats
+            0x82, 0x20, 0xFF, 0x51, 0x03, 0x05, 0x16, 0x15,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+
+        let mut data = b"MThd".to_vec();
+        data.extend_from_slice(&[0, 0, 0, 6, 0, 0, 0, 1, 0, 96]);
+        data.extend_from_slice(b"MTrk");
+        data.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        data.extend_from_slice(&track);
+
+        let tempo_map = TempoMap::from_midi(&data).unwrap();
+        assert_eq!(tempo_map.initial_bpm(), 120.0);
+        assert_eq!(tempo_map.offset(), 0.0);
+
+        let bpm_changes = tempo_map.bpm_changes().unwrap().0;
+        assert_eq!(bpm_changes.len(), 1);
+        assert_eq!(bpm_changes[0].0, 4);
+        assert!((bpm_changes[0].1 - 180.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_reaper_csv_tempo_map() {
+        let csv = "#,Name,Start,End,Length\nM1,BPM \
+                   150,0:01.000,,\nM2,Chorus,0:02.000,,\nM3,200,0:05.000,,\n";
+
+        let tempo_map = TempoMap::from_reaper_csv(csv).unwrap();
+        assert_eq!(tempo_map.initial_bpm(), 150.0);
+        assert_eq!(tempo_map.offset(), 1.0);
+        assert_eq!(tempo_map.bpm_changes().unwrap().0, vec![(10, 200.0)]);
+    }
+
+    #[test]
+    fn test_parse_bpm() {
+        assert_eq!(parse_bpm("180"), Some(180.0));
+        assert_eq!(parse_bpm(" BPM 150.5 "), Some(150.5));
+        assert_eq!(parse_bpm("200bpm"), Some(200.0));
+        assert_eq!(parse_bpm("Verse 2"), None);
+        assert_eq!(parse_bpm("Chorus 180"), None);
+        assert_eq!(parse_bpm("1.2.3"), None);
+        assert_eq!(parse_bpm("2"), None);
+        assert_eq!(parse_bpm("5000"), None);
+    }
+}
@@ -1,15 +1,210 @@
-use std::{path::Path, process::Command};
+use std::{
+    path::Path,
+    process::{Command, Output},
+};
 
-pub fn convert_file(file_path: &Path, dest_path: &Path) -> std::io::Result<()> {
+/// Runs ffmpeg set up to print only errors, failing with them if it exits
+/// unsuccessfully
+fn run_ffmpeg(cmd: &mut Command) -> std::io::Result<Output> {
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "ffmpeg exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output)
+}
+
+/// Converts `file_path` into `dest_path`, with volume adjusted by `volume_db`
+pub fn convert_file(file_path: &Path, dest_path: &Path, volume_db: f32) -> std::io::Result<()> {
     let mut cmd = Command::new("ffmpeg");
 
     setup_cmd(&mut cmd);
 
-    cmd.arg("-i").arg(file_path).arg(dest_path).output()?;
+    cmd.args(["-v", "error", "-y"]).arg("-i").arg(file_path);
+    if volume_db != 0.0 {
+        cmd.arg("-af").arg(format!("volume={volume_db}dB"));
+    }
+    run_ffmpeg(cmd.arg(dest_path))?;
+
+    Ok(())
+}
+
+/// Joins audio files in order into `dest_path`, with volume adjusted by
+/// `volume_db`
+pub fn concat_files(file_paths: &[&Path], dest_path: &Path, volume_db: f32) -> std::io::Result<()> {
+    let mut cmd = Command::new("ffmpeg");
+
+    setup_cmd(&mut cmd);
+
+    cmd.args(["-v", "error", "-y"]);
+    for file_path in file_paths {
+        cmd.arg("-i").arg(file_path);
+    }
+
+    let inputs = (0..file_paths.len())
+        .map(|i| format!("[{i}:a]"))
+        .collect::<String>();
+    let filter = format!(
+        "{inputs}concat=n={}:v=0:a=1,volume={volume_db}dB[a]",
+        file_paths.len()
+    );
+
+    cmd.arg("-filter_complex")
+        .arg(filter)
+        .args(["-map", "[a]"])
+        .arg(dest_path);
+    run_ffmpeg(&mut cmd)?;
+
+    Ok(())
+}
+
+/// Joins songs into `dest_path`, overlapping adjacent songs by `crossfade`
+/// seconds. Each song is given as its audio files in play order and the volume
+/// adjustment in dB
+pub fn crossfade_files(
+    songs: &[(Vec<&Path>, f32)],
+    crossfade: f32,
+    dest_path: &Path,
+) -> std::io::Result<()> {
+    let mut cmd = Command::new("ffmpeg");
+
+    setup_cmd(&mut cmd);
+
+    cmd.args(["-v", "error", "-y"]);
+    let mut filters = vec![];
+    let mut input_idx = 0;
+    for (i, (file_paths, volume_db)) in songs.iter().enumerate() {
+        for file_path in file_paths {
+            cmd.arg("-i").arg(file_path);
+        }
+
+        let inputs = (input_idx..input_idx + file_paths.len())
+            .map(|idx| format!("[{idx}:a]"))
+            .collect::<String>();
+        filters.push(format!(
+            "{inputs}concat=n={}:v=0:a=1,volume={volume_db}dB[s{i}]",
+            file_paths.len()
+        ));
+        input_idx += file_paths.len();
+    }
+
+    for i in 1..songs.len() {
+        let prev = if i == 1 {
+            "s0".to_owned()
+        } else {
+            format!("x{}", i - 1)
+        };
+        filters.push(if crossfade > 0.0 {
+            format!("[{prev}][s{i}]acrossfade=d={crossfade}[x{i}]")
+        } else {
+            format!("[{prev}][s{i}]concat=n=2:v=0:a=1[x{i}]")
+        });
+    }
+
+    let output = match songs.len() {
+        0 | 1 => "[s0]".to_owned(),
+        len => format!("[x{}]", len - 1),
+    };
+
+    cmd.arg("-filter_complex")
+        .arg(filters.join(";"))
+        .args(["-map", &output])
+        .arg(dest_path);
+    run_ffmpeg(&mut cmd)?;
 
     Ok(())
 }
 
+/// First line of `ffmpeg -version`, fails if ffmpeg is not found or not
+/// runnable
+pub fn version() -> std::io::Result<String> {
+    tool_version("ffmpeg")
+}
+
+/// First line of `ffprobe -version`, fails if ffprobe is not found or not
+/// runnable
+pub fn ffprobe_version() -> std::io::Result<String> {
+    tool_version("ffprobe")
+}
+
+fn tool_version(program: &str) -> std::io::Result<String> {
+    let mut cmd = Command::new(program);
+
+    setup_cmd(&mut cmd);
+
+    let output = cmd.arg("-version").output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "{program} exited with {}",
+            output.status
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_owned())
+}
+
+/// Decodes audio files joined in order into mono 16-bit samples at
+/// `sample_rate`
+pub fn decode_mono(file_paths: &[&Path], sample_rate: u32) -> std::io::Result<Vec<i16>> {
+    let mut cmd = Command::new("ffmpeg");
+
+    setup_cmd(&mut cmd);
+
+    cmd.args(["-v", "error"]);
+    for file_path in file_paths {
+        cmd.arg("-i").arg(file_path);
+    }
+
+    let inputs = (0..file_paths.len())
+        .map(|i| format!("[{i}:a]"))
+        .collect::<String>();
+    let filter = format!("{inputs}concat=n={}:v=0:a=1[a]", file_paths.len());
+
+    cmd.arg("-filter_complex")
+        .arg(filter)
+        .args(["-map", "[a]", "-ac", "1", "-ar"])
+        .arg(sample_rate.to_string())
+        .args(["-f", "s16le", "-"]);
+    let output = run_ffmpeg(&mut cmd)?;
+
+    Ok(output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect())
+}
+
+/// Reads the duration of an audio file in seconds with ffprobe
+pub fn audio_duration(file_path: &Path) -> std::io::Result<f32> {
+    let mut cmd = Command::new("ffprobe");
+
+    setup_cmd(&mut cmd);
+
+    let output = cmd
+        .args(["-v", "error", "-show_entries", "format=duration"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(file_path)
+        .output()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unable to read duration of {}", file_path.display()),
+            )
+        })
+}
+
 #[cfg(windows)]
 fn setup_cmd(cmd: &mut Command) {
     use std::os::windows::process::CommandExt;
@@ -0,0 +1,144 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+    path::{Path, PathBuf},
+};
+
+/// FAT32 cannot store files of 4 GiB or larger
+const FAT32_MAX_FILE_SIZE: u64 = u32::MAX as u64;
+/// Both exFAT and FAT32 (with LFN) limit a single name to 255 UTF-16 units
+const MAX_NAME_LENGTH: usize = 255;
+const RESERVED_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// A problem that breaks the output tree when copied onto an exFAT or FAT32
+/// formatted SD card
+#[derive(Debug)]
+pub enum FsWarning {
+    ReservedCharacter(PathBuf),
+    ReservedName(PathBuf),
+    TrailingDotOrSpace(PathBuf),
+    NameTooLong(PathBuf),
+    CaseCollision(PathBuf, PathBuf),
+    FileTooLarge(PathBuf, u64),
+}
+
+impl Display for FsWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ReservedCharacter(p) => {
+                write!(
+                    f,
+                    "{} contains characters not allowed on SD cards",
+                    p.display()
+                )
+            }
+            Self::ReservedName(p) => write!(f, "{} uses a reserved file name", p.display()),
+            Self::TrailingDotOrSpace(p) => {
+                write!(
+                    f,
+                    "{} ends with a dot or space, which is dropped on SD cards",
+                    p.display()
+                )
+            }
+            Self::NameTooLong(p) => write!(
+                f,
+                "{} has a name longer than {MAX_NAME_LENGTH} characters",
+                p.display()
+            ),
+            Self::CaseCollision(a, b) => write!(
+                f,
+                "{} and {} only differ in case and overwrite each other on SD cards",
+                a.display(),
+                b.display()
+            ),
+            Self::FileTooLarge(p, size) => write!(
+                f,
+                "{} is {size} bytes, which exceeds the 4 GiB file size limit of FAT32",
+                p.display()
+            ),
+        }
+    }
+}
+
+fn check_name(path: &Path, warnings: &mut Vec<FsWarning>) {
+    let Some(name) = path.file_name() else {
+        return;
+    };
+    let name = name.to_string_lossy();
+
+    if name
+        .chars()
+        .any(|c| c.is_control() || RESERVED_CHARS.contains(&c))
+    {
+        warnings.push(FsWarning::ReservedCharacter(path.to_owned()));
+    }
+
+    let stem = name.split('.').next().unwrap_or_default();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        warnings.push(FsWarning::ReservedName(path.to_owned()));
+    }
+
+    if name.ends_with('.') || name.ends_with(' ') {
+        warnings.push(FsWarning::TrailingDotOrSpace(path.to_owned()));
+    }
+
+    if name.encode_utf16().count() > MAX_NAME_LENGTH {
+        warnings.push(FsWarning::NameTooLong(path.to_owned()));
+    }
+}
+
+fn check_dir(dir: &Path, warnings: &mut Vec<FsWarning>) -> std::io::Result<()> {
+    let mut lowercase_names: HashMap<String, PathBuf> = HashMap::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let metadata = entry.metadata()?;
+
+        check_name(&path, warnings);
+
+        let lowercase_name = entry.file_name().to_string_lossy().to_lowercase();
+        if let Some(other) = lowercase_names.insert(lowercase_name, path.clone()) {
+            warnings.push(FsWarning::CaseCollision(other, path.clone()));
+        }
+
+        if metadata.is_dir() {
+            check_dir(&path, warnings)?;
+        } else if metadata.len() > FAT32_MAX_FILE_SIZE {
+            warnings.push(FsWarning::FileTooLarge(path, metadata.len()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the generated output tree against exFAT/FAT32 constraints
+pub fn check_output_tree(out_dir: &Path) -> std::io::Result<Vec<FsWarning>> {
+    let mut warnings = vec![];
+    check_dir(out_dir, &mut warnings)?;
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        let mut warnings = vec![];
+
+        check_name(Path::new("out/BGM_NEWLY.acb"), &mut warnings);
+        assert!(warnings.is_empty());
+
+        check_name(Path::new("out/score_a:b"), &mut warnings);
+        check_name(Path::new("out/CON.acb"), &mut warnings);
+        check_name(Path::new("out/score_new."), &mut warnings);
+        assert!(matches!(warnings[0], FsWarning::ReservedCharacter(_)));
+        assert!(matches!(warnings[1], FsWarning::ReservedName(_)));
+        assert!(matches!(warnings[2], FsWarning::TrailingDotOrSpace(_)));
+    }
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{map, mod_merge::MANIFEST_FILE, output_sink::EXEFS_PATCHES_DIR, platform};
+
+/// Game files read when generating mods, relative to RomFS root. Files of the
+/// score donor are left out if no donor is found in the dump.
+fn input_files(romfs_root: &Path) -> Vec<String> {
+    let platform = platform::current();
+    let mut files = vec![
+        platform.share_data(),
+        platform.global_metadata().to_owned(),
+        platform.main_bundle(),
+    ];
+    if let Ok(donor) = map::score_donor(romfs_root) {
+        files.push(platform.sound_file(&donor, "acb"));
+        files.push(platform.score_file(&donor));
+    }
+    files
+}
+const MAIN_EXE_KEY: &str = "exefs/main";
+
+/// SHA-256 hashes of input game files, keyed by their relative paths
+pub type InputHashes = BTreeMap<String, String>;
+
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Hashes input game files, missing files are skipped
+pub fn hash_inputs(romfs_root: &Path, main_exe: Option<&Path>) -> std::io::Result<InputHashes> {
+    let mut hashes = InputHashes::new();
+
+    for file in input_files(romfs_root) {
+        let path = romfs_root.join(&file);
+        if path.is_file() {
+            hashes.insert(file, hash_file(&path)?);
+        }
+    }
+
+    if let Some(main_exe) = main_exe.filter(|p| p.is_file()) {
+        hashes.insert(MAIN_EXE_KEY.to_owned(), hash_file(main_exe)?);
+    }
+
+    Ok(hashes)
+}
+
+/// Top-level folders of game files in generated mods, in all output layouts
+const MOD_CONTENT_DIRS: &[&str] = &["contents", "mods", "load"];
+
+fn is_generated_mod_root(dir: &Path) -> bool {
+    // Mods generated before manifests are written are told by their IPS patches
+    dir.join(MANIFEST_FILE).is_file() || dir.join(EXEFS_PATCHES_DIR).is_dir()
+}
+
+/// Returns the root of the generated mod `input` is inside, if it is one.
+/// Patching game files of a generated mod again corrupts them (e.g. songs
+/// added twice to metadata enums), so they must not be used as inputs.
+pub fn generated_mod_root(input: &Path) -> Option<PathBuf> {
+    let input = std::path::absolute(input).ok()?;
+
+    input
+        .ancestors()
+        .filter(|dir| is_generated_mod_root(dir))
+        .find(|dir| {
+            let rel_path = input.strip_prefix(dir).unwrap();
+            rel_path
+                .components()
+                .next()
+                .is_none_or(|c| MOD_CONTENT_DIRS.iter().any(|d| c.as_os_str() == *d))
+        })
+        .map(Path::to_owned)
+}
+
+/// Returns input files changed since `recorded`, which usually means the game
+/// is updated or the dump is replaced. Nothing is reported if no hashes are
+/// recorded yet. Only files hashed in `current` are compared, as files not read
+/// by a run (e.g. the main executable with `--romfs-only`) are not hashed.
+pub fn changed_inputs(recorded: &InputHashes, current: &InputHashes) -> Vec<String> {
+    if recorded.is_empty() {
+        return vec![];
+    }
+
+    current
+        .iter()
+        .filter(|(file, hash)| recorded.get(*file) != Some(hash))
+        .map(|(file, _)| file.clone())
+        .collect()
+}
+
+/// Records `hashes` into `input_hashes` of the map config at `path`, editing
+/// the file in place so that comments and layout of the rest are kept
+pub fn record_input_hashes(path: &Path, hashes: &InputHashes) -> anyhow::Result<()> {
+    let mut config = std::fs::read_to_string(path)?.parse::<toml_edit::Document>()?;
+
+    let recorded = config
+        .entry("input_hashes")
+        .or_insert_with(toml_edit::table)
+        .as_table_like_mut()
+        .ok_or(anyhow::anyhow!(
+            "input_hashes in {} is not a table",
+            path.display()
+        ))?;
+    for (file, hash) in hashes {
+        recorded.insert(file, toml_edit::value(hash));
+    }
+
+    std::fs::write(path, config.to_string())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changed_inputs() {
+        let recorded = InputHashes::from([
+            ("share_data".to_owned(), "a".to_owned()),
+            ("global-metadata.dat".to_owned(), "b".to_owned()),
+        ]);
+
+        assert!(changed_inputs(&InputHashes::new(), &recorded).is_empty());
+        assert!(changed_inputs(&recorded, &recorded).is_empty());
+
+        let current = InputHashes::from([
+            ("share_data".to_owned(), "c".to_owned()),
+            ("global-metadata.dat".to_owned(), "b".to_owned()),
+            ("main".to_owned(), "d".to_owned()),
+        ]);
+        assert_eq!(
+            changed_inputs(&recorded, &current),
+            vec!["main".to_owned(), "share_data".to_owned()]
+        );
+
+        let romfs_only = InputHashes::from([("share_data".to_owned(), "c".to_owned())]);
+        assert!(changed_inputs(&current, &romfs_only).is_empty());
+    }
+
+    #[test]
+    fn test_record_input_hashes() {
+        let path = std::env::temp_dir().join("spell_bubble_record_inputs_test.toml");
+        std::fs::write(
+            &path,
+            "# Maps of the pack\nmaps = []\n\n[input_hashes]\nmain = \"a\"\n",
+        )
+        .unwrap();
+
+        let hashes = InputHashes::from([
+            ("main".to_owned(), "b".to_owned()),
+            (
+                "StreamingAssets/Switch/share_data".to_owned(),
+                "c".to_owned(),
+            ),
+        ]);
+        record_input_hashes(&path, &hashes).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("# Maps of the pack\n"));
+        let config = map::MapsConfig::from_toml(&content).unwrap();
+        assert_eq!(config.input_hashes, hashes);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_generated_mod_root() {
+        let root = std::env::temp_dir().join("spell_bubble_generated_input_test");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let mod_dir = root.join("mod");
+        let romfs_root = mod_dir.join("contents/0100E9D00D6C2000/romfs/Data");
+        let dump_dir = mod_dir.join("dump/romfs");
+        std::fs::create_dir_all(&romfs_root).unwrap();
+        std::fs::create_dir_all(&dump_dir).unwrap();
+        std::fs::write(mod_dir.join(MANIFEST_FILE), b"").unwrap();
+
+        assert_eq!(generated_mod_root(&romfs_root), Some(mod_dir.clone()));
+        assert_eq!(generated_mod_root(&mod_dir), Some(mod_dir.clone()));
+        // Only folders of game files in the mod are considered generated
+        assert_eq!(generated_mod_root(&dump_dir), None);
+        assert_eq!(generated_mod_root(&root), None);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,4 +1,9 @@
-use std::{ffi::c_void, os::raw::c_char};
+use std::{
+    ffi::{CString, c_void},
+    io::{Error, ErrorKind},
+    os::raw::c_char,
+    path::Path,
+};
 
 #[repr(C)]
 #[derive(Debug)]
@@ -47,3 +52,127 @@ impl Drop for StringWrapper {
 extern "C" {
     pub fn free_dotnet(pointer: *mut c_void);
 }
+
+/// Paths of this length or longer exceed MAX_PATH (248 for directories) and
+/// need the extended-length prefix on Windows
+const WINDOWS_MAX_PATH: usize = 248;
+
+/// Adds the extended-length prefix to an absolute Windows path, UNC paths
+/// (`\\server\share`) are converted into `\\?\UNC\server\share`
+fn extended_length_path(path: &str) -> String {
+    if path.starts_with(r"\\?\") {
+        path.to_owned()
+    } else if let Some(unc) = path.strip_prefix(r"\\") {
+        format!(r"\\?\UNC\{unc}")
+    } else {
+        format!(r"\\?\{path}")
+    }
+}
+
+/// Converts a path into a UTF-8 encoded C string passed to the helper. Long
+/// paths on Windows are converted into extended-length paths, and paths that
+/// are not valid Unicode are reported as errors instead of being mangled. How
+/// the helper decodes the string is not checked here.
+pub fn path_to_cstring(path: &Path) -> std::io::Result<CString> {
+    let path = if cfg!(windows) {
+        std::path::absolute(path)?
+    } else {
+        path.to_owned()
+    };
+
+    let path_str = path.to_str().ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "Path {} is not valid Unicode and can't be passed to the helper",
+                path.display()
+            ),
+        )
+    })?;
+
+    let path_str = if cfg!(windows) && path_str.len() >= WINDOWS_MAX_PATH {
+        extended_length_path(path_str)
+    } else {
+        path_str.to_owned()
+    };
+
+    CString::new(path_str).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidInput,
+            format!("Path {} contains NUL character", path.display()),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    /// Only checks the UTF-8 encoding on this side, a round trip through the
+    /// helper needs the built helper library
+    #[test]
+    fn test_non_ascii_path_encoding() {
+        let path = Path::new("東方スペルバブル/Data/StreamingAssets/Switch/share_data");
+        let path_c = path_to_cstring(path).unwrap();
+        let path_back = unsafe { CStr::from_ptr(path_c.as_ptr()) }.to_str().unwrap();
+
+        assert!(path_back.ends_with("東方スペルバブル/Data/StreamingAssets/Switch/share_data"));
+    }
+
+    /// Round trip of non-ASCII paths through the helper, reading DLC names of
+    /// share_data copied under a folder named in Japanese, longer than MAX_PATH
+    /// on Windows. Needs the built helper library and the share_data file of a
+    /// game dump, given by `SPELL_BUBBLE_SHARE_DATA`.
+    #[test]
+    fn test_non_ascii_path_helper_round_trip() {
+        extern "C" {
+            fn get_dlc_list(share_data_path: *const c_char) -> ArrayWrapper;
+        }
+
+        fn dlc_names(share_data: &Path) -> Vec<String> {
+            let share_data = path_to_cstring(share_data).unwrap();
+            let dlcs = unsafe { get_dlc_list(share_data.as_ptr()) };
+            let dlcs = unsafe {
+                std::slice::from_raw_parts(dlcs.array as *const *const c_char, dlcs.size as usize)
+            };
+            dlcs.iter()
+                .map(|&p| StringWrapper(p))
+                .map(|name| {
+                    unsafe { CStr::from_ptr(name.0) }
+                        .to_str()
+                        .unwrap()
+                        .to_owned()
+                })
+                .collect()
+        }
+
+        let Some(share_data) = std::env::var_os("SPELL_BUBBLE_SHARE_DATA") else {
+            return;
+        };
+        let share_data = Path::new(&share_data);
+
+        let root = std::env::temp_dir().join("東方スペルバブル");
+        let dir = (0..3).fold(root.clone(), |dir, _| dir.join("長いパス".repeat(15)));
+        std::fs::create_dir_all(&dir).unwrap();
+        let copied = dir.join("share_data");
+        std::fs::copy(share_data, &copied).unwrap();
+
+        let names = dlc_names(share_data);
+        assert!(!names.is_empty());
+        assert_eq!(dlc_names(&copied), names);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        assert_eq!(extended_length_path(r"C:\Mods\out"), r"\\?\C:\Mods\out");
+        assert_eq!(
+            extended_length_path(r"\\nas\share\out"),
+            r"\\?\UNC\nas\share\out"
+        );
+        assert_eq!(extended_length_path(r"\\?\C:\Mods"), r"\\?\C:\Mods");
+    }
+}
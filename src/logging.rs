@@ -0,0 +1,117 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use indicatif::MultiProgress;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Root module of records to output, records of dependencies are dropped
+const CRATE_NAME: &str = env!("CARGO_CRATE_NAME");
+
+struct Logger {
+    console_level: LevelFilter,
+    file:          Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target().starts_with(CRATE_NAME) && metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        if record.level() <= self.console_level {
+            let print = || match record.level() {
+                Level::Error => eprintln!("Error: {}", record.args()),
+                Level::Warn => eprintln!("Warning: {}", record.args()),
+                Level::Info => println!("{}", record.args()),
+                Level::Debug | Level::Trace => println!("[{}] {}", record.level(), record.args()),
+            };
+            // Progress bars are hidden while printing so that they are redrawn
+            // below the message instead of overwriting it
+            match PROGRESS.lock().ok().and_then(|progress| progress.clone()) {
+                Some(progress) => progress.suspend(print),
+                None => print(),
+            }
+        }
+
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "[{}] {}", record.level(), record.args());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+static PROGRESS: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// Routes console output through `progress` while its bars are shown, `None`
+/// after they are finished
+pub fn set_progress(progress: Option<MultiProgress>) {
+    if let Ok(mut current) = PROGRESS.lock() {
+        *current = progress;
+    }
+}
+
+/// Sets up log output of the CLI, messages up to `console_level` are printed
+/// and all messages down to debug level are written to `log_file` if given.
+/// Warnings and errors go to stderr, others to stdout.
+pub fn init(console_level: LevelFilter, log_file: Option<&Path>) -> std::io::Result<()> {
+    let file = log_file.map(File::create).transpose()?.map(Mutex::new);
+    let max_level = if file.is_some() {
+        console_level.max(LevelFilter::Debug)
+    } else {
+        console_level
+    };
+
+    let logger = LOGGER.get_or_init(|| Logger {
+        console_level,
+        file,
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+
+    Ok(())
+}
+
+/// Reports panics as errors through the logger instead of the default panic
+/// message, with a backtrace if `backtrace` is set
+pub fn install_panic_hook(backtrace: bool) {
+    std::panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown failure");
+        let location = info
+            .location()
+            .map(|location| format!(" at {location}"))
+            .unwrap_or_default();
+
+        log::error!("Internal error: {message}{location}");
+        if backtrace {
+            log::error!("{}", std::backtrace::Backtrace::force_capture());
+        } else {
+            log::error!(
+                "This is a bug of mod_tool, run with --backtrace to report it with details"
+            );
+        }
+    }));
+}
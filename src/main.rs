@@ -1,32 +1,105 @@
 #![feature(try_blocks)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_dirs;
+mod difficulty_compare;
+mod dlc_musics;
+mod dlc_select;
+mod doctor;
+mod draft_chart;
+mod duplicate_check;
 mod exefs;
 mod external_map;
 mod ffmpeg_helper;
+mod fs_check;
+mod input_check;
 mod interop;
+mod logging;
 mod map;
+mod map_list;
+mod mod_merge;
+mod mod_package;
+mod output_report;
+mod output_sink;
+mod patch_cache;
+mod patch_progress;
+mod platform;
+mod preview_pick;
+mod project_bundle;
+mod romfs_detect;
+mod romfs_diff;
 mod song_info;
+mod space_check;
+mod staging;
 mod ui;
+mod watch;
 
 use std::{
+    collections::HashSet,
     ffi::{CString, c_char, c_int, c_void},
     fs, mem,
     path::{Path, PathBuf},
     process::exit,
+    str::FromStr,
 };
 
-use clap::{Parser, Subcommand};
-use interop::ArrayWrapper;
+use anyhow::Context;
+use clap::{CommandFactory, Parser, Subcommand};
+use interop::{ArrayWrapper, path_to_cstring};
 use itertools::Itertools;
+use log::{error, info, warn};
+use rust_decimal::prelude::ToPrimitive;
+use strum::IntoEnumIterator;
 
-use crate::song_info::{get_song_info, write_song_info_csv};
+use crate::{
+    output_sink::{DirSink, OutputLayout, OutputSink, romfs_path},
+    song_info::{
+        CsvOptions, dlc_list, dlc_names, get_song_info, official_song_names, score_scripts_text,
+        write_song_info_csv, write_song_info_json, write_song_info_xlsx,
+    },
+};
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
     class_package_path: PathBuf,
 
+    /// Store config and other data in a folder beside the executable, also
+    /// enabled by placing a "portable.flag" file beside the executable
+    #[clap(long, global = true)]
+    portable: bool,
+
+    /// Title ID of the game in output paths, for dumps of other regions or
+    /// releases. Overrides the title_id of map configs
+    #[clap(long, global = true, value_parser = output_sink::parse_title_id)]
+    title_id: Option<String>,
+
+    /// Also print debug messages, e.g. each game file read and written
+    #[clap(long, short, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Only print warnings and errors
+    #[clap(long, short, global = true)]
+    quiet: bool,
+
+    /// Write all messages including debug ones into a file, e.g. next to the
+    /// generated mod
+    #[clap(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Print backtraces of errors and internal failures, for reporting bugs
+    #[clap(long, global = true)]
+    backtrace: bool,
+
+    /// Platform whose game file layout is read and written
+    #[clap(long, global = true, default_value = "switch")]
+    platform: platform::Platform,
+
+    /// Reject map configs with keys not read by any field, e.g. misspelt ones,
+    /// instead of ignoring them. Always enabled for Validate
+    #[clap(long, global = true)]
+    strict: bool,
+
     #[clap(subcommand)]
     command: Commands,
 }
@@ -36,77 +109,734 @@ enum Commands {
     /// Unlocks some hidden or DLC-related game features
     UnlockFeatures {
         /// The path to extracted share_data file
-        share_data:    PathBuf,
+        share_data:          PathBuf,
         /// Output path of generated content
-        outdir:        PathBuf,
+        outdir:              PathBuf,
         /// Unlock special challenge rules for PvE games
         #[clap(short, long)]
-        special_rules: bool,
+        special_rules:       bool,
         /// Unlock all musics (including DLC musics and musics in shop, one
         /// music: "Lostword" is kept unlocked to keep the shop functioning
         /// normally)
         #[clap(short, long)]
-        musics:        bool,
+        musics:              bool,
         /// Unlock DLC characters (one DLC must be present, the program sets it
         /// to the first one)
         #[clap(short, long)]
+        characters:          bool,
+        /// Exclude DLCs from being unlocked, by ID (see ListDlcs) or by name.
+        /// Names match as case-insensitive substrings, or with * wildcards
+        #[clap(short, long)]
+        exclude:             Vec<dlc_select::DlcSelector>,
+        /// Only unlock these DLCs, by ID or name as --exclude, excluding all
+        /// others
+        #[clap(long)]
+        include:             Vec<dlc_select::DlcSelector>,
+        /// Directory structure of output, for Atmosphère or an emulator
+        #[clap(long, default_value = "atmosphere")]
+        output_layout:       OutputLayout,
+        /// Use game files inside a mod generated by this tool as input,
+        /// which patches them twice and usually corrupts them
+        #[clap(long)]
+        allow_patched_input: bool,
+    },
+    /// Show songs sold as DLC, and what UnlockFeatures with the given options
+    /// would unlock
+    InspectUnlocks {
+        /// The path to dumped game RomFS files
+        romfs_root:    PathBuf,
+        /// Preview unlocking special challenge rules
+        #[clap(short, long)]
+        special_rules: bool,
+        /// Preview unlocking all musics
+        #[clap(short, long)]
+        musics:        bool,
+        /// Preview unlocking DLC characters
+        #[clap(short, long)]
         characters:    bool,
-        /// Exclude DLC IDs from being unlocked
+        /// DLCs excluded from being unlocked, by ID or name
         #[clap(short, long)]
-        exclude:       Vec<u16>,
+        exclude:       Vec<dlc_select::DlcSelector>,
+        /// Only DLCs unlocked, by ID or name
+        #[clap(long)]
+        include:       Vec<dlc_select::DlcSelector>,
+    },
+    /// List DLCs with their IDs and songs
+    ListDlcs {
+        /// The path to dumped game RomFS files
+        romfs_root: PathBuf,
     },
     /// Patch game files given map config toml
     PatchMap {
         /// The path to dumped game RomFS files
-        romfs_root:    PathBuf,
+        romfs_root:              PathBuf,
         /// Map config toml file
-        maps:          PathBuf,
+        maps:                    PathBuf,
         /// Output path of generated content
-        outdir:        PathBuf,
+        outdir:                  PathBuf,
         #[clap(long)]
         /// Only patch romfs to replace existing song with provided ones,
         /// only existing IDs are usable in this mode
-        romfs_only:    bool,
+        romfs_only:              bool,
         #[clap(required_unless_present("romfs_only"))]
         /// The path to the "main" file in the ExeFS, used to extract build ID
-        main_exe_path: Option<PathBuf>,
+        main_exe_path:           Option<PathBuf>,
+        /// Name of the mod, used for the exefs_patches subfolder. Defaults to
+        /// the name of output folder
+        #[clap(long)]
+        mod_name:                Option<String>,
+        /// Reject map scores longer than this many entries, as overly long
+        /// scores may make the game misbehave on console. Without it, scores
+        /// longer than 2000 entries only get a warning
+        #[clap(long)]
+        max_length:              Option<usize>,
+        /// Pad map scores with trailing blanks so they end at the audio end,
+        /// optionally the given margin in seconds before it
+        #[clap(long, num_args = 0..=1, default_missing_value = "0")]
+        pad_end:                 Option<f32>,
+        /// Reject map scores with fewer blank entries than this before the
+        /// first note, as notes in the very first beats are unreadable in game.
+        /// Without it, fewer than 4 blank entries only get a warning
+        #[clap(long)]
+        min_lead_in:             Option<usize>,
+        /// Prepend blank entries to scores with shorter lead-in than
+        /// --min-lead-in (4 by default), moving the music offset earlier
+        /// accordingly
+        #[clap(long)]
+        fix_lead_in:             bool,
+        /// Shift music offsets of all maps to compensate display latency, in
+        /// milliseconds (e.g. 12ms) or 60Hz frames (e.g. 1f). Positive values
+        /// make notes appear later. Overrides offset_nudge of the map config
+        #[clap(long, allow_hyphen_values = true)]
+        offset_nudge:            Option<map::OffsetNudge>,
+        /// Form of ExeFS instruction overrides, ips for a patch applied on
+        /// launch or cheat for an Atmosphère cheat toggled at runtime
+        #[clap(long, default_value = "ips")]
+        exefs_format:            exefs::ExefsPatchFormat,
+        /// Number of songs converted and patched at the same time
+        #[clap(long, short, default_value_t = 1)]
+        jobs:                    usize,
+        /// Record hashes of game files into the map config, leaving the rest of
+        /// the file as it is. Patching is refused if recorded hashes differ, as
+        /// mods from different game versions should not be mixed
+        #[clap(long)]
+        record_inputs:           bool,
+        /// Directory structure of output, for Atmosphère or an emulator
+        #[clap(long, default_value = "atmosphere")]
+        output_layout:           OutputLayout,
+        /// Use game files inside a mod generated by this tool as input,
+        /// which patches them twice and usually corrupts them
+        #[clap(long)]
+        allow_patched_input:     bool,
+        /// Generate for a game build the ExeFS patch is not recorded as made
+        /// for, the patch may not apply and new songs may not appear in game
+        #[clap(long)]
+        allow_unsupported_build: bool,
+        /// Patch all songs again, instead of skipping songs whose inputs are
+        /// unchanged since they were patched into the output directory
+        #[clap(long)]
+        rebuild:                 bool,
+        /// Keep running and patch again whenever the map config or audio files
+        /// of its maps change
+        #[clap(long, conflicts_with_all(["record_inputs", "rebuild"]))]
+        watch:                   bool,
+        /// Only patch maps with these music IDs (comma separated), leaving
+        /// other maps of the config out of the mod
+        #[clap(long, value_delimiter = ',')]
+        only:                    Vec<String>,
+        /// Leave maps with these music IDs (comma separated) out of the mod
+        #[clap(long, value_delimiter = ',')]
+        skip:                    Vec<String>,
+    },
+    /// Generate an ExeFS patch from a user patch config in the format of the
+    /// built-in one (src/exefs_patches.toml), without patching other files
+    GenerateIps {
+        /// Patch config toml file with [[patches]] and optionally [[builds]]
+        patch_config:     PathBuf,
+        /// The path to the "main" file in the ExeFS, used to extract build ID
+        main_exe_path:    PathBuf,
+        /// Output path of generated content
+        outdir:           PathBuf,
+        /// Name of the mod, used for the exefs_patches subfolder. Defaults to
+        /// the name of output folder
+        #[clap(long)]
+        mod_name:         Option<String>,
+        /// Added to immediates of patches without override_patch, as PatchMap
+        /// adds the count of music IDs
+        #[clap(long, default_value_t = 0, allow_hyphen_values = true)]
+        immediate_offset: i16,
+        /// Form of ExeFS instruction overrides, as in PatchMap
+        #[clap(long, default_value = "ips")]
+        exefs_format:     exefs::ExefsPatchFormat,
+        /// Directory structure of output, for Atmosphère or an emulator
+        #[clap(long, default_value = "atmosphere")]
+        output_layout:    OutputLayout,
+    },
+    /// Merge mods generated separately into one, re-applying their patches of
+    /// shared game files (share_data, metadata) on top of each other
+    MergeMods {
+        /// The path to dumped game RomFS files
+        romfs_root:              PathBuf,
+        /// Output path of the merged mod
+        outdir:                  PathBuf,
+        /// Output folders of the mods to merge, generated in the Atmosphère
+        /// layout
+        #[clap(required = true, num_args = 2..)]
+        mods:                    Vec<PathBuf>,
+        /// The path to the "main" file in the ExeFS, required if any mod adds
+        /// new songs
+        #[clap(long)]
+        main_exe_path:           Option<PathBuf>,
+        /// Name of the merged mod, used for the exefs_patches subfolder.
+        /// Defaults to the name of output folder
+        #[clap(long)]
+        mod_name:                Option<String>,
+        /// Form of ExeFS instruction overrides, as in PatchMap
+        #[clap(long, default_value = "ips")]
+        exefs_format:            exefs::ExefsPatchFormat,
+        /// Directory structure of output, for Atmosphère or an emulator
+        #[clap(long, default_value = "atmosphere")]
+        output_layout:           OutputLayout,
+        /// Use game files inside a mod generated by this tool as input,
+        /// which patches them twice and usually corrupts them
+        #[clap(long)]
+        allow_patched_input:     bool,
+        /// Generate for a game build the ExeFS patch is not recorded as made
+        /// for, the patch may not apply and new songs may not appear in game
+        #[clap(long)]
+        allow_unsupported_build: bool,
+    },
+    /// Package a generated mod into a zip archive to be extracted onto the SD
+    /// card root
+    Package {
+        /// Output path of the generated mod
+        mod_dir:  PathBuf,
+        /// Path of the zip archive to create
+        archive:  PathBuf,
+        /// Name of the mod, used for the exefs_patches subfolder. Defaults to
+        /// the name of the mod folder
+        #[clap(long)]
+        mod_name: Option<String>,
+    },
+    /// Write an example map config with comments describing its fields
+    Init {
+        /// Path of the map config toml file to create
+        #[clap(default_value = "maps.toml")]
+        maps:  PathBuf,
+        /// Overwrite the file if it exists
+        #[clap(long)]
+        force: bool,
+    },
+    /// Upgrade map config files to the latest schema version. Other commands
+    /// read older configs as well, this rewrites them explicitly. Comments
+    /// are not kept, the original file is backed up beside it
+    MigrateConfig {
+        /// Map config toml files
+        #[clap(required = true)]
+        maps: Vec<PathBuf>,
+    },
+    /// List maps in map config with effective BPM, duration, estimated levels
+    /// and validation status
+    ListMaps {
+        /// Map config toml file
+        maps:       PathBuf,
+        /// Validate maps for replacing existing songs, as in PatchMap
+        #[clap(long)]
+        romfs_only: bool,
+        /// Print the list as JSON
+        #[clap(long)]
+        json:       bool,
     },
     /// Convert map information (length, bpm, offset, scores) from adofai to
     /// toml files
     ConvertAdofai {
-        /// The path to adofai map file
-        #[clap(required_unless_present("list"))]
-        adofai:     Option<PathBuf>,
         /// The path to map config toml file
-        map:        PathBuf,
+        map:            PathBuf,
+        /// The path to adofai map file, or a directory to convert all adofai
+        /// files in it, one map each
+        #[clap(required_unless_present_any(["list", "easy", "normal", "hard"]))]
+        adofai:         Option<PathBuf>,
+        /// Difficulty to choose inside map config
+        #[clap(required_unless_present_any(["list", "easy", "normal", "hard"]))]
+        difficulty:     Option<map::Difficulty>,
+        /// The path to adofai map file of the Easy score, converted into the
+        /// same map as --normal and --hard
+        #[clap(long, conflicts_with_all(["adofai", "difficulty"]))]
+        easy:           Option<PathBuf>,
+        /// The path to adofai map file of the Normal score
+        #[clap(long, conflicts_with_all(["adofai", "difficulty"]))]
+        normal:         Option<PathBuf>,
+        /// The path to adofai map file of the Hard score
+        #[clap(long, conflicts_with_all(["adofai", "difficulty"]))]
+        hard:           Option<PathBuf>,
+        /// Update n-th element of the map config file, if not exists, add a new
+        /// entry
+        #[clap(long, short)]
+        update:         Option<usize>,
+        /// Update the map with this music ID, if not exists, add a new entry
+        /// with it. Unlike --update, this stays correct when maps are added or
+        /// removed
+        #[clap(long, conflicts_with("update"))]
+        id:             Option<String>,
+        /// List current maps in the config file
+        #[clap(long, short)]
+        list:           bool,
+        /// Snap imported BPMs within this difference to integers or common
+        /// fractions, 0 to disable
+        #[clap(long, default_value_t = map::DEFAULT_SNAP_TOLERANCE)]
+        snap_tolerance: f32,
+    },
+    /// Convert map information (length, bpm, offset, scores) from osu to toml
+    /// files
+    ConvertOsu {
+        /// The path to map config toml file
+        map:            PathBuf,
+        /// The path to osu map file
+        #[clap(required_unless_present("list"))]
+        osu:            Option<PathBuf>,
         /// Difficulty to choose inside map config
         #[clap(required_unless_present("list"))]
-        difficulty: Option<map::Difficulty>,
+        difficulty:     Option<map::Difficulty>,
         /// Update n-th element of the map config file, if not exists, add a new
         /// entry
         #[clap(long, short)]
-        update:     Option<usize>,
+        update:         Option<usize>,
         /// List current maps in the config file
         #[clap(long, short)]
-        list:       bool,
+        list:           bool,
+        /// Snap imported BPMs within this difference to integers or common
+        /// fractions, 0 to disable
+        #[clap(long, default_value_t = map::DEFAULT_SNAP_TOLERANCE)]
+        snap_tolerance: f32,
+    },
+    /// Import BPM changes and offset from a DAW tempo map (MIDI file with tempo
+    /// track or Reaper marker/region CSV) into toml files
+    ImportTempoMap {
+        /// The path to .mid or .csv tempo map file
+        tempo_map:      PathBuf,
+        /// The path to map config toml file
+        map:            PathBuf,
+        /// Update n-th element of the map config file, if not exists, add a new
+        /// entry
+        #[clap(long, short)]
+        update:         Option<usize>,
+        /// Snap imported BPMs within this difference to integers or common
+        /// fractions, 0 to disable
+        #[clap(long, default_value_t = map::DEFAULT_SNAP_TOLERANCE)]
+        snap_tolerance: f32,
+    },
+    /// Join several maps in map config into one medley map with crossfaded
+    /// audio, which is appended to the config
+    BuildMedley {
+        /// Map config toml file
+        maps:      PathBuf,
+        /// Indices of maps in the config to join, in play order
+        #[clap(required = true)]
+        indices:   Vec<usize>,
+        /// ID of the medley
+        #[clap(long)]
+        id:        String,
+        /// Title of the medley
+        #[clap(long)]
+        title:     String,
+        /// Output path of the joined audio file
+        #[clap(long)]
+        audio_out: PathBuf,
+        /// Crossfade between songs in seconds
+        #[clap(long, default_value_t = map::DEFAULT_CROSSFADE)]
+        crossfade: f32,
+    },
+    /// Set preview starting points of maps in map config to the loudest
+    /// (usually chorus) section of their audio
+    PickPreview {
+        /// Map config toml file
+        maps: PathBuf,
+        /// Also replace preview starting points already set, by default only
+        /// maps with preview starting at 0 are changed
+        #[clap(long)]
+        all:  bool,
+    },
+    /// Draft a score of a map in map config from onsets (e.g. drum hits) of
+    /// its audio, as a starting point for charting. BPM and offset must be set
+    DraftChart {
+        /// Map config toml file
+        maps:       PathBuf,
+        /// Index of the map in the config
+        index:      usize,
+        /// Difficulty of the drafted score, replacing the existing one
+        difficulty: map::Difficulty,
+        /// Audio to detect onsets in instead of music_file, such as a drum stem
+        /// aligned with it. The map still plays music_file
+        #[clap(long)]
+        stem:       Option<PathBuf>,
+        /// Scale of note density, higher values turn weaker onsets into notes
+        #[clap(long, default_value_t = 1.0)]
+        density:    f32,
+    },
+    /// Compare official songs of two game dumps (e.g. before and after a game
+    /// update), reporting added, removed and retimed songs and changed scores
+    DiffRomfs {
+        /// The path to dumped game RomFS files of the old version
+        old_romfs_root: PathBuf,
+        /// The path to dumped game RomFS files of the new version
+        new_romfs_root: PathBuf,
+    },
+    /// Compare note densities of maps in map config against official charts
+    /// of the same difficulty
+    CompareDifficulty {
+        /// The path to dumped game RomFS files
+        romfs_root: PathBuf,
+        /// Map config toml file
+        maps:       PathBuf,
+    },
+    /// Report repeated measures of maps in map config and how varied their
+    /// charts are, to spot copy-pasted sections
+    ChartVariety {
+        /// Map config toml file
+        maps:  PathBuf,
+        /// Only analyze the map at this index
+        #[clap(long)]
+        index: Option<usize>,
+        /// Count of most repeated patterns listed for each chart
+        #[clap(long, default_value_t = 5)]
+        top:   usize,
+    },
+    /// Export official charts as osu or ADoFaI files for studying them in
+    /// editors
+    ExtractCharts {
+        /// The path to dumped game RomFS files
+        romfs_root: PathBuf,
+        /// Output directory of exported charts
+        outdir:     PathBuf,
+        /// Music ID of the song to export
+        #[clap(required_unless_present("all"), conflicts_with("all"))]
+        music_id:   Option<String>,
+        /// Export charts of all songs
+        #[clap(long)]
+        all:        bool,
+        /// Output format, osu or adofai
+        #[clap(long, short, default_value = "osu")]
+        format:     ChartFormat,
+        /// Difficulty to export, all difficulties are exported if not given
+        #[clap(long, short)]
+        difficulty: Option<map::Difficulty>,
+    },
+    /// Write the beat script and score scripts of each official song into a
+    /// text file named after its music ID, for editing charts by hand or
+    /// diffing them across game versions
+    DumpScores {
+        /// The path to dumped game RomFS files
+        romfs_root: PathBuf,
+        /// Output directory of score text files
+        outdir:     PathBuf,
+    },
+    /// Estimate in-game levels of osu or ADoFaI charts, as PatchMap would set
+    /// them, without converting the charts into a map config
+    EstimateLevel {
+        /// Chart of the Easy difficulty, an osu or adofai file
+        #[clap(long, required_unless_present_any(["normal", "hard"]))]
+        easy:   Option<PathBuf>,
+        /// Chart of the Normal difficulty, an osu or adofai file
+        #[clap(long)]
+        normal: Option<PathBuf>,
+        /// Chart of the Hard difficulty, an osu or adofai file
+        #[clap(long)]
+        hard:   Option<PathBuf>,
+        /// Initial BPM overriding the one of the charts
+        #[clap(long)]
+        bpm:    Option<f32>,
+    },
+    /// Print the score of a map as numbered lines of its score script, with
+    /// BPM changes marked, to review the chart structure before patching
+    Preview {
+        /// Map config toml file, or the path to dumped game RomFS files with
+        /// --romfs
+        source:     PathBuf,
+        /// Index of the map in the config, or the music ID of an official
+        /// song with --romfs
+        map:        String,
+        /// Difficulty of the score
+        #[clap(long, short, default_value = "hard")]
+        difficulty: map::Difficulty,
+        /// Preview an official song of the game dump at `source`
+        #[clap(long)]
+        romfs:      bool,
+    },
+    /// Convert between beat indices and timestamps (m:ss.mmm) with BPM
+    /// changes and offset of a map in map config
+    BeatCalc {
+        /// Map config toml file
+        maps:  PathBuf,
+        /// Index of the map in the config
+        index: usize,
+        /// Beat index to get the timestamp of
+        #[clap(long, conflicts_with = "time", required_unless_present = "time")]
+        beat:  Option<usize>,
+        /// Timestamp to get the nearest beat index of, like 1:23.456
+        #[clap(long)]
+        time:  Option<String>,
     },
     /// Extract song information
     ExtractSongInfo {
         /// The path to dumped game RomFS files
-        romfs_root: PathBuf,
-        /// Output csv file
-        out_csv:    PathBuf,
+        romfs_root:  PathBuf,
+        /// Output csv, xlsx or json file
+        out_file:    PathBuf,
+        /// Output format, csv, xlsx or json. Chosen by extension of the output
+        /// file if not given
+        #[clap(long)]
+        format:      Option<SongInfoFormat>,
+        /// Languages to write title, artist and original columns for, among
+        /// JA, EN, KO, Chs and Cht
+        #[clap(
+            long,
+            visible_alias = "lang",
+            value_delimiter = ',',
+            default_value = "JA"
+        )]
+        langs:       Vec<map::Lang>,
+        /// Write title, artist and original columns for all languages
+        #[clap(long, conflicts_with = "langs")]
+        all_langs:   bool,
+        /// Write Excel compatible CSV (BOM, CRLF line endings and quoted text
+        /// fields)
+        #[clap(long)]
+        excel:       bool,
+        /// Use semicolon as delimiter, for Excel installs using comma as
+        /// decimal separator
+        #[clap(long)]
+        semicolon:   bool,
+        /// Add note count, heavy note count and max notes per second (in the
+        /// densest 4 seconds) of each difficulty
+        #[clap(long)]
+        score_stats: bool,
+    },
+    /// Report songs in map config whose titles nearly duplicate official songs
+    /// or other songs in the config
+    CheckDuplicates {
+        /// Map config toml file
+        maps:       PathBuf,
+        /// The path to dumped game RomFS files, official songs are only checked
+        /// if provided
+        #[clap(long)]
+        romfs_root: Option<PathBuf>,
+    },
+    /// Check maps in a map config and report problems of each map, without
+    /// reading game files or generating output
+    Validate {
+        /// Map config toml file
+        maps:       PathBuf,
+        /// Validate maps as replacements of existing songs, as in PatchMap
+        #[clap(long)]
+        romfs_only: bool,
+        /// Ignore keys not read by any field instead of reporting them
+        #[clap(long)]
+        no_strict:  bool,
+    },
+    /// Check that ffmpeg, ffprobe and the given game files are usable, and
+    /// print what to fix otherwise
+    Doctor {
+        /// The path to dumped game RomFS files
+        #[clap(long)]
+        romfs_root:    Option<PathBuf>,
+        /// The path to the "main" file in the ExeFS
+        #[clap(long)]
+        main_exe_path: Option<PathBuf>,
     },
+    /// Show locations of config, cache and state directories, and sizes of
+    /// cached data
+    CacheInfo,
+    /// Remove all cached data, which is regenerated when needed
+    CacheClear,
+    /// Print the completion script for a shell to stdout
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+}
+
+#[derive(strum::EnumString, Clone, Copy, Debug)]
+#[strum(ascii_case_insensitive)]
+enum SongInfoFormat {
+    Csv,
+    Xlsx,
+    Json,
+}
+
+#[derive(strum::EnumString, Clone, Copy, Debug)]
+#[strum(ascii_case_insensitive)]
+enum ChartFormat {
+    Osu,
+    ADoFaI,
+}
+
+impl ChartFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ChartFormat::Osu => "osu",
+            ChartFormat::ADoFaI => "adofai",
+        }
+    }
+}
+
+fn load_adofai(path: &Path) -> anyhow::Result<external_map::ADoFaIMap> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(
+        content.trim_start_matches('\u{feff}'),
+    )?)
+}
+
+/// Writes timing and score of `adofai` into `map_obj`, title and artist are
+/// filled from the chart only if not set yet
+fn convert_adofai(
+    map_obj: &mut map::Map,
+    adofai: &mut external_map::ADoFaIMap,
+    difficulty: map::Difficulty,
+    snap_tolerance: f32,
+) {
+    map_obj.song_info.length = adofai.length() as u16;
+    map_obj.song_info.bpm = adofai.bpm();
+    map_obj.song_info.offset = adofai.offset();
+    map_obj.map_scores.insert(
+        difficulty,
+        map::MapScore {
+            scores: map::ScoreData(adofai.scores()),
+        },
+    );
+
+    let bpm_changes = adofai.bpm_changes();
+    if !bpm_changes.is_empty() {
+        map_obj.song_info.bpm_changes = map::BpmChanges(bpm_changes).into();
+    }
+
+    let adjustments = map::snap_bpms(
+        &mut map_obj.song_info.bpm,
+        &mut map_obj.song_info.bpm_changes,
+        snap_tolerance,
+    );
+    for adjustment in adjustments {
+        info!("{adjustment}");
+    }
+
+    if map_obj.song_info.info_text.is_empty() {
+        map_obj
+            .song_info
+            .info_text
+            .insert(map::Lang::JA, map::SongInfoText::default());
+    }
+
+    if let Some(info_text) = map_obj.song_info.info_text.get_mut(&map::Lang::JA) {
+        if info_text.title.is_empty() {
+            info_text.title = adofai.title().unwrap_or_default();
+        }
+        if info_text.artist.is_empty() {
+            info_text.artist = adofai.artist().unwrap_or_default();
+        }
+    }
+}
+
+/// Map in `maps_config` a conversion writes to, selected by music `id` or
+/// index `update`. A new map is appended (with `id` set) if none matches.
+fn target_map<'a>(
+    maps_config: &'a mut map::MapsConfig,
+    update: Option<usize>,
+    id: Option<&str>,
+) -> &'a mut map::Map {
+    let existing = match id {
+        Some(id) => maps_config
+            .maps
+            .iter()
+            .position(|m| m.song_info.id.to_string() == id),
+        None => update.filter(|idx| *idx < maps_config.maps.len()),
+    };
+
+    match existing {
+        Some(idx) => &mut maps_config.maps[idx],
+        None => {
+            let mut map_obj = map::Map::default();
+            if let Some(id) = id {
+                map_obj.song_info.id = map::MusicID::from(id);
+            }
+            maps_config.maps.push(map_obj);
+            maps_config.maps.last_mut().unwrap()
+        }
+    }
+}
+
+/// Writes scores of charts of several difficulties into `map_obj`. The charts
+/// must share their timing, as a map has one BPM and offset for all
+/// difficulties, and the length is the one of the longest chart.
+fn convert_adofai_difficulties(
+    map_obj: &mut map::Map,
+    charts: &[(map::Difficulty, &PathBuf)],
+    snap_tolerance: f32,
+) -> anyhow::Result<()> {
+    // Difficulty of the first chart, with its BPM, offset and BPM changes
+    type Timing = (map::Difficulty, f32, f32, Vec<(u16, f32)>);
+    let mut timing: Option<Timing> = None;
+    let mut length = 0;
+
+    for (difficulty, path) in charts {
+        let mut chart = load_adofai(path)?;
+
+        let (bpm, offset, bpm_changes) = (chart.bpm(), chart.offset(), chart.bpm_changes());
+        if let Some((other, other_bpm, other_offset, other_bpm_changes)) = &timing {
+            if *other_bpm != bpm || *other_offset != offset {
+                anyhow::bail!(
+                    "{difficulty} chart has BPM {bpm} and offset {offset}, differing from \
+                     {other_bpm} and {other_offset} of {other} chart"
+                );
+            }
+            if *other_bpm_changes != bpm_changes {
+                anyhow::bail!("{difficulty} chart has BPM changes differing from {other} chart");
+            }
+        }
+        timing = Some((*difficulty, bpm, offset, bpm_changes));
+
+        // Charts without BPM changes must not keep the ones of the previous chart
+        map_obj.song_info.bpm_changes = None;
+        convert_adofai(map_obj, &mut chart, *difficulty, snap_tolerance);
+        length = length.max(map_obj.song_info.length);
+    }
+
+    map_obj.song_info.length = length;
+    Ok(())
 }
 
-fn create_out_dir_structure(out_base: &Path) -> anyhow::Result<PathBuf> {
-    let switch_path = "./contents/0100E9D00D6C2000/romfs/Data/StreamingAssets/Switch/";
+/// Reads timing and score of an osu or adofai chart into a map, telling the
+/// format by the file extension
+fn load_external_chart(path: &Path, difficulty: map::Difficulty) -> anyhow::Result<map::Map> {
+    let mut map_obj = map::Map::default();
+
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    let score = match &*extension.to_lowercase() {
+        "osu" => {
+            let osu = external_map::Osu::new(&fs::read_to_string(path)?)?;
+            map_obj.song_info.bpm = osu.initial_bpm().to_f32().unwrap_or_default();
+            map_obj.song_info.bpm_changes = osu.bpm_changes();
+            osu.score()
+        }
+        "adofai" => {
+            let mut adofai = load_adofai(path)?;
+            map_obj.song_info.bpm = adofai.bpm();
+            map_obj.song_info.bpm_changes = Some(map::BpmChanges(adofai.bpm_changes()))
+                .filter(|bpm_changes| !bpm_changes.0.is_empty());
+            map::ScoreData(adofai.scores())
+        }
+        _ => anyhow::bail!("{} is neither an osu nor an adofai chart", path.display()),
+    };
 
-    let mut assets_switch_out_path = out_base.to_owned();
-    assets_switch_out_path.push(switch_path);
-    fs::create_dir_all(&assets_switch_out_path)?;
+    map_obj.song_info.length = score.0.len() as u16;
+    map_obj
+        .map_scores
+        .insert(difficulty, map::MapScore { scores: score });
+    Ok(map_obj)
+}
 
-    Ok(assets_switch_out_path)
+fn print_map_list(maps_config: &map::MapsConfig) {
+    for summary in map_list::summarize_maps(maps_config, false) {
+        println!("{summary}");
+    }
 }
 
 extern "C" {
@@ -122,14 +852,191 @@ extern "C" {
     );
 }
 
+/// Refuses game files inside a generated mod as input unless `allow_patched`
+fn check_unpatched_input(input: &Path, allow_patched: bool) -> anyhow::Result<()> {
+    let Some(mod_root) = input_check::generated_mod_root(input) else {
+        return Ok(());
+    };
+
+    if allow_patched {
+        warn!(
+            "{} is inside mod {} generated by this tool, patching it again may corrupt game files",
+            input.display(),
+            mod_root.display()
+        );
+        Ok(())
+    } else {
+        anyhow::bail!(
+            "{} is inside mod {} generated by this tool, pass the original game dump instead, or \
+             run with --allow-patched-input to use it anyway",
+            input.display(),
+            mod_root.display()
+        )
+    }
+}
+
+fn check_supported_build(main_exe: &Path, allow_unsupported: bool) -> anyhow::Result<()> {
+    match exefs::check_build(main_exe) {
+        Err(e) if e.is::<exefs::UnsupportedBuildError>() => {
+            if allow_unsupported {
+                warn!("{e} Generating anyway as --allow-unsupported-build is given.");
+                Ok(())
+            } else {
+                anyhow::bail!("{e} Run with --allow-unsupported-build to generate anyway.")
+            }
+        }
+        result => result,
+    }
+}
+
+/// Resolves DLCs selected by name or ID to IDs of DLCs excluded from
+/// unlocking and reports which DLCs are unlocked. With `include`, all DLCs
+/// not in it are excluded as well.
+fn resolve_excluded_dlcs(
+    share_data: &Path,
+    exclude: &[dlc_select::DlcSelector],
+    include: &[dlc_select::DlcSelector],
+) -> anyhow::Result<Vec<u16>> {
+    if exclude.is_empty() && include.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let dlcs = dlc_names(share_data)?;
+    let mut excluded = dlc_select::resolve_dlcs(exclude, &dlcs)?;
+    if !include.is_empty() {
+        let included = dlc_select::resolve_dlcs(include, &dlcs)?;
+        excluded.extend((1..=dlcs.len() as u16).filter(|index| !included.contains(index)));
+        excluded.sort();
+        excluded.dedup();
+    }
+
+    for (index, name) in (1..).zip(&dlcs) {
+        let state = if excluded.contains(&index) {
+            "excluded"
+        } else {
+            "unlocked"
+        };
+        info!("DLC {index} ({name}): {state}");
+    }
+
+    Ok(excluded)
+}
+
+/// Fails with the error of a limit check if the limit is given on the command
+/// line, otherwise only warns, as default limits are not known limits of the
+/// game
+fn enforce_limit(
+    map: &map::Map,
+    result: Result<(), map::InvalidMapError>,
+    enforced: bool,
+) -> anyhow::Result<()> {
+    match result {
+        Err(e) if enforced => Err(e.into()),
+        Err(e) => {
+            warn!("{}: {e}", map.song_info.id);
+            Ok(())
+        }
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Reads song info of official songs, telling where from if it fails
+fn read_song_info(romfs_root: &Path) -> anyhow::Result<song_info::SongInfos> {
+    check_romfs_root(romfs_root);
+    get_song_info(romfs_root)
+        .with_context(|| format!("while reading song info from {}", romfs_root.display()))
+}
+
+/// Remembers `romfs_root` for RomFS autodetection if it has the expected
+/// layout, otherwise warns with RomFS dumps found elsewhere
+fn check_romfs_root(romfs_root: &Path) {
+    if romfs_detect::has_romfs_layout(romfs_root) {
+        romfs_detect::remember(romfs_root);
+    } else if let Some(suggestion) = romfs_detect::suggestion() {
+        warn!(
+            "{} is missing game files. {suggestion}",
+            romfs_root.display()
+        );
+    }
+}
+
+fn unlock_features(
+    share_data: &Path,
+    out_path: &Path,
+    options: &mod_merge::UnlockOptions,
+) -> std::io::Result<()> {
+    let share_data_path = path_to_cstring(share_data)?;
+    let out_path = path_to_cstring(out_path)?;
+    let left_music_id = CString::new(dlc_musics::KEPT_LOCKED_MUSIC).unwrap();
+
+    unsafe {
+        let exclude_list_wrapper = ArrayWrapper {
+            managed: 0,
+            size:    options.exclude.len() as u32,
+            array:   mem::transmute::<*const u16, *mut c_void>(options.exclude.as_ptr()),
+        };
+
+        patch_features(
+            share_data_path.as_ptr(),
+            out_path.as_ptr(),
+            if options.musics { 1 } else { 0 },
+            exclude_list_wrapper,
+            left_music_id.as_ptr(),
+            if options.characters { 1 } else { 0 },
+            1,
+            if options.special_rules { 1 } else { 0 },
+        );
+    }
+
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
-    if std::env::args().len() <= 1 {
+    if std::env::args().skip(1).all(|arg| arg == "--portable") {
+        if std::env::args().len() > 1 {
+            app_dirs::enable_portable();
+        }
+
         return ui::start_gui();
     }
 
     let args = Args::parse();
 
-    match &args.command {
+    let console_level = match (args.verbose, args.quiet) {
+        (true, _) => log::LevelFilter::Debug,
+        (_, true) => log::LevelFilter::Warn,
+        _ => log::LevelFilter::Info,
+    };
+    logging::init(console_level, args.log_file.as_deref())?;
+    if args.backtrace {
+        // Backtraces of errors are only captured with this set
+        std::env::set_var("RUST_BACKTRACE", "1");
+    }
+    logging::install_panic_hook(args.backtrace);
+
+    if args.portable {
+        app_dirs::enable_portable();
+    }
+    platform::select(args.platform);
+    if args.strict {
+        map::enable_strict();
+    }
+    if let Some(title_id) = &args.title_id {
+        output_sink::set_title_id(title_id.clone());
+    }
+
+    if let Commands::PatchMap {
+        maps, watch: true, ..
+    } = &args.command
+    {
+        return watch::watch_maps(maps, || run_command(&args.command));
+    }
+
+    run_command(&args.command)
+}
+
+fn run_command(command: &Commands) -> anyhow::Result<()> {
+    match command {
         Commands::UnlockFeatures {
             share_data,
             outdir,
@@ -137,136 +1044,694 @@ fn main() -> anyhow::Result<()> {
             musics,
             characters,
             exclude: exclude_list,
+            include,
+            output_layout,
+            allow_patched_input,
         } => {
             if !share_data.is_file() {
-                println!("share_data file does not exist!");
+                error!("share_data file does not exist!");
                 exit(1)
             };
+            check_unpatched_input(share_data, *allow_patched_input)?;
 
-            let mut assets_switch_out_path = create_out_dir_structure(outdir)?;
-
-            assets_switch_out_path.push("share_data");
-
-            let share_data_path = CString::new(share_data.to_string_lossy().as_ref()).unwrap();
-            let out_path = CString::new(assets_switch_out_path.to_string_lossy().as_ref()).unwrap();
-            let left_music_id = CString::new("Lostword").unwrap();
+            let options = mod_merge::UnlockOptions {
+                special_rules: *special_rules,
+                musics:        *musics,
+                characters:    *characters,
+                exclude:       resolve_excluded_dlcs(share_data, exclude_list, include)?,
+            };
 
-            unsafe {
-                let exclude_list_wrapper = ArrayWrapper {
-                    managed: 0,
-                    size:    exclude_list.len() as u32,
-                    array:   mem::transmute::<*const u16, *mut c_void>(exclude_list.as_ptr()),
-                };
+            let mod_name = exefs::default_mod_name(outdir);
+            let mut sink = DirSink::with_layout(outdir, *output_layout, &mod_name);
+            let out_path = sink.file_path(&romfs_path(platform::current().share_data()))?;
+            unlock_features(share_data, &out_path, &options)
+                .with_context(|| format!("while unlocking features of {}", share_data.display()))?;
 
-                patch_features(
-                    share_data_path.as_ptr(),
-                    out_path.as_ptr(),
-                    if *musics { 1 } else { 0 },
-                    exclude_list_wrapper,
-                    left_music_id.as_ptr(),
-                    if *characters { 1 } else { 0 },
-                    1,
-                    if *special_rules { 1 } else { 0 },
-                );
+            mod_merge::ModManifest {
+                unlock: Some(options),
+                ..Default::default()
             }
+            .write(&sink)?;
+            sink.finish()?;
         }
         Commands::PatchMap {
             romfs_root,
-            maps,
+            maps: maps_path,
             outdir,
             romfs_only,
             main_exe_path,
+            mod_name,
+            max_length,
+            pad_end,
+            min_lead_in,
+            fix_lead_in,
+            offset_nudge,
+            exefs_format,
+            jobs,
+            record_inputs,
+            output_layout,
+            allow_patched_input,
+            allow_unsupported_build,
+            rebuild,
+            watch: _,
+            only,
+            skip,
         } => {
-            let maps: map::MapsConfig = {
-                let content = fs::read_to_string(maps)?;
-                toml::from_str(&content)?
-            };
+            check_romfs_root(romfs_root);
+            check_unpatched_input(romfs_root, *allow_patched_input)?;
 
-            for map in maps.maps.iter() {
-                map.validate(*romfs_only)?
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps_path)
+                    .with_context(|| format!("while reading {}", maps_path.display()))?;
+                map::MapsConfig::from_toml(&content)
+                    .with_context(|| format!("while parsing {}", maps_path.display()))?
+            };
+            if let Some(title_id) = &maps_config.title_id {
+                output_sink::set_title_id(
+                    output_sink::parse_title_id(title_id).map_err(anyhow::Error::msg)?,
+                );
+            }
+            let (mut maps, unknown_ids) = map::filter_maps(maps_config.expanded_maps(), only, skip);
+            for id in unknown_ids {
+                warn!("{id}: no map with this ID in {}", maps_path.display());
+            }
+            if maps.is_empty() {
+                anyhow::bail!("No maps left to patch after --only and --skip");
             }
 
-            map::Map::patch_files(romfs_root, outdir, &maps.maps, *romfs_only)?;
-
-            if !*romfs_only {
-                let names = maps
-                    .maps
-                    .iter()
-                    .map(|m| m.song_info.id.to_string())
-                    .collect::<Vec<_>>();
-
-                exefs::patch_files(romfs_root, main_exe_path.as_ref().unwrap(), outdir, &names);
+            if let Some(main_exe_path) = main_exe_path.as_ref().filter(|_| !*romfs_only) {
+                check_supported_build(main_exe_path, *allow_unsupported_build)?;
             }
-        }
-        Commands::ConvertAdofai {
-            adofai,
-            map,
-            difficulty,
-            update,
-            list,
-        } => {
-            let mut maps_config = fs::read_to_string(map)
-                .ok()
-                .and_then(|s| toml::from_str(&s).ok())
-                .unwrap_or(map::MapsConfig { maps: vec![] });
 
-            if *list {
-                let output = maps_config
-                    .maps
-                    .iter()
-                    .enumerate()
-                    .map(|(i, m)| {
-                        let title = m
-                            .song_info
-                            .info_text
-                            .iter()
-                            .next()
-                            .map(|(_, it)| it.title())
-                            .unwrap_or_default();
-
-                        let duration = m.duration();
-                        let effective_bpm = m.effective_bpm();
-                        let replace = &m.song_info.id;
-
-                        let (level_e, level_n, level_h) = m.levels();
-
-                        format!(
-                            "Map {i}: {title}, effective BPM: {effective_bpm}, duration: \
-                             {duration}, levels (E/N/H): {level_e}/{level_n}/{level_h}, id: \
-                             {replace}"
-                        )
-                    })
-                    .join("\n");
-
-                println!("{output}");
-                return Ok(());
+            let input_hashes = input_check::hash_inputs(romfs_root, main_exe_path.as_deref())?;
+            let changed_inputs =
+                input_check::changed_inputs(&maps_config.input_hashes, &input_hashes);
+            if !changed_inputs.is_empty() && !*record_inputs {
+                anyhow::bail!(
+                    "Game files changed since the hashes were recorded (game updated or dump \
+                     replaced): {}. Re-validate maps and regenerate the whole mod, then run with \
+                     --record-inputs to record the new hashes",
+                    changed_inputs.join(", ")
+                );
             }
 
-            let mut adofai: external_map::ADoFaIMap = {
-                let content = fs::read_to_string(adofai.as_ref().unwrap())?;
-                serde_json::from_str(content.trim_start_matches('\u{feff}'))?
+            let official_songs = if *romfs_only {
+                official_song_names(&read_song_info(romfs_root)?)
+            } else {
+                Default::default()
             };
 
-            let map_obj = match maps_config.maps.get_mut(update.unwrap_or(usize::MAX)) {
-                Some(map_obj) => map_obj,
-                None => {
-                    maps_config.maps.push(map::Map::default());
-                    maps_config.maps.last_mut().unwrap()
+            let offset_nudge = offset_nudge.or(maps_config.offset_nudge);
+            for map in maps.iter_mut() {
+                let id = map.song_info.id.to_string();
+                map.validate(*romfs_only)
+                    .with_context(|| format!("while validating map {id}"))?;
+
+                if let Some(name) = official_songs.get(&id.to_lowercase()) {
+                    info!("{id}: replaces official song {name}");
                 }
+
+                for adjustment in map.collapse_bpm_changes() {
+                    warn!("{}: {adjustment}", map.song_info.id);
+                }
+
+                if let Some(nudge) = offset_nudge {
+                    map.nudge_offset(nudge)?;
+                }
+
+                let lead_in = min_lead_in.unwrap_or(map::DEFAULT_MIN_LEAD_IN);
+                if *fix_lead_in {
+                    let added = map.fix_lead_in(lead_in)?;
+                    if added > 0 {
+                        info!("{}: prepended {added} blank entries", map.song_info.id);
+                    }
+                }
+                enforce_limit(map, map.validate_lead_in(lead_in), min_lead_in.is_some())?;
+
+                if let Some(margin) = pad_end {
+                    match map.song_info.audio_duration() {
+                        Ok(duration) => {
+                            let added = map.pad_to_duration(duration, *margin);
+                            info!("{}: padded with {added} blank entries", map.song_info.id);
+                        }
+                        Err(e) => warn!("{}: {e}", map.song_info.id),
+                    }
+                }
+
+                enforce_limit(
+                    map,
+                    map.validate_length(max_length.unwrap_or(map::DEFAULT_MAX_SCORE_LENGTH)),
+                    max_length.is_some(),
+                )?;
+            }
+
+            if !maps_config.variants.is_empty() {
+                if *romfs_only {
+                    warn!("variant charts need new IDs, skipped in RomFS-only mode");
+                } else {
+                    let variants = map::variant_maps(&maps, &maps_config.variants)?;
+                    info!("Added {} variant charts", variants.len());
+                    maps.extend(variants);
+                }
+            }
+
+            let required = space_check::estimate_output_size(romfs_root, &maps, *romfs_only);
+            space_check::check_free_space(outdir, required)?;
+
+            let mod_name = mod_name
+                .clone()
+                .unwrap_or_else(|| exefs::default_mod_name(outdir));
+
+            let old_cache = if *rebuild {
+                patch_cache::PatchCache::default()
+            } else {
+                patch_cache::PatchCache::read(outdir)
             };
+            let mut cache = patch_cache::PatchCache::default();
+            let mut unchanged = HashSet::new();
+            for map in &maps {
+                let id = map.song_info.id.to_string();
+                // Songs with unreadable audio are patched, which reports the error
+                let Ok(hash) = patch_cache::song_hash(map, &input_hashes, *romfs_only) else {
+                    continue;
+                };
 
-            map_obj.song_info.length = adofai.length() as u16;
-            map_obj.song_info.bpm = adofai.bpm();
-            map_obj.song_info.offset = adofai.offset();
+                if old_cache.is_unchanged(&id, &hash)
+                    && patch_cache::has_outputs(outdir, &id, *output_layout, &mod_name)
+                {
+                    unchanged.insert(id.clone());
+                }
+                cache.insert(id, hash);
+            }
+            if !unchanged.is_empty() {
+                info!(
+                    "Skipping {} unchanged songs, run with --rebuild to patch them again",
+                    unchanged.len()
+                );
+            }
+
+            let staging = staging::StagingDir::new(outdir)?;
+            let progress_bars = patch_progress::PatchProgressBars::new(maps.len());
+            let result: anyhow::Result<_> = try {
+                let mut sink = DirSink::with_layout(staging.path(), *output_layout, &mod_name);
+                let score_donor = map::Map::patch_files(
+                    romfs_root,
+                    &sink,
+                    &maps,
+                    *romfs_only,
+                    &unchanged,
+                    *jobs,
+                    &|p| progress_bars.update(p),
+                )?;
+
+                let mut asset_bundle = None;
+                if !*romfs_only {
+                    let names = maps
+                        .iter()
+                        .map(|m| m.song_info.id.to_string())
+                        .collect::<Vec<_>>();
+
+                    progress_bars.set_message("patching ExeFS");
+                    let main_exe_path = main_exe_path.as_ref().unwrap();
+                    let asset_bundle_info = exefs::patch_files(
+                        romfs_root,
+                        main_exe_path,
+                        &sink,
+                        &mod_name,
+                        &names,
+                        *exefs_format,
+                    )
+                    .with_context(|| {
+                        format!("while patching ExeFS of {}", main_exe_path.display())
+                    })?;
+                    asset_bundle = Some(asset_bundle_info);
+                }
+
+                let manifest = if *romfs_only {
+                    mod_merge::ModManifest {
+                        replaced_maps: maps.clone(),
+                        ..Default::default()
+                    }
+                } else {
+                    mod_merge::ModManifest {
+                        added_maps: maps.clone(),
+                        ..Default::default()
+                    }
+                    .with_score_donor(score_donor.as_deref())
+                };
+                manifest.write(&sink)?;
+                cache.write(&sink)?;
+
+                sink.finish()?;
+                (score_donor, asset_bundle)
+            };
+            match &result {
+                Ok(_) => progress_bars.finish(),
+                Err(_) => progress_bars.abandon(),
+            }
+
+            let (score_donor, asset_bundle) = match result {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Generation failed, {} is left unchanged", outdir.display());
+                    return Err(e);
+                }
+            };
+
+            if let Err(e) = staging.commit() {
+                error!(
+                    "Failed to move generated files, {} may be partially updated",
+                    outdir.display()
+                );
+                return Err(e.into());
+            }
+
+            let outputs = output_report::song_outputs(outdir, &maps, *output_layout, &mod_name);
+            info!(
+                "{}",
+                output_report::OutputReport(&outputs).to_string().trim_end()
+            );
+            if output_report::has_outliers(&outputs) {
+                warn!(
+                    "some songs have missing or suspiciously small audio output, check their \
+                     music files before installing the mod"
+                );
+            }
+            if let Some(score_donor) = score_donor {
+                info!("Scores of new songs are based on the score of {score_donor}");
+            }
+            if let Some(asset_bundle) = asset_bundle {
+                info!("{asset_bundle}");
+            }
+
+            if *record_inputs {
+                input_check::record_input_hashes(maps_path, &input_hashes).with_context(|| {
+                    format!("while recording inputs in {}", maps_path.display())
+                })?;
+            }
+
+            for warning in fs_check::check_output_tree(outdir)? {
+                warn!("{warning}");
+            }
+        }
+        Commands::GenerateIps {
+            patch_config,
+            main_exe_path,
+            outdir,
+            mod_name,
+            immediate_offset,
+            exefs_format,
+            output_layout,
+        } => {
+            let mod_name = mod_name
+                .clone()
+                .unwrap_or_else(|| exefs::default_mod_name(outdir));
+            let mut sink = DirSink::with_layout(outdir, *output_layout, &mod_name);
+            let build_id = exefs::generate_user_patch(
+                main_exe_path,
+                patch_config,
+                &sink,
+                &mod_name,
+                *immediate_offset,
+                *exefs_format,
+            )
+            .with_context(|| format!("while generating patch from {}", patch_config.display()))?;
+            sink.finish()?;
+
+            info!(
+                "Generated {exefs_format} patch for build {build_id} into {}",
+                outdir.display()
+            );
+        }
+        Commands::MergeMods {
+            romfs_root,
+            outdir,
+            mods,
+            main_exe_path,
+            mod_name,
+            exefs_format,
+            output_layout,
+            allow_patched_input,
+            allow_unsupported_build,
+        } => {
+            check_unpatched_input(romfs_root, *allow_patched_input)?;
+
+            let manifests = mods
+                .iter()
+                .map(|dir| mod_merge::ModManifest::read(dir))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            for (dir, manifest) in mods.iter().zip(&manifests) {
+                if let Some(mismatch) = manifest.version_mismatch() {
+                    warn!(
+                        "{} is {mismatch}, its songs may not work in the merged mod, regenerate \
+                         it with this version if they do not",
+                        dir.display()
+                    );
+                }
+            }
+            let manifest = mod_merge::ModManifest::merge(manifests)?;
+
+            if !manifest.added_maps.is_empty() {
+                match main_exe_path {
+                    Some(main_exe_path) => {
+                        check_supported_build(main_exe_path, *allow_unsupported_build)?
+                    }
+                    None => anyhow::bail!("--main-exe-path is required as merged mods add songs"),
+                }
+            }
+
+            let mod_name = mod_name
+                .clone()
+                .unwrap_or_else(|| exefs::default_mod_name(outdir));
+
+            let staging = staging::StagingDir::new(outdir)?;
+            // Unique per process so that concurrent merges do not share files
+            let work_dir = std::env::temp_dir()
+                .join(format!("spell_bubble_merge_mods_{}", std::process::id()));
+            fs::create_dir_all(&work_dir)?;
+
+            let result: anyhow::Result<_> = try {
+                let mut sink = DirSink::with_layout(staging.path(), *output_layout, &mod_name);
+                mod_merge::copy_mod_files(mods, &sink)?;
+
+                // Each step patches the share_data produced by the previous one
+                let original_share_data = romfs_root.join(platform::current().share_data());
+                let mut share_data = original_share_data.clone();
+
+                if let Some(unlock) = &manifest.unlock {
+                    let out_path = work_dir.join("share_data.unlocked");
+                    unlock_features(&share_data, &out_path, unlock)?;
+                    share_data = out_path;
+                }
+                if !manifest.replaced_maps.is_empty() {
+                    let out_path = work_dir.join("share_data.replaced");
+                    map::Map::patch_share_data_file(
+                        &share_data,
+                        &out_path,
+                        &manifest.replaced_maps,
+                        true,
+                    )?;
+                    share_data = out_path;
+                }
+
+                let mut asset_bundle = None;
+                if !manifest.added_maps.is_empty() {
+                    let out_path = work_dir.join("share_data.added");
+                    map::Map::patch_share_data_file(
+                        &share_data,
+                        &out_path,
+                        &manifest.added_maps,
+                        false,
+                    )?;
+                    share_data = out_path;
+
+                    let names = manifest
+                        .added_maps
+                        .iter()
+                        .map(|m| m.song_info.id.to_string())
+                        .collect::<Vec<_>>();
+
+                    asset_bundle = Some(exefs::patch_files(
+                        romfs_root,
+                        main_exe_path.as_ref().unwrap(),
+                        &sink,
+                        &mod_name,
+                        &names,
+                        *exefs_format,
+                    )?);
+                }
+
+                if share_data != original_share_data {
+                    fs::copy(
+                        &share_data,
+                        sink.file_path(&romfs_path(platform::current().share_data()))?,
+                    )?;
+                }
+
+                manifest.write(&sink)?;
+                sink.finish()?;
+                asset_bundle
+            };
+            let _ = fs::remove_dir_all(&work_dir);
+
+            let asset_bundle = match result {
+                Ok(asset_bundle) => asset_bundle,
+                Err(e) => {
+                    error!("Merging failed, {} is left unchanged", outdir.display());
+                    return Err(e);
+                }
+            };
+
+            if let Err(e) = staging.commit() {
+                error!(
+                    "Failed to move merged files, {} may be partially updated",
+                    outdir.display()
+                );
+                return Err(e.into());
+            }
+
+            info!(
+                "Merged {} mods: {} replaced songs, {} new songs{}",
+                mods.len(),
+                manifest.replaced_maps.len(),
+                manifest.added_maps.len(),
+                if manifest.unlock.is_some() {
+                    ", feature unlocks"
+                } else {
+                    ""
+                }
+            );
+            if let Some(asset_bundle) = asset_bundle {
+                info!("{asset_bundle}");
+            }
+        }
+        Commands::Package {
+            mod_dir,
+            archive,
+            mod_name,
+        } => {
+            let mod_name = mod_name
+                .clone()
+                .unwrap_or_else(|| exefs::default_mod_name(mod_dir));
+            let info = mod_package::package_mod(mod_dir, &mod_name, archive)?;
+
+            info!(
+                "Packaged {mod_name} with {} songs into {}",
+                info.songs.len(),
+                archive.display()
+            );
+            for song in &info.songs {
+                info!("  {}", song.id);
+            }
+        }
+        Commands::Init { maps, force } => {
+            if maps.exists() && !*force {
+                anyhow::bail!(
+                    "{} already exists, run with --force to overwrite it",
+                    maps.display()
+                );
+            }
+
+            fs::write(maps, map::EXAMPLE_CONFIG)?;
+            info!(
+                "Wrote example map config to {}, edit it and run PatchMap with it",
+                maps.display()
+            );
+        }
+        Commands::MigrateConfig { maps } => {
+            for maps_path in maps {
+                let content = fs::read_to_string(maps_path)?;
+                let version = map::MapsConfig::file_version(&content)?;
+                if version == map::CURRENT_SCHEMA_VERSION {
+                    info!("{}: already at version {version}", maps_path.display());
+                    continue;
+                }
+
+                let maps_config = map::MapsConfig::from_toml(&content)?;
+                let mut backup = maps_path.clone().into_os_string();
+                backup.push(format!(".v{version}.bak"));
+                fs::copy(maps_path, &backup)?;
+                fs::write(maps_path, toml::to_string_pretty(&maps_config)?)?;
+                info!(
+                    "{}: upgraded from version {version} to {}, the original is kept as {}",
+                    maps_path.display(),
+                    map::CURRENT_SCHEMA_VERSION,
+                    Path::new(&backup).display()
+                );
+            }
+        }
+        Commands::ListMaps {
+            maps,
+            romfs_only,
+            json,
+        } => {
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+
+            let summaries = map_list::summarize_maps(&maps_config, *romfs_only);
+            if *json {
+                println!("{}", serde_json::to_string_pretty(&summaries)?);
+            } else {
+                for summary in &summaries {
+                    println!("{summary}");
+                }
+            }
+        }
+        Commands::ConvertAdofai {
+            map,
+            adofai,
+            difficulty,
+            easy,
+            normal,
+            hard,
+            update,
+            id,
+            list,
+            snap_tolerance,
+        } => {
+            let mut maps_config = map::MapsConfig::read_for_update(map)?;
+
+            if *list {
+                print_map_list(&maps_config);
+                return Ok(());
+            }
+
+            let charts = [
+                (map::Difficulty::Easy, easy),
+                (map::Difficulty::Normal, normal),
+                (map::Difficulty::Hard, hard),
+            ]
+            .into_iter()
+            .filter_map(|(difficulty, path)| path.as_ref().map(|path| (difficulty, path)))
+            .collect::<Vec<_>>();
+            if !charts.is_empty() {
+                let map_obj = target_map(&mut maps_config, *update, id.as_deref());
+                convert_adofai_difficulties(map_obj, &charts, *snap_tolerance)?;
+
+                fs::write(map, toml::to_string_pretty(&maps_config)?)?;
+                return Ok(());
+            }
+
+            let adofai = adofai.as_ref().unwrap();
+            let difficulty = difficulty.unwrap();
+
+            if adofai.is_dir() {
+                if update.is_some() || id.is_some() {
+                    warn!("--update and --id are ignored when converting a directory");
+                }
+
+                let mut files = fs::read_dir(adofai)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .filter_ok(|path| path.extension().is_some_and(|ext| ext == "adofai"))
+                    .collect::<Result<Vec<_>, _>>()?;
+                files.sort();
+
+                for file in files {
+                    let mut chart = match load_adofai(&file) {
+                        Ok(chart) => chart,
+                        Err(e) => {
+                            warn!("{}: skipped, {e}", file.display());
+                            continue;
+                        }
+                    };
+
+                    let music_file = chart
+                        .song_filename()
+                        .map(|name| std::path::absolute(file.with_file_name(name)))
+                        .transpose()?
+                        .map(|path| path.to_string_lossy().to_string());
+                    let title = chart.title();
+
+                    // Charts are matched to existing entries by music file, or title if the
+                    // chart has no music file
+                    let existing = maps_config.maps.iter().position(|m| match &music_file {
+                        Some(music_file) => m.song_info.music_file == *music_file,
+                        None => {
+                            title.is_some()
+                                && m.song_info.info_text.get(&map::Lang::JA).map(|t| &t.title)
+                                    == title.as_ref()
+                        }
+                    });
+                    let idx = match existing {
+                        Some(idx) => {
+                            info!("{}: updated map {idx}", file.display());
+                            idx
+                        }
+                        None => {
+                            maps_config.maps.push(map::Map::default());
+                            info!(
+                                "{}: added map {}",
+                                file.display(),
+                                maps_config.maps.len() - 1
+                            );
+                            maps_config.maps.len() - 1
+                        }
+                    };
+
+                    let map_obj = &mut maps_config.maps[idx];
+                    convert_adofai(map_obj, &mut chart, difficulty, *snap_tolerance);
+                    if let Some(music_file) = music_file {
+                        map_obj.song_info.music_file = music_file;
+                    }
+                }
+            } else {
+                let mut chart = load_adofai(adofai)?;
+
+                let map_obj = target_map(&mut maps_config, *update, id.as_deref());
+                convert_adofai(map_obj, &mut chart, difficulty, *snap_tolerance);
+            }
+
+            fs::write(map, toml::to_string_pretty(&maps_config)?)?;
+        }
+        Commands::ConvertOsu {
+            map,
+            osu,
+            difficulty,
+            update,
+            list,
+            snap_tolerance,
+        } => {
+            let mut maps_config = map::MapsConfig::read_for_update(map)?;
+
+            if *list {
+                print_map_list(&maps_config);
+                return Ok(());
+            }
+
+            let osu = {
+                let content = fs::read_to_string(osu.as_ref().unwrap())?;
+                external_map::Osu::new(&content)?
+            };
+
+            let map_obj = match maps_config.maps.get_mut(update.unwrap_or(usize::MAX)) {
+                Some(map_obj) => map_obj,
+                None => {
+                    maps_config.maps.push(map::Map::default());
+                    maps_config.maps.last_mut().unwrap()
+                }
+            };
+
+            let score = osu.score();
+            map_obj.song_info.length = score.0.len() as u16;
+            map_obj.song_info.bpm = osu.initial_bpm().to_f32().unwrap();
+            map_obj.song_info.offset = osu.offset().to_f32().unwrap() / 1000.0;
             map_obj
                 .map_scores
-                .insert(difficulty.unwrap(), map::MapScore {
-                    scores: map::ScoreData(adofai.scores()),
-                });
+                .insert(difficulty.unwrap(), map::MapScore { scores: score });
 
-            let bpm_changes = adofai.bpm_changes();
-            if !bpm_changes.is_empty() {
-                map_obj.song_info.bpm_changes = map::BpmChanges(bpm_changes).into();
+            if let Some(bpm_changes) = osu.bpm_changes() {
+                map_obj.song_info.bpm_changes = Some(bpm_changes);
+            }
+
+            let adjustments = map::snap_bpms(
+                &mut map_obj.song_info.bpm,
+                &mut map_obj.song_info.bpm_changes,
+                *snap_tolerance,
+            );
+            for adjustment in adjustments {
+                info!("{adjustment}");
             }
 
             if map_obj.song_info.info_text.is_empty() {
@@ -278,15 +1743,652 @@ fn main() -> anyhow::Result<()> {
 
             fs::write(map, toml::to_string_pretty(&maps_config)?)?;
         }
+        Commands::ImportTempoMap {
+            tempo_map,
+            map,
+            update,
+            snap_tolerance,
+        } => {
+            let mut maps_config = map::MapsConfig::read_for_update(map)?;
+
+            let tempo_map = external_map::TempoMap::load(tempo_map)?;
+
+            let map_obj = match maps_config.maps.get_mut(update.unwrap_or(usize::MAX)) {
+                Some(map_obj) => map_obj,
+                None => {
+                    maps_config.maps.push(map::Map::default());
+                    maps_config.maps.last_mut().unwrap()
+                }
+            };
+
+            map_obj.song_info.bpm = tempo_map.initial_bpm();
+            map_obj.song_info.offset = tempo_map.offset();
+            map_obj.song_info.bpm_changes = tempo_map.bpm_changes();
+
+            let adjustments = map::snap_bpms(
+                &mut map_obj.song_info.bpm,
+                &mut map_obj.song_info.bpm_changes,
+                *snap_tolerance,
+            );
+            for adjustment in adjustments {
+                info!("{adjustment}");
+            }
+
+            fs::write(map, toml::to_string_pretty(&maps_config)?)?;
+        }
+        Commands::BuildMedley {
+            maps: maps_path,
+            indices,
+            id,
+            title,
+            audio_out,
+            crossfade,
+        } => {
+            let mut maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps_path)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+            // Expanded maps carry the resolved volume adjustment
+            let expanded_maps = maps_config.expanded_maps();
+
+            let maps = indices
+                .iter()
+                .map(|i| {
+                    expanded_maps
+                        .get(*i)
+                        .ok_or(anyhow::anyhow!("No map at index {i} in the config"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let audio_durations = maps
+                .iter()
+                .map(|map| map.song_info.audio_duration())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut medley = map::build_medley(&maps, &audio_durations, *crossfade, id, title)?;
+
+            let songs = maps
+                .iter()
+                .map(|map| {
+                    let audio_files = map
+                        .song_info
+                        .audio_files()
+                        .map(Path::new)
+                        .collect::<Vec<_>>();
+                    (audio_files, map.song_info.volume_db.unwrap_or_default())
+                })
+                .collect::<Vec<_>>();
+            ffmpeg_helper::crossfade_files(&songs, *crossfade, audio_out)?;
+            medley.song_info.music_file = std::path::absolute(audio_out)?
+                .to_string_lossy()
+                .to_string();
+            // Volume adjustments of joined songs are already applied to the audio
+            medley.song_info.volume_db = Some(0.0);
+
+            info!(
+                "Medley {id}: {} entries, {:.1}s of audio",
+                medley.song_info.length,
+                audio_durations.iter().sum::<f32>() - crossfade * (maps.len() - 1) as f32
+            );
+
+            maps_config.maps.push(medley);
+            fs::write(maps_path, toml::to_string_pretty(&maps_config)?)?;
+        }
+        Commands::PickPreview {
+            maps: maps_path,
+            all,
+        } => {
+            let mut maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps_path)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+
+            for map in maps_config.maps.iter_mut() {
+                if map.song_info.prev_start_ms != 0 && !*all {
+                    continue;
+                }
+
+                match preview_pick::pick_preview_start(&map.song_info) {
+                    Ok(prev_start_ms) => {
+                        info!("{}: preview starts at {prev_start_ms}ms", map.song_info.id);
+                        map.song_info.prev_start_ms = prev_start_ms;
+                    }
+                    Err(e) => warn!("{}: {e}", map.song_info.id),
+                }
+            }
+
+            fs::write(maps_path, toml::to_string_pretty(&maps_config)?)?;
+        }
+        Commands::DraftChart {
+            maps: maps_path,
+            index,
+            difficulty,
+            stem,
+            density,
+        } => {
+            let mut maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps_path)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+            let Some(map) = maps_config.maps.get_mut(*index) else {
+                anyhow::bail!("Map {index} does not exist in {}", maps_path.display());
+            };
+
+            let score = draft_chart::draft_map_score(map, stem.as_deref(), *density)?;
+            let notes = score.0.iter().filter(|e| **e != map::ScoreEntry::B).count();
+            info!(
+                "{}: drafted {difficulty} score with {notes} notes in {} entries",
+                map.song_info.id,
+                score.0.len()
+            );
+
+            if map.song_info.length == 0 {
+                map.song_info.length = score.0.len() as u16;
+            }
+            map.map_scores
+                .insert(*difficulty, map::MapScore { scores: score });
+
+            fs::write(maps_path, toml::to_string_pretty(&maps_config)?)?;
+        }
+        Commands::ExtractCharts {
+            romfs_root,
+            outdir,
+            music_id,
+            all: _,
+            format,
+            difficulty,
+        } => {
+            let maps = get_song_info(romfs_root)?
+                .maps
+                .into_iter()
+                .map(|m| m.map)
+                .filter(|m| {
+                    music_id
+                        .as_ref()
+                        .is_none_or(|id| m.song_info.id.to_string().eq_ignore_ascii_case(id))
+                })
+                .collect::<Vec<_>>();
+            if maps.is_empty() {
+                anyhow::bail!(
+                    "No song with music ID {}",
+                    music_id.as_deref().unwrap_or("")
+                );
+            }
+
+            let difficulties = match difficulty {
+                Some(difficulty) => vec![*difficulty],
+                None => vec![
+                    map::Difficulty::Easy,
+                    map::Difficulty::Normal,
+                    map::Difficulty::Hard,
+                ],
+            };
+
+            fs::create_dir_all(outdir)?;
+            for map in &maps {
+                let id = map.song_info.id.to_string();
+                let text = map
+                    .song_info
+                    .info_text
+                    .get(&map::Lang::JA)
+                    .cloned()
+                    .unwrap_or_default();
+
+                for difficulty in &difficulties {
+                    let out_path =
+                        outdir.join(format!("{id} [{difficulty}].{}", format.extension()));
+                    match format {
+                        ChartFormat::Osu => external_map::Osu::convert_from_map(
+                            map,
+                            *difficulty,
+                            &text.title(),
+                            &text.artist(),
+                            &id,
+                            &out_path,
+                        )?,
+                        ChartFormat::ADoFaI => {
+                            external_map::ADoFaIMap::convert_from_map(map, *difficulty, &out_path)?
+                        }
+                    }
+                }
+                info!("Exported {id}");
+            }
+        }
+        Commands::DiffRomfs {
+            old_romfs_root,
+            new_romfs_root,
+        } => {
+            let old = get_song_info(old_romfs_root)?;
+            let new = get_song_info(new_romfs_root)?;
+            print!("{}", romfs_diff::diff_song_infos(&old, &new));
+        }
+        Commands::CompareDifficulty { romfs_root, maps } => {
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+            let official = difficulty_compare::load_official_stats(romfs_root)?;
+
+            for map in maps_config.maps.iter() {
+                println!("{}:", map.song_info.id);
+                for comparison in difficulty_compare::compare_map(map, &official) {
+                    println!("  {comparison}");
+                }
+            }
+        }
+        Commands::ChartVariety { maps, index, top } => {
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+
+            for (i, map) in maps_config.maps.iter().enumerate() {
+                if index.is_some_and(|index| index != i) {
+                    continue;
+                }
+
+                println!("Map {i} ({}):", map.song_info.id);
+                for difficulty in map::Difficulty::iter() {
+                    if let Some(variety) = map.chart_variety(difficulty, *top) {
+                        println!("  {difficulty}: {variety}");
+                    }
+                }
+            }
+        }
+        Commands::DumpScores { romfs_root, outdir } => {
+            let infos = get_song_info(romfs_root)?;
+
+            fs::create_dir_all(outdir)?;
+            for map_info in &infos.maps {
+                let out_path = outdir.join(format!("{}.txt", map_info.map.song_info.id));
+                fs::write(out_path, score_scripts_text(map_info))?;
+            }
+            info!(
+                "Wrote scores of {} songs to {}",
+                infos.maps.len(),
+                outdir.display()
+            );
+        }
+        Commands::EstimateLevel {
+            easy,
+            normal,
+            hard,
+            bpm,
+        } => {
+            let charts = [
+                (map::Difficulty::Easy, easy),
+                (map::Difficulty::Normal, normal),
+                (map::Difficulty::Hard, hard),
+            ];
+            for (difficulty, path) in charts {
+                let Some(path) = path else { continue };
+
+                let mut map_obj = load_external_chart(path, difficulty)
+                    .with_context(|| format!("while reading {}", path.display()))?;
+                if let Some(bpm) = bpm {
+                    map_obj.song_info.bpm = *bpm;
+                }
+
+                println!(
+                    "{difficulty}: level {} ({})",
+                    map_obj.level(difficulty, None),
+                    path.display()
+                );
+            }
+        }
+        Commands::Preview {
+            source,
+            map,
+            difficulty,
+            romfs,
+        } => {
+            let map_obj = if *romfs {
+                read_song_info(source)?
+                    .maps
+                    .into_iter()
+                    .map(|map_info| map_info.map)
+                    .find(|m| m.song_info.id.to_string().eq_ignore_ascii_case(map))
+                    .ok_or_else(|| anyhow::anyhow!("No official song with ID {map}"))?
+            } else {
+                let maps_config = {
+                    let content = fs::read_to_string(source)?;
+                    map::MapsConfig::from_toml(&content)?
+                };
+                let index = map.parse::<usize>().map_err(|_| {
+                    anyhow::anyhow!("{map} is not a map index, pass --romfs for official songs")
+                })?;
+                maps_config
+                    .maps
+                    .into_iter()
+                    .nth(index)
+                    .ok_or(anyhow::anyhow!("No map at index {index} in the config"))?
+            };
+
+            match map_obj.score_preview(*difficulty) {
+                Some(preview) => println!("{preview}"),
+                None => anyhow::bail!("{} has no {difficulty} score", map_obj.song_info.id),
+            }
+        }
+        Commands::BeatCalc {
+            maps,
+            index,
+            beat,
+            time,
+        } => {
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+            let map = maps_config
+                .maps
+                .get(*index)
+                .ok_or(anyhow::anyhow!("No map at index {index} in the config"))?;
+
+            if let Some(beat) = beat {
+                println!("{}", map::format_timestamp(map.beat_time(*beat)));
+            } else if let Some(time) = time {
+                let seconds = map::parse_timestamp(time)
+                    .ok_or(anyhow::anyhow!("Invalid timestamp: {time}"))?;
+                match map.beat_index(seconds) {
+                    Some(beat) => {
+                        println!("{beat} ({})", map::format_timestamp(map.beat_time(beat)))
+                    }
+                    None => println!("{time} is before the first beat"),
+                }
+            }
+        }
         Commands::ExtractSongInfo {
             romfs_root,
-            out_csv,
+            out_file,
+            format,
+            langs,
+            all_langs,
+            excel,
+            semicolon,
+            score_stats,
+        } => {
+            let infos = read_song_info(romfs_root)?;
+            let langs = if *all_langs {
+                map::Lang::iter().collect()
+            } else {
+                langs.clone()
+            };
+
+            let format = format.unwrap_or_else(|| {
+                out_file
+                    .extension()
+                    .and_then(|ext| SongInfoFormat::from_str(&ext.to_string_lossy()).ok())
+                    .unwrap_or(SongInfoFormat::Csv)
+            });
+            match format {
+                SongInfoFormat::Csv => {
+                    let options = CsvOptions {
+                        langs:       langs.clone(),
+                        excel:       *excel,
+                        semicolon:   *semicolon,
+                        score_stats: *score_stats,
+                    };
+                    write_song_info_csv(&infos, out_file, &options)
+                }
+                SongInfoFormat::Xlsx => {
+                    write_song_info_xlsx(&infos, out_file, &langs, *score_stats)
+                }
+                SongInfoFormat::Json => write_song_info_json(&infos, out_file, *score_stats),
+            }
+            .with_context(|| format!("while writing song info to {}", out_file.display()))?;
+        }
+        Commands::InspectUnlocks {
+            romfs_root,
+            special_rules,
+            musics,
+            characters,
+            exclude,
+            include,
+        } => {
+            let dlc_musics = dlc_musics::DlcMusics::from_song_infos(&read_song_info(romfs_root)?);
+            print!("{dlc_musics}");
+
+            let share_data = romfs_root.join(platform::current().share_data());
+            let options = mod_merge::UnlockOptions {
+                special_rules: *special_rules,
+                musics:        *musics,
+                characters:    *characters,
+                exclude:       resolve_excluded_dlcs(&share_data, exclude, include)?,
+            };
+            if options != Default::default() {
+                println!("{}", dlc_musics.preview(&options));
+            }
+        }
+        Commands::ListDlcs { romfs_root } => {
+            let infos = get_song_info(romfs_root)?;
+            for dlc in dlc_list(&infos) {
+                println!("{}: {} ({} songs)", dlc.index, dlc.name, dlc.songs.len());
+                for song in &dlc.songs {
+                    println!("    {song}");
+                }
+            }
+        }
+        Commands::CheckDuplicates { maps, romfs_root } => {
+            let maps: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                map::MapsConfig::from_toml(&content)?
+            };
+
+            let official = romfs_root
+                .as_ref()
+                .map(|romfs_root| {
+                    get_song_info(romfs_root)
+                        .map(|infos| infos.maps.into_iter().map(|m| m.map).collect::<Vec<_>>())
+                })
+                .transpose()?
+                .unwrap_or_default();
+
+            let duplicates = duplicate_check::find_duplicates(&maps.maps, &official);
+            if duplicates.is_empty() {
+                println!("No duplicates found");
+            } else {
+                println!("{}", duplicates.iter().join("\n"));
+            }
+        }
+        Commands::Validate {
+            maps,
+            romfs_only,
+            no_strict,
+        } => {
+            let maps_config: map::MapsConfig = {
+                let content = fs::read_to_string(maps)?;
+                if *no_strict {
+                    map::MapsConfig::from_toml(&content)?
+                } else {
+                    map::MapsConfig::from_toml_strict(&content)?
+                }
+            };
+
+            let mut error_count = 0;
+            for (i, map) in maps_config.expanded_maps().iter().enumerate() {
+                let validation = map.validate_with_warnings(*romfs_only);
+                let status = match validation.status() {
+                    map::MapStatus::Ok => "OK",
+                    map::MapStatus::Warning => "warnings",
+                    map::MapStatus::Error => {
+                        error_count += 1;
+                        "errors"
+                    }
+                };
+
+                println!("Map {i} ({}): {status}", map.song_info.id);
+                for error in &validation.errors {
+                    println!("  error: {error}");
+                }
+                for warning in &validation.warnings {
+                    println!("  warning: {warning}");
+                }
+            }
+
+            if error_count > 0 {
+                anyhow::bail!("{error_count} maps have errors");
+            }
+        }
+        Commands::Doctor {
+            romfs_root,
+            main_exe_path,
         } => {
-            let infos = get_song_info(romfs_root);
+            let mut diagnostics = vec![doctor::check_ffmpeg(), doctor::check_ffprobe()];
+            if let Some(romfs_root) = romfs_root {
+                diagnostics.push(doctor::check_romfs(romfs_root));
+            }
+            if let Some(main_exe_path) = main_exe_path {
+                diagnostics.push(doctor::check_main_exe(main_exe_path));
+            }
+
+            println!("{}", diagnostics.iter().join("\n"));
 
-            write_song_info_csv(infos, out_csv)
+            let problem_count = diagnostics.iter().filter(|d| !d.is_ok()).count();
+            if problem_count > 0 {
+                anyhow::bail!("{problem_count} problems found");
+            }
+        }
+        Commands::CacheInfo => {
+            let display = |dir: Option<PathBuf>| {
+                dir.map(|dir| dir.display().to_string())
+                    .unwrap_or("unavailable".to_owned())
+            };
+            println!("Config: {}", display(app_dirs::config_dir()));
+            println!("Cache:  {}", display(app_dirs::cache_dir()));
+            println!("State:  {}", display(app_dirs::state_dir()));
+
+            let entries = app_dirs::cache_entries()?;
+            if entries.is_empty() {
+                println!("Cache is empty");
+            } else {
+                println!();
+                for (path, size) in &entries {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    println!("{:>10.1} KiB  {name}", *size as f64 / 1024.0);
+                }
+                let total: u64 = entries.iter().map(|(_, size)| size).sum();
+                println!("{:>10.1} KiB  total", total as f64 / 1024.0);
+            }
+        }
+        Commands::CacheClear => {
+            let freed = app_dirs::clear_cache()?;
+            println!("Freed {:.1} KiB of cached data", freed as f64 / 1024.0);
+        }
+        Commands::Completions { shell } => {
+            clap_complete::generate(
+                *shell,
+                &mut Args::command(),
+                "mod_tool",
+                &mut std::io::stdout(),
+            );
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn test_convert_adofai_args() {
+        let parse = |args: &[&str]| {
+            let args = Args::try_parse_from(
+                ["mod_tool", "classdata.tpk", "convert-adofai"]
+                    .iter()
+                    .chain(args),
+            );
+            match args.map(|args| args.command) {
+                Ok(Commands::ConvertAdofai {
+                    map,
+                    adofai,
+                    difficulty,
+                    easy,
+                    list,
+                    ..
+                }) => Ok((map, adofai, difficulty, easy, list)),
+                Ok(command) => panic!("parsed as {command:?}"),
+                Err(e) => Err(e.kind()),
+            }
+        };
+
+        let map = PathBuf::from("maps.toml");
+        let easy = Some(PathBuf::from("e.adofai"));
+        assert_eq!(
+            parse(&["--easy", "e.adofai", "maps.toml"]),
+            Ok((map.clone(), None, None, easy.clone(), false))
+        );
+        assert_eq!(
+            parse(&["maps.toml", "--easy", "e.adofai"]),
+            Ok((map.clone(), None, None, easy, false))
+        );
+        assert_eq!(
+            parse(&["maps.toml", "h.adofai", "hard"]),
+            Ok((
+                map.clone(),
+                Some(PathBuf::from("h.adofai")),
+                Some(map::Difficulty::Hard),
+                None,
+                false
+            ))
+        );
+        assert_eq!(
+            parse(&["--list", "maps.toml"]),
+            Ok((map, None, None, None, true))
+        );
+        assert_eq!(
+            parse(&["maps.toml", "h.adofai", "hard", "--easy", "e.adofai"]),
+            Err(clap::error::ErrorKind::ArgumentConflict)
+        );
+        assert_eq!(
+            parse(&["maps.toml"]),
+            Err(clap::error::ErrorKind::MissingRequiredArgument)
+        );
+    }
+
+    #[test]
+    fn test_convert_osu_args() {
+        let parse = |args: &[&str]| {
+            let args = Args::try_parse_from(
+                ["mod_tool", "classdata.tpk", "convert-osu"]
+                    .iter()
+                    .chain(args),
+            );
+            match args.map(|args| args.command) {
+                Ok(Commands::ConvertOsu {
+                    map,
+                    osu,
+                    difficulty,
+                    list,
+                    ..
+                }) => Ok((map, osu, difficulty, list)),
+                Ok(command) => panic!("parsed as {command:?}"),
+                Err(e) => Err(e.kind()),
+            }
+        };
+
+        let map = PathBuf::from("maps.toml");
+        assert_eq!(
+            parse(&["--list", "maps.toml"]),
+            Ok((map.clone(), None, None, true))
+        );
+        assert_eq!(
+            parse(&["maps.toml", "chart.osu", "normal"]),
+            Ok((
+                map,
+                Some(PathBuf::from("chart.osu")),
+                Some(map::Difficulty::Normal),
+                false
+            ))
+        );
+        assert_eq!(
+            parse(&["maps.toml"]),
+            Err(clap::error::ErrorKind::MissingRequiredArgument)
+        );
+    }
+}
@@ -1,20 +1,49 @@
+mod beat_time;
+mod bpm_cleanup;
+mod chart_stats;
+mod chart_variety;
+mod config_migration;
+mod diff;
 mod enums;
 mod interop;
+mod medley;
+mod offset_nudge;
+mod score_preview;
+mod strict_config;
+mod variant;
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display, Formatter},
+    io::Read,
     iter::zip,
     path::Path,
     str::FromStr,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering},
 };
 
+use anyhow::Context;
+pub use beat_time::{format_timestamp, parse_timestamp};
+pub use bpm_cleanup::{BpmAdjustment, DEFAULT_SNAP_TOLERANCE, snap_bpms};
+pub use chart_stats::ChartStats;
+pub use config_migration::{CURRENT_SCHEMA_VERSION, SchemaVersion};
+pub use diff::field_diff;
 pub use enums::{Area, Music};
-pub use interop::get_song_info;
+pub use interop::{get_song_info, lock_helper};
 use interop::{patch_acb_file, patch_score_file, patch_share_data};
 use itertools::Itertools;
+pub use medley::{DEFAULT_CROSSFADE, build_medley};
+pub use offset_nudge::OffsetNudge;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_with::{DisplayFromStr, serde_as};
+pub use strict_config::enable_strict;
+pub use variant::{ChartVariant, variant_maps};
+
+use crate::{
+    input_check::InputHashes,
+    output_sink::{OutputSink, romfs_path},
+    platform,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum InvalidMapError {
@@ -32,10 +61,158 @@ pub enum InvalidMapError {
     InvalidIDNotExists(MusicID),
     #[error("In exeFS mode, IDs must be non-existing ones (to prevent overwrite): {0}")]
     InvalidIDExists(MusicID),
+    #[error(
+        "Map score of {0} has {1} entries, longer than the maximum {2}. The game is observed to \
+         misbehave on console with overly long scores even when they work in emulators, shorten \
+         the score or raise the maximum if the length is confirmed to work on console"
+    )]
+    ScoreTooLong(Difficulty, usize, usize),
+    #[error(
+        "First note of {0} score is at index {1}, at least {2} blank entries are required before \
+         it as notes in the very first beats are unreadable in game"
+    )]
+    ShortLeadIn(Difficulty, usize, usize),
+    #[error(
+        "Unable to prepend {0} blank entries for lead-in, music offset {1}s is shorter than their \
+         duration {2}s"
+    )]
+    LeadInNotFixable(usize, f32, f32),
+    #[error("Music offset {0}s nudged by {1} becomes negative")]
+    NegativeOffset(f32, OffsetNudge),
+}
+
+/// Minimum count of blank entries before the first note below which a warning
+/// is shown before patching, shorter lead-in is only rejected when a minimum is
+/// given explicitly
+pub const DEFAULT_MIN_LEAD_IN: usize = 4;
+
+/// Map score length (entries) above which a warning is shown before patching.
+/// It is not a known limit of the game, but a conservative cap leaving headroom
+/// above long official scores, so longer scores are only rejected when a
+/// maximum is given explicitly
+pub const DEFAULT_MAX_SCORE_LENGTH: usize = 2000;
+
+/// Song whose score and acb files are used as the templates of new songs, it
+/// belongs to a DLC so other base game songs are searched if it is not in the
+/// dump
+const DEFAULT_SCORE_DONOR: &str = "Karisuma";
+
+/// Score files are Unity asset bundles
+const SCORE_FILE_MAGIC: &[u8] = b"UnityFS\0";
+/// Acb files are CRI @UTF tables
+const ACB_FILE_MAGIC: &[u8] = b"@UTF";
+
+/// Whether `path` is a file starting with `magic`
+fn has_magic(path: &Path, magic: &[u8]) -> bool {
+    let mut header = vec![0; magic.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| header == magic)
+}
+
+/// Whether the score and acb files of `song_id` in the dump are usable as
+/// templates of new songs
+fn is_score_donor(game_files_dir: &Path, song_id: &str) -> bool {
+    let platform = platform::current();
+    has_magic(
+        &game_files_dir.join(platform.score_file(song_id)),
+        SCORE_FILE_MAGIC,
+    ) && has_magic(
+        &game_files_dir.join(platform.sound_file(song_id, "acb")),
+        ACB_FILE_MAGIC,
+    )
+}
+
+/// Picks the song whose score and acb files new songs are based on: the
+/// default donor if present, otherwise the first base game song with both
+/// files in the dump
+pub fn score_donor(game_files_dir: &Path) -> std::io::Result<String> {
+    if is_score_donor(game_files_dir, DEFAULT_SCORE_DONOR) {
+        return Ok(DEFAULT_SCORE_DONOR.to_owned());
+    }
+
+    get_song_info(game_files_dir)?
+        .into_iter()
+        .map(|(map, ..)| map.song_info)
+        .filter(|song_info| song_info.dlc_index == 0)
+        .map(|song_info| song_info.id.to_string())
+        .sorted()
+        .find(|id| is_score_donor(game_files_dir, id))
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!(
+                    "No score and acb files usable as the templates of new songs are found in {} \
+                     and {}, check that the RomFS dump is complete",
+                    platform::current().score_dir(),
+                    platform::current().sounds_dir()
+                ),
+            )
+        })
+}
+
+/// A step of [`Map::patch_files`] that has started
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchProgress<'a> {
+    /// The song of the ID entered the phase
+    Song(&'a str, SongPhase),
+    /// All songs are patched and share_data is being patched
+    ShareData,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SongPhase {
+    /// Audio files are converted to wav by ffmpeg
+    Converting,
+    /// Audio is encoded into the acb and awb files
+    PatchingAcb,
+    PatchingScore,
+    Done,
+}
+
+/// Overall state of a map, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, strum::Display, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum MapStatus {
+    Ok,
+    /// Patching works but the song is likely broken in game
+    Warning,
+    /// Patching fails
+    Error,
+}
+
+/// Problems found in a map by `Map::validate_with_warnings`
+#[derive(Debug, Default)]
+pub struct MapValidation {
+    pub errors:   Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl MapValidation {
+    pub fn status(&self) -> MapStatus {
+        if !self.errors.is_empty() {
+            MapStatus::Error
+        } else if !self.warnings.is_empty() {
+            MapStatus::Warning
+        } else {
+            MapStatus::Ok
+        }
+    }
 }
 
 #[derive(
-    Eq, PartialEq, Hash, Clone, strum::Display, strum::EnumString, Serialize, Deserialize, Default,
+    Eq,
+    PartialEq,
+    Hash,
+    Clone,
+    Debug,
+    strum::Display,
+    strum::EnumString,
+    strum::EnumIter,
+    Serialize,
+    Deserialize,
+    Default,
 )]
 #[strum(ascii_case_insensitive)]
 pub enum Lang {
@@ -88,6 +265,27 @@ impl SongInfoText {
     pub fn original(&self) -> String {
         self.original.clone()
     }
+
+    /// Replaces `{name}` placeholders in all fields with values in `variables`
+    fn expand_templates(&self, variables: &HashMap<String, String>) -> Self {
+        let expand = |field: &str| {
+            variables
+                .iter()
+                .fold(field.to_owned(), |field, (name, value)| {
+                    field.replace(&format!("{{{name}}}"), value)
+                })
+        };
+
+        Self {
+            title:       expand(&self.title),
+            title_kana:  expand(&self.title_kana),
+            sub_title:   expand(&self.sub_title),
+            artist:      expand(&self.artist),
+            artist2:     expand(&self.artist2),
+            artist_kana: expand(&self.artist_kana),
+            original:    expand(&self.original),
+        }
+    }
 }
 
 /// (u16, f32) is Index, TargetBpm pair
@@ -319,11 +517,55 @@ impl From<String> for MusicID {
     }
 }
 
+impl MusicID {
+    /// Returns `id` if it is free, otherwise `id` with the smallest number
+    /// starting from 2 appended that makes it free. IDs of official songs and
+    /// those that `is_taken` returns true for are not free.
+    pub fn dedup(id: &str, is_taken: impl Fn(&str) -> bool) -> String {
+        let is_free = |id: &str| Music::try_from(id).is_err() && !is_taken(id);
+        if is_free(id) {
+            return id.to_owned();
+        }
+
+        (2..)
+            .map(|n| format!("{id}{n}"))
+            .find(|id| is_free(id))
+            .unwrap()
+    }
+}
+
+/// Turns a title into an identifier by joining ASCII alphanumeric parts of its
+/// words in CamelCase, returns an empty string if there are none
+fn sanitize_id(title: &str) -> String {
+    let id = title
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect::<String>();
+
+    if id.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("Song{id}")
+    } else {
+        id
+    }
+}
+
 #[serde_as]
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct SongInfo {
     pub id:            MusicID,
     pub music_file:    String,
+    /// Audio played once before `music_file`, joined without gap when encoding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intro_file:    Option<String>,
+    /// Volume adjustment in dB applied when converting audio, overrides the
+    /// project-level `volume_db`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_db:     Option<f32>,
     pub bpm:           f32,
     pub offset:        f32,
     pub length:        u16,
@@ -340,6 +582,34 @@ pub struct SongInfo {
 }
 
 impl SongInfo {
+    /// Audio files in play order, the intro file comes first if there is one
+    pub fn audio_files(&self) -> impl Iterator<Item = &String> {
+        self.intro_file
+            .iter()
+            .chain(std::iter::once(&self.music_file))
+    }
+
+    /// Suggests a new ID from the title, preferring the English one since
+    /// titles in other languages may have few latin characters. It is
+    /// deduplicated with [`MusicID::dedup`].
+    pub fn suggest_id(&self, is_taken: impl Fn(&str) -> bool) -> String {
+        let id = [Lang::EN, Lang::JA, Lang::KO, Lang::Chs, Lang::Cht]
+            .iter()
+            .filter_map(|lang| self.info_text.get(lang))
+            .map(|text| sanitize_id(&text.title))
+            .find(|id| !id.is_empty())
+            .unwrap_or_else(|| "CustomSong".to_owned());
+
+        MusicID::dedup(&id, is_taken)
+    }
+
+    /// Total duration of audio files in seconds
+    pub fn audio_duration(&self) -> std::io::Result<f32> {
+        self.audio_files()
+            .map(|file| crate::ffmpeg_helper::audio_duration(Path::new(file)))
+            .sum()
+    }
+
     fn validate(&self) -> Result<(), InvalidMapError> {
         for text in self.info_text.values() {
             text.validate()?
@@ -360,6 +630,7 @@ impl SongInfo {
 #[derive(
     strum::Display,
     strum::EnumString,
+    strum::EnumIter,
     Eq,
     PartialEq,
     Hash,
@@ -557,104 +828,399 @@ impl Map {
         Ok(())
     }
 
-    pub fn patch_files<T, U>(
-        game_files_dir: &Path,
-        out_dir: &Path,
-        maps: T,
-        replace_existing: bool,
-    ) -> std::io::Result<()>
-    where
-        T: IntoIterator<Item = U> + Clone,
-        U: std::borrow::Borrow<Map>,
-    {
-        let mut share_data_path = game_files_dir.to_owned();
-        share_data_path.push("StreamingAssets/Switch/share_data");
+    /// Checks that no map score is longer than `max_length` entries
+    pub fn validate_length(&self, max_length: usize) -> Result<(), InvalidMapError> {
+        match self
+            .map_scores
+            .iter()
+            .find(|(_, score)| score.scores.0.len() > max_length)
+        {
+            Some((difficulty, score)) => Err(InvalidMapError::ScoreTooLong(
+                *difficulty,
+                score.scores.0.len(),
+                max_length,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Validates the map like `validate` and checks its audio files exist and
+    /// BPMs are positive, which fail patching. Lead-in and score length are
+    /// checked with default limits as warnings, which are only enforced by CLI
+    /// options, along with BPM changes and `length` inconsistent with scores
+    pub fn validate_with_warnings(&self, replace_existing: bool) -> MapValidation {
+        let mut validation = MapValidation::default();
+
+        if let Err(e) = self.validate(replace_existing) {
+            validation.errors.push(e.to_string());
+        }
+        if self.song_info.music_file.is_empty() {
+            validation.errors.push("No music file provided".to_owned());
+        }
+        for file in self.song_info.audio_files() {
+            if !file.is_empty() && !Path::new(file).is_file() {
+                validation
+                    .errors
+                    .push(format!("Audio file {file} not found"));
+            }
+        }
 
-        let mut out_base_path = out_dir.to_owned();
-        out_base_path.push("contents/0100E9D00D6C2000/romfs/Data");
+        validation.warnings.extend(
+            [
+                self.validate_lead_in(DEFAULT_MIN_LEAD_IN),
+                self.validate_length(DEFAULT_MAX_SCORE_LENGTH),
+            ]
+            .into_iter()
+            .filter_map(Result::err)
+            .map(|e| e.to_string()),
+        );
 
-        let mut out_share_data_path = out_base_path.to_owned();
-        out_share_data_path.push("StreamingAssets/Switch/share_data");
+        self.check_timing(&mut validation);
 
-        let mut share_scores_dir = out_base_path.clone();
-        share_scores_dir.push("StreamingAssets/Switch/share_scores");
+        validation
+    }
+
+    fn check_timing(&self, validation: &mut MapValidation) {
+        let bpm = self.song_info.bpm;
+        if !(bpm.is_finite() && bpm > 0.0) {
+            validation
+                .errors
+                .push(format!("Initial BPM {bpm} is not positive"));
+        }
 
-        let mut sounds_dir = out_base_path.clone();
-        sounds_dir.push("StreamingAssets/Sounds");
+        let len = self
+            .map_scores
+            .values()
+            .map(|score| score.scores.0.len())
+            .max()
+            .unwrap_or_default();
 
-        [&share_scores_dir, &sounds_dir]
+        let bpm_changes = self
+            .song_info
+            .bpm_changes
             .iter()
-            .map(std::fs::create_dir_all)
-            .collect::<Result<Vec<_>, _>>()?;
-
-        for map in maps.clone() {
-            let map = map.borrow();
-            let song_id = map.song_info.id.to_string();
-
-            let mut acb_path = game_files_dir.to_owned();
-            // The corresponding acb file was used for patching, but that causes many
-            // problems (unable to play, early stop freeze, not stopping freeze), a fixed
-            // DLC music is used instead now.
-
-            // acb_path.push(format!(
-            //     "StreamingAssets/Sounds/BGM_{}.acb",
-            //     song_id.to_uppercase()
-            // ));
-            acb_path.push("StreamingAssets/Sounds/BGM_KARISUMA.acb");
-
-            let mut out_acb_path = out_base_path.to_owned();
-            out_acb_path.push(format!(
-                "StreamingAssets/Sounds/BGM_{}.acb",
-                song_id.to_uppercase()
-            ));
+            .flat_map(|changes| changes.0.iter());
+        let mut last_idx = None;
+        for (idx, bpm) in bpm_changes {
+            if !(bpm.is_finite() && *bpm > 0.0) {
+                validation.errors.push(format!(
+                    "BPM change at index {idx} to {bpm} is not positive"
+                ));
+            }
+            if *idx as usize >= len {
+                validation.warnings.push(format!(
+                    "BPM change at index {idx} is beyond the end of scores ({len} entries)"
+                ));
+            }
+            if last_idx.is_some_and(|last_idx| *idx <= last_idx) {
+                validation
+                    .warnings
+                    .push(format!("BPM change at index {idx} is out of order"));
+            }
+            last_idx = Some(*idx);
+        }
 
-            let mut out_awb_path = out_base_path.to_owned();
-            out_awb_path.push(format!(
-                "StreamingAssets/Sounds/BGM_{}.awb",
-                song_id.to_uppercase()
+        if len != self.song_info.length as usize {
+            validation.warnings.push(format!(
+                "Length {} differs from the longest score ({len} entries)",
+                self.song_info.length
             ));
+        }
+    }
 
-            let mut score_path = game_files_dir.to_owned();
-            if replace_existing {
-                score_path.push(format!(
-                    "StreamingAssets/Switch/share_scores/score_{}",
-                    song_id.to_lowercase()
-                ));
-            } else {
-                score_path.push("StreamingAssets/Switch/share_scores/score_karisuma");
+    /// Returns the index of first note for every non-empty map score
+    fn first_notes(&self) -> impl Iterator<Item = (Difficulty, usize)> + '_ {
+        self.map_scores.iter().filter_map(|(difficulty, score)| {
+            let first_note = score.scores.0.iter().position(|e| *e != ScoreEntry::B)?;
+            Some((*difficulty, first_note))
+        })
+    }
+
+    /// Checks that every map score has at least `min_lead_in` blank entries
+    /// before the first note, reporting the map score with the shortest one
+    pub fn validate_lead_in(&self, min_lead_in: usize) -> Result<(), InvalidMapError> {
+        match self
+            .first_notes()
+            .filter(|(_, idx)| *idx < min_lead_in)
+            .min_by_key(|(_, idx)| *idx)
+        {
+            Some((difficulty, idx)) => {
+                Err(InvalidMapError::ShortLeadIn(difficulty, idx, min_lead_in))
             }
+            None => Ok(()),
+        }
+    }
+
+    /// Prepends blank entries to all map scores so that each has at least
+    /// `min_lead_in` blank entries before the first note. The music offset is
+    /// moved earlier to keep notes in sync, returns the count of entries added
+    pub fn fix_lead_in(&mut self, min_lead_in: usize) -> Result<usize, InvalidMapError> {
+        let added = self
+            .first_notes()
+            .map(|(_, idx)| min_lead_in.saturating_sub(idx))
+            .max()
+            .unwrap_or_default();
+        if added == 0 {
+            return Ok(0);
+        }
 
-            let mut out_score_path = out_base_path.to_owned();
-            out_score_path.push(format!(
-                "StreamingAssets/Switch/share_scores/score_{}",
-                song_id.to_lowercase()
+        let added_duration = added as f32 * 60.0 / self.song_info.bpm;
+        if added_duration > self.song_info.offset {
+            return Err(InvalidMapError::LeadInNotFixable(
+                added,
+                self.song_info.offset,
+                added_duration,
             ));
+        }
+
+        for score in self.map_scores.values_mut() {
+            score
+                .scores
+                .0
+                .splice(0..0, std::iter::repeat_n(ScoreEntry::B, added));
+        }
+        if let Some(bpm_changes) = self.song_info.bpm_changes.as_mut() {
+            for (idx, _) in bpm_changes.0.iter_mut() {
+                *idx += added as u16;
+            }
+        }
+        self.song_info.offset -= added_duration;
+        self.song_info.length += added as u16;
+
+        Ok(added)
+    }
+
+    /// BPM in effect at score index `idx`, a BPM change takes effect after its
+    /// index as in `beat_time_table`
+    fn bpm_at(&self, idx: usize) -> f32 {
+        self.song_info
+            .bpm_changes
+            .iter()
+            .flat_map(|bc| bc.0.iter())
+            .rfind(|(i, _)| (*i as usize) < idx)
+            .map_or(self.song_info.bpm, |(_, bpm)| *bpm)
+    }
+
+    /// Pads map scores with trailing blanks so they end `margin` seconds before
+    /// `audio_duration`, returns the maximum count of entries added. Nothing is
+    /// padded if any BPM is not positive, as beats would never reach the end.
+    pub fn pad_to_duration(&mut self, audio_duration: f32, margin: f32) -> usize {
+        let target_time = audio_duration - self.song_info.offset - margin;
+        let bpms_valid = std::iter::once(&self.song_info.bpm)
+            .chain(
+                self.song_info
+                    .bpm_changes
+                    .iter()
+                    .flat_map(|changes| changes.0.iter().map(|(_, bpm)| bpm)),
+            )
+            .all(|bpm| bpm.is_finite() && *bpm > 0.0);
+        if !bpms_valid || !target_time.is_finite() {
+            return 0;
+        }
 
-            patch_acb_file(
-                &map.song_info.music_file,
-                &acb_path,
-                &out_acb_path,
-                &out_awb_path,
-                map.song_info.prev_start_ms,
-            )?;
+        let padded_lengths = self
+            .map_scores
+            .iter()
+            .map(|(difficulty, score)| {
+                let mut len = score.scores.0.len();
+                let mut time: f32 = (0..len).map(|i| 60.0 / self.bpm_at(i)).sum();
+                loop {
+                    let next_time = time + 60.0 / self.bpm_at(len);
+                    if next_time > target_time {
+                        break;
+                    }
+                    time = next_time;
+                    len += 1;
+                }
+                (*difficulty, len)
+            })
+            .collect::<Vec<_>>();
 
-            patch_score_file(
-                &score_path,
-                &out_score_path,
-                &song_id,
-                &map.map_scores,
-                &map.song_info.bpm_changes,
-                replace_existing,
-            );
+        let mut max_added = 0;
+        for (difficulty, len) in padded_lengths {
+            let scores = &mut self.map_scores.get_mut(&difficulty).unwrap().scores.0;
+            max_added = max_added.max(len - scores.len());
+            scores.resize(len, ScoreEntry::B);
         }
 
+        let max_len = self.map_scores.values().map(|s| s.scores.0.len()).max();
+        if let Some(max_len) = max_len {
+            self.song_info.length = self.song_info.length.max(max_len as u16);
+        }
+
+        max_added
+    }
+
+    /// Collapses redundant BPM changes, which bloat the beat script and may
+    /// confuse the game
+    pub fn collapse_bpm_changes(&mut self) -> Vec<BpmAdjustment> {
+        bpm_cleanup::collapse_bpm_changes(self.song_info.bpm, &mut self.song_info.bpm_changes)
+    }
+
+    /// Patches audio and score files of `maps` with up to `jobs` songs
+    /// processed at once, then patches share_data with all of them. Audio and
+    /// score files of songs with IDs in `unchanged` are not patched, as they
+    /// are kept from a previous run. Steps are reported to `progress` as they
+    /// start.
+    ///
+    /// Returns the ID of the song whose score file scores of new songs are
+    /// based on, `None` when replacing existing songs. Its acb file is the
+    /// template of audio of all songs.
+    pub fn patch_files<T, U>(
+        game_files_dir: &Path,
+        sink: &(dyn OutputSink + Sync),
+        maps: T,
+        replace_existing: bool,
+        unchanged: &HashSet<String>,
+        jobs: usize,
+        progress: &(dyn Fn(PatchProgress) + Sync),
+    ) -> anyhow::Result<Option<String>>
+    where
+        T: IntoIterator<Item = U> + Clone,
+        U: std::borrow::Borrow<Map> + Sync,
+    {
+        let share_data_path = game_files_dir.join(platform::current().share_data());
+
+        let donor =
+            score_donor(game_files_dir).context("finding the song new songs are based on")?;
+        let score_donor = (!replace_existing).then_some(donor.as_str());
+
+        let song_maps = maps.clone().into_iter().collect::<Vec<_>>();
+        let next_idx = AtomicUsize::new(0);
+        let failed = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            let workers = (0..jobs.clamp(1, song_maps.len().max(1)))
+                .map(|_| {
+                    scope.spawn(|| -> anyhow::Result<()> {
+                        while !failed.load(AtomicOrdering::Relaxed) {
+                            let idx = next_idx.fetch_add(1, AtomicOrdering::Relaxed);
+                            let Some(map) = song_maps.get(idx) else { break };
+
+                            // Files of unchanged songs are kept in the output directory
+                            let song_id = map.borrow().song_info.id.to_string();
+                            if unchanged.contains(&song_id) {
+                                progress(PatchProgress::Song(&song_id, SongPhase::Done));
+                                continue;
+                            }
+
+                            let result = map.borrow().patch_song_files(
+                                game_files_dir,
+                                sink,
+                                &donor,
+                                score_donor,
+                                progress,
+                            );
+                            if result.is_err() {
+                                failed.store(true, AtomicOrdering::Relaxed);
+                            }
+                            result?;
+                        }
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers.into_iter().try_for_each(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("a song patching thread panicked")))
+            })
+        })?;
+
+        progress(PatchProgress::ShareData);
+        let out_share_data_path = sink.file_path(&romfs_path(platform::current().share_data()))?;
         patch_share_data(
             &share_data_path,
             &out_share_data_path,
             maps,
             replace_existing,
-        );
+        )
+        .with_context(|| format!("patching {}", share_data_path.display()))?;
+
+        Ok(score_donor.map(str::to_owned))
+    }
 
+    /// Patches song info of `maps` into share_data without patching their audio
+    /// and score files, used to apply patches of several mods in turn
+    pub fn patch_share_data_file(
+        share_data_path: &Path,
+        out_share_data_path: &Path,
+        maps: &[Map],
+        replace_existing: bool,
+    ) -> std::io::Result<()> {
+        patch_share_data(share_data_path, out_share_data_path, maps, replace_existing)
+    }
+
+    /// Patches audio and score files of this song, which are separate for each
+    /// song so songs can be patched in parallel. Errors tell the song, step and
+    /// file that failed.
+    fn patch_song_files(
+        &self,
+        game_files_dir: &Path,
+        sink: &(dyn OutputSink + Sync),
+        acb_donor: &str,
+        score_donor: Option<&str>,
+        progress: &(dyn Fn(PatchProgress) + Sync),
+    ) -> anyhow::Result<()> {
+        let song_id = self.song_info.id.to_string();
+        progress(PatchProgress::Song(&song_id, SongPhase::Converting));
+
+        let platform = platform::current();
+
+        let mut acb_path = game_files_dir.to_owned();
+        // The corresponding acb file was used for patching, but that causes many
+        // problems (unable to play, early stop freeze, not stopping freeze), the
+        // acb file of the donor song is used instead now.
+
+        // acb_path.push(format!(
+        //     "StreamingAssets/Sounds/BGM_{}.acb",
+        //     song_id.to_uppercase()
+        // ));
+        acb_path.push(platform.sound_file(acb_donor, "acb"));
+
+        let out_acb_path = sink.file_path(&romfs_path(platform.sound_file(&song_id, "acb")))?;
+
+        let out_awb_path = sink.file_path(&romfs_path(platform.sound_file(&song_id, "awb")))?;
+
+        let score_path = game_files_dir.join(platform.score_file(score_donor.unwrap_or(&song_id)));
+
+        let out_score_path = sink.file_path(&romfs_path(platform.score_file(&song_id)))?;
+
+        patch_acb_file(
+            &self.song_info.music_file,
+            self.song_info.intro_file.as_deref(),
+            self.song_info.volume_db.unwrap_or_default(),
+            &acb_path,
+            &out_acb_path,
+            &out_awb_path,
+            self.song_info.prev_start_ms,
+            || progress(PatchProgress::Song(&song_id, SongPhase::PatchingAcb)),
+        )
+        .with_context(|| {
+            format!(
+                "while patching song {song_id}, converting audio file {}",
+                self.song_info.music_file
+            )
+        })?;
+
+        progress(PatchProgress::Song(&song_id, SongPhase::PatchingScore));
+        patch_score_file(
+            &score_path,
+            &out_score_path,
+            &song_id,
+            &self.map_scores,
+            &self.song_info.bpm_changes,
+            score_donor,
+        )
+        .with_context(|| {
+            format!(
+                "while patching song {song_id}, patching score file {}",
+                score_path.display()
+            )
+        })?;
+
+        progress(PatchProgress::Song(&song_id, SongPhase::Done));
         Ok(())
     }
 
@@ -711,14 +1277,6 @@ impl Map {
         *self.beat_time_table().last().unwrap()
     }
 
-    pub fn levels(&self) -> (u8, u8, u8) {
-        (
-            self.level(Difficulty::Easy, None),
-            self.level(Difficulty::Normal, None),
-            self.level(Difficulty::Hard, None),
-        )
-    }
-
     pub fn level(&self, difficulty: Difficulty, score_str: Option<&str>) -> u8 {
         // I can't find out how this still differs the origin implementation (maybe due
         // to architecture differences?), so I will hard code thesw wrong value
@@ -804,9 +1362,133 @@ impl Map {
     }
 }
 
-#[derive(Serialize, Deserialize)]
+/// Commented map config with a replacing map and a new map, written by Init
+pub const EXAMPLE_CONFIG: &str = include_str!("maps_example.toml");
+
+#[derive(Default, Serialize, Deserialize)]
 pub struct MapsConfig {
-    pub maps: Vec<Map>,
+    /// Version of the config layout, older configs are upgraded on load
+    #[serde(default)]
+    pub schema_version: SchemaVersion,
+    pub maps:           Vec<Map>,
+    /// Project-level variables expanded in info_text fields at patch time
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub variables:      HashMap<String, String>,
+    /// Hashes of game files the mod was generated from, to detect game updates
+    #[serde(default, skip_serializing_if = "InputHashes::is_empty")]
+    pub input_hashes:   InputHashes,
+    /// Volume adjustment in dB applied to every map without its own
+    /// `volume_db`, to bring a whole pack in line with the game's mix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume_db:      Option<f32>,
+    /// Variant charts added as new songs for every map, only in exeFS mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants:       Vec<ChartVariant>,
+    /// Shift of music offsets of all maps compensating display latency
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_nudge:   Option<OffsetNudge>,
+    /// Title ID of the game release in output paths, for dumps of other
+    /// regions or releases
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_id:       Option<String>,
+}
+
+impl MapsConfig {
+    /// Returns maps with `{name}` placeholders in info_text expanded. Besides
+    /// `variables`, `{index}` (1-based position in the config) and `{id}` are
+    /// available for every map. Maps without `volume_db` get the project-level
+    /// one.
+    pub fn expanded_maps(&self) -> Vec<Map> {
+        self.maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| {
+                let mut variables = self.variables.clone();
+                variables.insert("index".to_owned(), (i + 1).to_string());
+                variables.insert("id".to_owned(), map.song_info.id.to_string());
+
+                let mut map = map.clone();
+                map.song_info.info_text = map
+                    .song_info
+                    .info_text
+                    .iter()
+                    .map(|(lang, text)| (lang.clone(), text.expand_templates(&variables)))
+                    .collect();
+                map.song_info.volume_db = map.song_info.volume_db.or(self.volume_db);
+                map
+            })
+            .collect()
+    }
+
+    /// Validates maps as PatchMap does, where either all maps replace existing
+    /// songs (`--romfs-only`) or all add new ones, told by the first map
+    pub fn validate_maps(&self) -> anyhow::Result<()> {
+        let replace_existing = self
+            .maps
+            .first()
+            .is_some_and(|map| matches!(map.song_info.id, MusicID::Existing(_)));
+
+        for map in &self.maps {
+            map.validate(replace_existing)
+                .with_context(|| format!("while validating map {}", map.song_info.id))?;
+        }
+        Ok(())
+    }
+
+    /// Replaces maps with the edited ones in `maps` keyed by ID, keeping the
+    /// order of maps and other settings of the config. Maps missing from `maps`
+    /// are removed and new ones appended by ID. Of maps sharing an ID, only the
+    /// last one is replaced, as it is the one kept when keying maps by ID.
+    pub fn update_maps(&mut self, maps: &HashMap<String, Map>) {
+        let last_indices = self
+            .maps
+            .iter()
+            .enumerate()
+            .map(|(i, map)| (map.song_info.id.to_string(), i))
+            .collect::<HashMap<_, _>>();
+
+        self.maps = std::mem::take(&mut self.maps)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, map)| {
+                let id = map.song_info.id.to_string();
+                let edited = maps.get(&id)?;
+                Some(if last_indices[&id] == i {
+                    edited.clone()
+                } else {
+                    map
+                })
+            })
+            .collect();
+
+        let added_maps = maps
+            .iter()
+            .filter(|(id, _)| !last_indices.contains_key(*id))
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(_, map)| map.clone());
+        self.maps.extend(added_maps);
+    }
+}
+
+/// Keeps maps with IDs in `only` (all maps if it is empty) and not in `skip`,
+/// comparing IDs case-insensitively. IDs in `only` or `skip` matching no map
+/// are returned as well, as they are likely typos.
+pub fn filter_maps(maps: Vec<Map>, only: &[String], skip: &[String]) -> (Vec<Map>, Vec<String>) {
+    let matches = |id: &String, map: &Map| map.song_info.id.to_string().eq_ignore_ascii_case(id);
+
+    let unknown = only
+        .iter()
+        .chain(skip)
+        .filter(|id| !maps.iter().any(|map| matches(id, map)))
+        .cloned()
+        .collect();
+    let maps = maps
+        .into_iter()
+        .filter(|map| only.is_empty() || only.iter().any(|id| matches(id, map)))
+        .filter(|map| !skip.iter().any(|id| matches(id, map)))
+        .collect();
+
+    (maps, unknown)
 }
 
 #[cfg(test)]
@@ -821,6 +1503,8 @@ mod test {
             song_info:  SongInfo {
                 id:            MusicID::Existing(Music::Agepoyo),
                 music_file:    "file_path".to_string(),
+                intro_file:    None,
+                volume_db:     None,
                 bpm:           150.0,
                 offset:        0.01,
                 length:        1500,
@@ -852,6 +1536,8 @@ mod test {
             song_info:  SongInfo {
                 id:            MusicID::New("Newly".to_string()),
                 music_file:    "file_path2".to_string(),
+                intro_file:    None,
+                volume_db:     None,
                 bpm:           152.0,
                 offset:        0.02,
                 length:        1502,
@@ -881,11 +1567,226 @@ mod test {
 
         let maps = MapsConfig {
             maps: vec![map1, map2],
+            ..Default::default()
         };
 
         println!("{}", toml::to_string_pretty(&maps).unwrap());
     }
 
+    #[test]
+    fn test_example_config() {
+        let config: MapsConfig = toml::from_str(EXAMPLE_CONFIG).unwrap();
+        let maps = config.expanded_maps();
+        assert_eq!(maps.len(), 2);
+
+        maps[0].validate(true).unwrap();
+        maps[1].validate(false).unwrap();
+        for map in &maps {
+            assert!(map.validate_with_warnings(false).warnings.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_suggest_id() {
+        assert_eq!(sanitize_id("my song (feat. someone)"), "MySongFeatSomeone");
+        assert_eq!(sanitize_id("2 Fast"), "Song2Fast");
+        assert_eq!(sanitize_id("東方"), "");
+
+        let song_info = SongInfo {
+            info_text: hashmap! {
+                Lang::JA => SongInfoText {
+                    title: "恋色 Master Spark".to_owned(),
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+        assert_eq!(song_info.suggest_id(|_| false), "MasterSpark");
+        assert_eq!(
+            song_info.suggest_id(|id| ["MasterSpark", "MasterSpark2"].contains(&id)),
+            "MasterSpark3"
+        );
+        assert_eq!(SongInfo::default().suggest_id(|_| false), "CustomSong");
+    }
+
+    #[test]
+    fn test_validate_length() {
+        let map = Map {
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore::default_with_len(DEFAULT_MAX_SCORE_LENGTH + 1),
+                Difficulty::Easy => MapScore::default_with_len(100),
+            },
+            ..Default::default()
+        };
+
+        assert!(map.validate_length(DEFAULT_MAX_SCORE_LENGTH * 2).is_ok());
+        assert!(matches!(
+            map.validate_length(DEFAULT_MAX_SCORE_LENGTH),
+            Err(InvalidMapError::ScoreTooLong(Difficulty::Hard, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_validate_with_warnings() {
+        let text = SongInfoText {
+            title: "Title".to_owned(),
+            artist: "Artist".to_owned(),
+            ..Default::default()
+        };
+        let mut map = Map {
+            song_info:  SongInfo {
+                id: MusicID::New("Newly".to_owned()),
+                bpm: 120.0,
+                length: 10,
+                info_text: hashmap! { Lang::JA => text },
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str("----O-S-O-").unwrap(),
+                },
+            },
+        };
+
+        let validation = map.validate_with_warnings(false);
+        assert_eq!(validation.status(), MapStatus::Error);
+        assert_eq!(validation.errors.len(), 1);
+
+        map.song_info.music_file = env!("CARGO_MANIFEST_DIR").to_owned() + "/Cargo.toml";
+        assert_eq!(map.validate_with_warnings(false).status(), MapStatus::Ok);
+
+        map.song_info.bpm_changes = Some(BpmChanges(vec![(5, 0.0), (12, 150.0)]));
+        let validation = map.validate_with_warnings(false);
+        assert_eq!(validation.status(), MapStatus::Error);
+        assert_eq!(validation.errors.len(), 1);
+        assert_eq!(validation.warnings.len(), 1);
+
+        map.song_info.bpm_changes = None;
+        map.map_scores = hashmap! {
+            Difficulty::Hard => MapScore {
+                scores: ScoreData::from_str("O-S-O-").unwrap(),
+            },
+        };
+        let validation = map.validate_with_warnings(false);
+        assert_eq!(validation.status(), MapStatus::Warning);
+        assert_eq!(validation.warnings.len(), 2);
+    }
+
+    #[test]
+    fn test_pad_to_duration() {
+        let mut map = Map {
+            song_info:  SongInfo {
+                bpm: 120.0,
+                offset: 0.5,
+                bpm_changes: BpmChanges(vec![(3, 60.0)]).into(),
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore::default_with_len(4),
+                Difficulty::Easy => MapScore::default_with_len(8),
+            },
+        };
+
+        // 4 entries take 2 seconds, entries after index 3 take 1 second each, so 2
+        // more entries fit in 5.5 - 0.5 - 1 seconds
+        assert_eq!(map.pad_to_duration(5.5, 1.0), 2);
+        assert_eq!(map.map_scores[&Difficulty::Hard].scores.0.len(), 6);
+        assert_eq!(map.map_scores[&Difficulty::Easy].scores.0.len(), 8);
+        assert_eq!(map.song_info.length, 8);
+
+        for bpm in [0.0, -60.0, f32::NAN] {
+            map.song_info.bpm_changes = BpmChanges(vec![(3, bpm)]).into();
+            assert_eq!(map.pad_to_duration(60.0, 1.0), 0);
+        }
+    }
+
+    #[test]
+    fn test_fix_lead_in() {
+        let mut map = Map {
+            song_info:  SongInfo {
+                bpm: 120.0,
+                offset: 1.5,
+                length: 8,
+                bpm_changes: BpmChanges(vec![(4, 60.0)]).into(),
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore::from_score("-, O, -, -, O, -, -, -,"),
+                Difficulty::Easy => MapScore::from_score("-, -, -, O, -, -, -, -,"),
+            },
+        };
+
+        assert!(matches!(
+            map.validate_lead_in(4),
+            Err(InvalidMapError::ShortLeadIn(Difficulty::Hard, 1, 4))
+        ));
+
+        assert_eq!(map.fix_lead_in(4).unwrap(), 3);
+        assert!(map.validate_lead_in(4).is_ok());
+        assert_eq!(map.song_info.offset, 0.0);
+        assert_eq!(map.song_info.length, 11);
+        assert_eq!(
+            map.song_info.bpm_changes.as_ref().unwrap().0,
+            vec![(7, 60.0)]
+        );
+
+        assert!(matches!(
+            map.fix_lead_in(5),
+            Err(InvalidMapError::LeadInNotFixable(1, _, _))
+        ));
+    }
+
+    #[test]
+    fn test_expand_templates() {
+        let text = SongInfoText {
+            title: "Title".to_string(),
+            sub_title: "{pack_name} #{index}".to_string(),
+            artist: "{unknown}".to_string(),
+            ..Default::default()
+        };
+        let map = Map {
+            song_info:  SongInfo {
+                id: MusicID::New("Newly".to_string()),
+                info_text: hashmap! { Lang::JA => text },
+                ..Default::default()
+            },
+            map_scores: HashMap::new(),
+        };
+        let maps = MapsConfig {
+            maps: vec![map.clone(), map],
+            variables: hashmap! { "pack_name".to_string() => "Pack".to_string() },
+            ..Default::default()
+        };
+
+        let expanded = maps.expanded_maps();
+        let text = expanded[1].song_info.info_text.get(&Lang::JA).unwrap();
+        assert_eq!(text.sub_title, "Pack #2");
+        assert_eq!(text.artist, "{unknown}");
+    }
+
+    #[test]
+    fn test_project_volume() {
+        let map = Map {
+            song_info:  SongInfo {
+                id: MusicID::New("Newly".to_string()),
+                ..Default::default()
+            },
+            map_scores: HashMap::new(),
+        };
+        let mut loud_map = map.clone();
+        loud_map.song_info.volume_db = Some(-6.0);
+
+        let maps = MapsConfig {
+            maps: vec![map, loud_map],
+            volume_db: Some(-2.5),
+            ..Default::default()
+        };
+
+        let expanded = maps.expanded_maps();
+        assert_eq!(expanded[0].song_info.volume_db, Some(-2.5));
+        assert_eq!(expanded[1].song_info.volume_db, Some(-6.0));
+    }
+
     #[test]
     fn test_beats_layout() {
         let bpm_changes = BpmChanges(vec![
@@ -964,6 +1865,68 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_filter_maps() {
+        let maps = ["Agepoyo", "NewSong", "OtherSong"]
+            .map(|id| {
+                let mut map = Map::default();
+                map.song_info.id = MusicID::from(id);
+                map
+            })
+            .to_vec();
+        let ids = |maps: &[Map]| {
+            maps.iter()
+                .map(|map| map.song_info.id.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let (kept, unknown) = filter_maps(maps.clone(), &["newsong".to_owned()], &[]);
+        assert_eq!(ids(&kept), vec!["NewSong"]);
+        assert!(unknown.is_empty());
+
+        let (kept, unknown) = filter_maps(maps, &[], &["Agepoyo".to_owned(), "Typo".to_owned()]);
+        assert_eq!(ids(&kept), vec!["NewSong", "OtherSong"]);
+        assert_eq!(unknown, vec!["Typo"]);
+    }
+
+    #[test]
+    fn test_update_maps() {
+        let map = |id: &str, bpm| {
+            let mut map = Map::default();
+            map.song_info.id = MusicID::from(id);
+            map.song_info.bpm = bpm;
+            map
+        };
+        let mut config = MapsConfig {
+            maps: vec![map("B", 1.0), map("A", 1.0), map("B", 2.0), map("C", 1.0)],
+            variables: hashmap! { "pack".to_owned() => "Pack".to_owned() },
+            ..Default::default()
+        };
+
+        let edited = hashmap! {
+            "A".to_owned() => map("A", 3.0),
+            "B".to_owned() => map("B", 3.0),
+            "D".to_owned() => map("D", 1.0),
+        };
+        config.update_maps(&edited);
+
+        let maps = config
+            .maps
+            .iter()
+            .map(|map| (map.song_info.id.to_string(), map.song_info.bpm))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            maps,
+            [
+                ("B".to_owned(), 1.0),
+                ("A".to_owned(), 3.0),
+                ("B".to_owned(), 3.0),
+                ("D".to_owned(), 1.0)
+            ]
+        );
+        assert_eq!(config.variables["pack"], "Pack");
+    }
+
     #[test]
     fn test_bpm_changes() {
         let bpm_changes = BpmChanges(vec![(1428, 100.), (1430, 150.)]);
@@ -0,0 +1,94 @@
+use super::Map;
+
+impl Map {
+    /// Time in seconds in the audio at which score entry `idx` is
+    pub fn beat_time(&self, idx: usize) -> f32 {
+        self.song_info.offset + (0..idx).map(|i| 60.0 / self.bpm_at(i)).sum::<f32>()
+    }
+
+    /// Index of the score entry nearest to `time` in seconds, `None` if `time`
+    /// is before the first entry or too far for entry times to be told apart
+    pub fn beat_index(&self, time: f32) -> Option<usize> {
+        if !time.is_finite() || time < self.song_info.offset {
+            return None;
+        }
+
+        let mut idx = 0;
+        let mut entry_time = self.song_info.offset;
+        loop {
+            let bpm = self.bpm_at(idx);
+            if bpm <= 0.0 {
+                return None;
+            }
+
+            let next_time = entry_time + 60.0 / bpm;
+            if next_time <= entry_time {
+                return None;
+            }
+            if next_time > time {
+                return Some(if time - entry_time <= next_time - time {
+                    idx
+                } else {
+                    idx + 1
+                });
+            }
+            entry_time = next_time;
+            idx += 1;
+        }
+    }
+}
+
+/// Formats seconds as `m:ss.mmm` as shown in audio editors
+pub fn format_timestamp(seconds: f32) -> String {
+    let ms = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!("{}:{:02}.{:03}", ms / 60_000, ms / 1000 % 60, ms % 1000)
+}
+
+/// Parses timestamps like `1:23.456`, `83.456` or `0:01:23.456` into seconds
+pub fn parse_timestamp(timestamp: &str) -> Option<f32> {
+    timestamp
+        .trim()
+        .split(':')
+        .try_fold(0.0, |acc, part| {
+            Some(acc * 60.0 + part.trim().parse::<f32>().ok()?)
+        })
+        .filter(|seconds: &f32| seconds.is_finite() && *seconds >= 0.0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::{BpmChanges, SongInfo};
+
+    #[test]
+    fn test_beat_time() {
+        let map = Map {
+            song_info:  SongInfo {
+                bpm: 120.0,
+                offset: 1.0,
+                bpm_changes: BpmChanges(vec![(3, 60.0)]).into(),
+                ..Default::default()
+            },
+            map_scores: Default::default(),
+        };
+
+        assert_eq!(map.beat_time(0), 1.0);
+        assert_eq!(map.beat_time(4), 3.0);
+        assert_eq!(map.beat_time(6), 5.0);
+
+        assert_eq!(map.beat_index(0.5), None);
+        assert_eq!(map.beat_index(3.4), Some(4));
+        assert_eq!(map.beat_index(3.6), Some(5));
+
+        assert_eq!(format_timestamp(83.456), "1:23.456");
+        assert_eq!(parse_timestamp("1:23.456"), Some(83.456));
+        assert_eq!(parse_timestamp("0:01:23.5"), Some(83.5));
+        assert_eq!(parse_timestamp("abc"), None);
+        assert_eq!(parse_timestamp("inf"), None);
+        assert_eq!(parse_timestamp("1:NaN"), None);
+
+        assert_eq!(map.beat_index(f32::INFINITY), None);
+        assert_eq!(map.beat_index(f32::NAN), None);
+        assert_eq!(map.beat_index(1e30), None);
+    }
+}
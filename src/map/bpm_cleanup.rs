@@ -0,0 +1,203 @@
+use std::fmt::{Display, Formatter};
+
+use super::BpmChanges;
+
+/// Maximum BPM difference snapped by default, imports usually produce errors
+/// far below it due to millisecond rounding
+pub const DEFAULT_SNAP_TOLERANCE: f32 = 0.05;
+/// Denominators of fractions BPMs are snapped to, in order of preference
+const SNAP_DENOMINATORS: [f32; 4] = [1.0, 2.0, 4.0, 3.0];
+
+/// A change made on BPMs by the cleanup passes
+#[derive(Debug, PartialEq)]
+pub enum BpmAdjustment {
+    /// BPM snapped to integer or common fraction, `idx` is `None` for the
+    /// initial BPM
+    Snapped {
+        idx:  Option<u16>,
+        from: f32,
+        to:   f32,
+    },
+    /// BPM change removed as it equals the BPM before it
+    Merged { idx: u16, bpm: f32 },
+    /// BPM change removed as a later change lands on the same index
+    Overridden { idx: u16, bpm: f32 },
+}
+
+impl Display for BpmAdjustment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Snapped {
+                idx: None,
+                from,
+                to,
+            } => write!(f, "Initial BPM snapped from {from} to {to}"),
+            Self::Snapped {
+                idx: Some(idx),
+                from,
+                to,
+            } => write!(f, "BPM change at index {idx} snapped from {from} to {to}"),
+            Self::Merged { idx, bpm } => write!(
+                f,
+                "BPM change at index {idx} removed as it keeps the previous BPM {bpm}"
+            ),
+            Self::Overridden { idx, bpm } => write!(
+                f,
+                "BPM change at index {idx} to {bpm} removed as another change on the same index \
+                 overrides it"
+            ),
+        }
+    }
+}
+
+/// Snaps BPM to the nearest integer or common fraction within tolerance
+pub fn snap_bpm(bpm: f32, tolerance: f32) -> f32 {
+    SNAP_DENOMINATORS
+        .iter()
+        .map(|d| (bpm * d).round() / d)
+        .find(|snapped| (snapped - bpm).abs() <= tolerance)
+        .unwrap_or(bpm)
+}
+
+/// Snaps imported BPMs within `tolerance` and merges adjacent changes ending
+/// up with the same BPM, returns the adjustments made
+pub fn snap_bpms(
+    bpm: &mut f32,
+    bpm_changes: &mut Option<BpmChanges>,
+    tolerance: f32,
+) -> Vec<BpmAdjustment> {
+    let mut adjustments = vec![];
+
+    let bpms = std::iter::once((None, &mut *bpm)).chain(
+        bpm_changes
+            .iter_mut()
+            .flat_map(|changes| changes.0.iter_mut())
+            .map(|(idx, bpm)| (Some(*idx), bpm)),
+    );
+    for (idx, bpm) in bpms {
+        let snapped = snap_bpm(*bpm, tolerance);
+        if snapped != *bpm {
+            adjustments.push(BpmAdjustment::Snapped {
+                idx,
+                from: *bpm,
+                to: snapped,
+            });
+            *bpm = snapped;
+        }
+    }
+
+    adjustments.append(&mut collapse_bpm_changes(*bpm, bpm_changes));
+    adjustments
+}
+
+/// Removes BPM changes which keep the BPM before them, for changes on the same
+/// index only the last one is kept as it is the one taking effect. Returns the
+/// adjustments made
+pub fn collapse_bpm_changes(bpm: f32, bpm_changes: &mut Option<BpmChanges>) -> Vec<BpmAdjustment> {
+    let mut adjustments = vec![];
+
+    let Some(changes) = bpm_changes else {
+        return adjustments;
+    };
+
+    // Sorting is stable, so changes on the same index keep their order
+    changes.0.sort_by_key(|(idx, _)| *idx);
+
+    let mut collapsed: Vec<(u16, f32)> = vec![];
+    for (idx, change_bpm) in changes.0.drain(..) {
+        match collapsed.last_mut() {
+            Some(last) if last.0 == idx => {
+                adjustments.push(BpmAdjustment::Overridden { idx, bpm: last.1 });
+                *last = (idx, change_bpm);
+            }
+            _ => collapsed.push((idx, change_bpm)),
+        }
+    }
+
+    let mut last_bpm = bpm;
+    collapsed.retain(|(idx, change_bpm)| {
+        if *change_bpm == last_bpm {
+            adjustments.push(BpmAdjustment::Merged {
+                idx: *idx,
+                bpm: last_bpm,
+            });
+            return false;
+        }
+
+        last_bpm = *change_bpm;
+        true
+    });
+
+    if collapsed.is_empty() {
+        *bpm_changes = None;
+    } else {
+        changes.0 = collapsed;
+    }
+
+    adjustments
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_snap_bpm() {
+        assert_eq!(snap_bpm(179.998, DEFAULT_SNAP_TOLERANCE), 180.0);
+        assert_eq!(snap_bpm(147.49, DEFAULT_SNAP_TOLERANCE), 147.5);
+        assert_eq!(snap_bpm(133.34, DEFAULT_SNAP_TOLERANCE), 133.33333);
+        assert_eq!(snap_bpm(150.2, DEFAULT_SNAP_TOLERANCE), 150.2);
+    }
+
+    #[test]
+    fn test_snap_bpms() {
+        let mut bpm = 179.998;
+        let mut bpm_changes = Some(BpmChanges(vec![(16, 180.01), (32, 200.0), (48, 90.002)]));
+
+        let adjustments = snap_bpms(&mut bpm, &mut bpm_changes, DEFAULT_SNAP_TOLERANCE);
+
+        assert_eq!(bpm, 180.0);
+        assert_eq!(bpm_changes.unwrap().0, vec![(32, 200.0), (48, 90.0)]);
+        assert_eq!(adjustments.len(), 4);
+        assert_eq!(
+            adjustments[3],
+            BpmAdjustment::Merged {
+                idx: 16,
+                bpm: 180.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_collapse_bpm_changes() {
+        let mut bpm_changes = Some(BpmChanges(vec![
+            (0, 150.0),
+            (64, 200.0),
+            (32, 180.0),
+            (32, 170.0),
+            (96, 200.0),
+        ]));
+
+        let adjustments = collapse_bpm_changes(150.0, &mut bpm_changes);
+
+        assert_eq!(bpm_changes.unwrap().0, vec![(32, 170.0), (64, 200.0)]);
+        assert_eq!(
+            adjustments,
+            vec![
+                BpmAdjustment::Overridden {
+                    idx: 32,
+                    bpm: 180.0,
+                },
+                BpmAdjustment::Merged { idx: 0, bpm: 150.0 },
+                BpmAdjustment::Merged {
+                    idx: 96,
+                    bpm: 200.0,
+                },
+            ]
+        );
+
+        let mut bpm_changes = Some(BpmChanges(vec![(0, 150.0)]));
+        collapse_bpm_changes(150.0, &mut bpm_changes);
+        assert!(bpm_changes.is_none());
+    }
+}
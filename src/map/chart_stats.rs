@@ -0,0 +1,85 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Difficulty, Map, ScoreEntry};
+
+/// Length of the window peak density is measured in, in seconds
+const PEAK_WINDOW: f32 = 4.0;
+
+/// Note density statistics of a chart
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ChartStats {
+    /// Notes per second between the first and the last note
+    pub density:      f32,
+    /// Notes per second in the densest `PEAK_WINDOW` seconds
+    pub peak_density: f32,
+}
+
+impl Map {
+    /// Note density statistics of the score of `difficulty`, `None` if it has
+    /// less than 2 notes
+    pub fn chart_stats(&self, difficulty: Difficulty) -> Option<ChartStats> {
+        let score = &self.map_scores.get(&difficulty)?.scores.0;
+
+        let mut time = 0.0;
+        let note_times = score
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                let entry_time = time;
+                time += 60.0 / self.bpm_at(idx);
+                (*entry != ScoreEntry::B).then_some(entry_time)
+            })
+            .collect::<Vec<f32>>();
+
+        let (first, last) = match note_times.as_slice() {
+            [first, .., last] if last > first => (*first, *last),
+            _ => return None,
+        };
+
+        let mut peak_notes = 0;
+        let mut window_start = 0;
+        for (i, time) in note_times.iter().enumerate() {
+            while note_times[window_start] <= time - PEAK_WINDOW {
+                window_start += 1;
+            }
+            peak_notes = peak_notes.max(i - window_start + 1);
+        }
+
+        Some(ChartStats {
+            density:      note_times.len() as f32 / (last - first),
+            peak_density: peak_notes as f32 / PEAK_WINDOW,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{MapScore, ScoreData, SongInfo};
+
+    #[test]
+    fn test_chart_stats() {
+        // 2 entries per second, notes in the first 4 seconds every entry, then every
+        // other entry for 4 seconds
+        let map = Map {
+            song_info:  SongInfo {
+                bpm: 120.0,
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str("OOOOOOOOO-O-O-O-O").unwrap(),
+                },
+            },
+        };
+
+        let stats = map.chart_stats(Difficulty::Hard).unwrap();
+        assert_eq!(stats.density, 13.0 / 8.0);
+        assert_eq!(stats.peak_density, 2.0);
+        assert!(map.chart_stats(Difficulty::Easy).is_none());
+    }
+}
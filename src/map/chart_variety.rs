@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use super::{Difficulty, Map, ScoreEntry};
+
+/// Score entries in a measure, as scores are laid out in lines of 4 beats
+const MEASURE_LENGTH: usize = 4;
+
+/// A measure pattern occurring more than once in a chart
+#[derive(Debug, PartialEq)]
+pub struct RepeatedPattern {
+    /// Score entries of the measure, e.g. "SO-O"
+    pub pattern:       String,
+    pub count:         usize,
+    /// Index of the first measure with the pattern
+    pub first_measure: usize,
+}
+
+/// Consecutive measures with the same pattern
+#[derive(Debug, PartialEq)]
+pub struct PatternRun {
+    pub pattern:       String,
+    pub start_measure: usize,
+    pub count:         usize,
+}
+
+/// Repetition statistics of a chart, blank measures are not counted
+#[derive(Debug)]
+pub struct ChartVariety {
+    pub measures:    usize,
+    pub distinct:    usize,
+    /// Most repeated patterns, most frequent first
+    pub repeated:    Vec<RepeatedPattern>,
+    /// Longest run of one pattern repeated back to back, if any pattern is
+    pub longest_run: Option<PatternRun>,
+}
+
+impl ChartVariety {
+    /// Share of distinct patterns among measures, 1 for a chart without any
+    /// repeated measure
+    pub fn variety(&self) -> f32 {
+        if self.measures == 0 {
+            1.0
+        } else {
+            self.distinct as f32 / self.measures as f32
+        }
+    }
+}
+
+impl Display for ChartVariety {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "variety {:.0}% ({} distinct of {} measures)",
+            self.variety() * 100.0,
+            self.distinct,
+            self.measures
+        )?;
+
+        for pattern in &self.repeated {
+            write!(
+                f,
+                "\n    {} x{}, first at measure {}",
+                pattern.pattern, pattern.count, pattern.first_measure
+            )?;
+        }
+        if let Some(run) = &self.longest_run {
+            write!(
+                f,
+                "\n    {} repeated {} times in a row from measure {}",
+                run.pattern, run.count, run.start_measure
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Map {
+    /// Repetition statistics of the score of `difficulty` in measures of 4
+    /// entries, with at most `top` repeated patterns. `None` if there is no
+    /// such score
+    pub fn chart_variety(&self, difficulty: Difficulty, top: usize) -> Option<ChartVariety> {
+        let score = &self.map_scores.get(&difficulty)?.scores;
+        let measures = score
+            .0
+            .chunks(MEASURE_LENGTH)
+            .enumerate()
+            .filter(|(_, measure)| measure.iter().any(|e| *e != ScoreEntry::B))
+            .map(|(idx, measure)| {
+                let pattern = measure.iter().map(|e| e.to_string()).collect::<String>();
+                (idx, pattern)
+            })
+            .collect::<Vec<_>>();
+
+        let mut counts = HashMap::<&str, (usize, usize)>::new();
+        for (idx, pattern) in &measures {
+            counts.entry(pattern).or_insert((0, *idx)).0 += 1;
+        }
+
+        let mut repeated = counts
+            .iter()
+            .filter(|(_, (count, _))| *count > 1)
+            .map(|(pattern, (count, first_measure))| RepeatedPattern {
+                pattern:       pattern.to_string(),
+                count:         *count,
+                first_measure: *first_measure,
+            })
+            .collect::<Vec<_>>();
+        repeated.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then(a.first_measure.cmp(&b.first_measure))
+        });
+        repeated.truncate(top);
+
+        let mut longest_run: Option<PatternRun> = None;
+        let mut run_start = 0;
+        for i in 1..=measures.len() {
+            let continues = i < measures.len()
+                && measures[i].1 == measures[i - 1].1
+                && measures[i].0 == measures[i - 1].0 + 1;
+            if continues {
+                continue;
+            }
+
+            let count = i - run_start;
+            if count > 1 && longest_run.as_ref().is_none_or(|run| count > run.count) {
+                longest_run = Some(PatternRun {
+                    pattern: measures[run_start].1.clone(),
+                    start_measure: measures[run_start].0,
+                    count,
+                });
+            }
+            run_start = i;
+        }
+
+        Some(ChartVariety {
+            measures: measures.len(),
+            distinct: counts.len(),
+            repeated,
+            longest_run,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{MapScore, ScoreData};
+
+    #[test]
+    fn test_chart_variety() {
+        let map = Map {
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str("----SO-OSO-OSO-O----O-O-SO-OO-O-").unwrap(),
+                },
+            },
+            ..Default::default()
+        };
+
+        let variety = map.chart_variety(Difficulty::Hard, 5).unwrap();
+        assert_eq!(variety.measures, 6);
+        assert_eq!(variety.distinct, 2);
+        assert_eq!(
+            variety.repeated,
+            vec![
+                RepeatedPattern {
+                    pattern:       "SO-O".to_owned(),
+                    count:         4,
+                    first_measure: 1,
+                },
+                RepeatedPattern {
+                    pattern:       "O-O-".to_owned(),
+                    count:         2,
+                    first_measure: 5,
+                },
+            ]
+        );
+        assert_eq!(
+            variety.longest_run,
+            Some(PatternRun {
+                pattern:       "SO-O".to_owned(),
+                start_measure: 1,
+                count:         3,
+            })
+        );
+        assert!(map.chart_variety(Difficulty::Easy, 5).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{io::ErrorKind, path::Path};
+
+use anyhow::Context;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    MapsConfig,
+    strict_config::{self, UnknownKey},
+};
+
+/// Schema version of map configs written by this version of mod_tool
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Upgrades a config of version `i` to version `i + 1`, by index. Each step
+/// edits the raw table, so fields can be renamed or restructured before the
+/// config is deserialized.
+const MIGRATIONS: [fn(&mut toml::Table); CURRENT_SCHEMA_VERSION as usize] = [
+    // Configs before versioning, which version 1 reads unchanged
+    |_| {},
+];
+
+/// Schema version of a map config, configs without one predate versioning
+/// and are version 0. New configs are of the current version.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct SchemaVersion(pub u32);
+
+impl Default for SchemaVersion {
+    fn default() -> Self {
+        Self(CURRENT_SCHEMA_VERSION)
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    #[error(
+        "Map config of schema version {0} is written by a newer mod_tool, which supports up to \
+         version {CURRENT_SCHEMA_VERSION}"
+    )]
+    NewerVersion(u32),
+    #[error("Map config has keys not read by any field:\n  {}", .0.iter().join("\n  "))]
+    UnknownKeys(Vec<UnknownKey>),
+}
+
+/// Schema version of a raw config
+fn table_version(table: &toml::Table) -> u32 {
+    table
+        .get("schema_version")
+        .and_then(toml::Value::as_integer)
+        .map_or(0, |version| version as u32)
+}
+
+impl MapsConfig {
+    /// Parses a map config, upgrading it from older schema versions. The
+    /// upgraded config is only written back by MigrateConfig, or when a
+    /// command rewrites the config anyway. Unknown keys are ignored unless
+    /// strict mode is enabled.
+    pub fn from_toml(content: &str) -> Result<Self, ConfigError> {
+        Self::parse(content, strict_config::is_strict())
+    }
+
+    /// Parses a map config like `from_toml`, rejecting keys not read by any
+    /// field, e.g. misspelt ones
+    pub fn from_toml_strict(content: &str) -> Result<Self, ConfigError> {
+        Self::parse(content, true)
+    }
+
+    fn parse(content: &str, strict: bool) -> Result<Self, ConfigError> {
+        let mut table = content.parse::<toml::Table>()?;
+
+        let version = table_version(&table);
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(ConfigError::NewerVersion(version));
+        }
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(&mut table);
+        }
+        table.insert(
+            "schema_version".to_owned(),
+            toml::Value::Integer(CURRENT_SCHEMA_VERSION as i64),
+        );
+
+        let value = toml::Value::Table(table);
+        if !strict {
+            return Ok(value.try_into()?);
+        }
+
+        let (config, unknown) = strict_config::deserialize_strict(value)?;
+        if unknown.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError::UnknownKeys(unknown))
+        }
+    }
+
+    /// Reads a map config that is updated and written back, or a new one if
+    /// the file doesn't exist. Configs that can't be read, e.g. of a newer
+    /// schema version or with unknown keys in strict mode, are errors, so that
+    /// they are not replaced by a config holding only the updated map.
+    pub fn read_for_update(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Self::from_toml(&content)
+                .with_context(|| format!("while reading {}", path.display()))?),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("while reading {}", path.display())),
+        }
+    }
+
+    /// Schema version the config was written in, before upgrading
+    pub fn file_version(content: &str) -> Result<u32, ConfigError> {
+        Ok(table_version(&content.parse::<toml::Table>()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::EXAMPLE_CONFIG;
+
+    #[test]
+    fn test_config_migration() {
+        let legacy = EXAMPLE_CONFIG.replace("schema_version = 1\n", "");
+        assert_eq!(MapsConfig::file_version(&legacy).unwrap(), 0);
+        assert_eq!(
+            MapsConfig::file_version(EXAMPLE_CONFIG).unwrap(),
+            CURRENT_SCHEMA_VERSION
+        );
+
+        let config = MapsConfig::from_toml(&legacy).unwrap();
+        assert_eq!(config.schema_version, SchemaVersion(CURRENT_SCHEMA_VERSION));
+        assert_eq!(config.maps.len(), 2);
+
+        let written = toml::to_string_pretty(&config).unwrap();
+        assert_eq!(
+            MapsConfig::file_version(&written).unwrap(),
+            CURRENT_SCHEMA_VERSION
+        );
+        assert_eq!(
+            MapsConfig::default().schema_version,
+            SchemaVersion(CURRENT_SCHEMA_VERSION)
+        );
+
+        let newer = format!("schema_version = {}\n", CURRENT_SCHEMA_VERSION + 1);
+        assert!(matches!(
+            MapsConfig::from_toml(&newer),
+            Err(ConfigError::NewerVersion(_))
+        ));
+
+        let path = std::env::temp_dir().join("spell_bubble_config_migration_test.toml");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(MapsConfig::read_for_update(&path).unwrap().maps.len(), 0);
+        std::fs::write(&path, &newer).unwrap();
+        assert!(MapsConfig::read_for_update(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        let typo = EXAMPLE_CONFIG.replace("bpm = 150.0", "bmp = 150.0\nbpm = 150.0");
+        assert!(MapsConfig::from_toml(&typo).is_ok());
+        assert!(matches!(
+            MapsConfig::from_toml_strict(&typo),
+            Err(ConfigError::UnknownKeys(keys)) if keys.len() == 1
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Map;
+
+/// Longest value shown in a diff line, longer ones such as scores are cut
+const MAX_VALUE_LEN: usize = 48;
+
+/// A field that differs between two maps, values are in toml form and are
+/// `None` if the field is absent
+#[derive(Debug, PartialEq, Eq)]
+pub struct FieldDiff {
+    /// Dotted path of the field as in map config files, e.g. `song_info.bpm`
+    pub field: String,
+    pub left:  Option<String>,
+    pub right: Option<String>,
+}
+
+impl std::fmt::Display for FieldDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<String>| match value {
+            Some(value) if value.chars().count() > MAX_VALUE_LEN => {
+                format!("{}…", value.chars().take(MAX_VALUE_LEN).collect::<String>())
+            }
+            Some(value) => value.clone(),
+            None => "(none)".to_owned(),
+        };
+
+        write!(
+            f,
+            "{}: {} → {}",
+            self.field,
+            show(&self.left),
+            show(&self.right)
+        )
+    }
+}
+
+fn flatten(prefix: &str, value: &toml::Value, fields: &mut BTreeMap<String, String>) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten(&path, value, fields);
+            }
+        }
+        toml::Value::String(s) => {
+            fields.insert(prefix.to_owned(), s.clone());
+        }
+        value => {
+            fields.insert(prefix.to_owned(), value.to_string());
+        }
+    }
+}
+
+fn fields(map: &Map) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    if let Ok(value) = toml::Value::try_from(map) {
+        flatten("", &value, &mut fields);
+    }
+    fields
+}
+
+/// Fields that differ between `left` and `right` in path order
+pub fn field_diff(left: &Map, right: &Map) -> Vec<FieldDiff> {
+    let mut left = fields(left);
+    let mut right = fields(right);
+
+    let paths = left
+        .keys()
+        .chain(right.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    paths
+        .into_iter()
+        .filter_map(|field| {
+            let left = left.remove(&field);
+            let right = right.remove(&field);
+            (left != right).then_some(FieldDiff { field, left, right })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{Lang, MusicID, SongInfo, SongInfoText};
+
+    #[test]
+    fn test_field_diff() {
+        let left = Map {
+            song_info: SongInfo {
+                id: MusicID::New("Song".to_owned()),
+                bpm: 120.0,
+                info_text: hashmap! {
+                    Lang::JA => SongInfoText {
+                        title: "Title".to_owned(),
+                        ..Default::default()
+                    },
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut right = left.clone();
+        right.song_info.bpm = 150.0;
+        right.song_info.intro_file = Some("intro.wav".to_owned());
+        right.song_info.info_text.get_mut(&Lang::JA).unwrap().title = "Title2".to_owned();
+
+        let diff = field_diff(&left, &right);
+        assert_eq!(
+            diff,
+            vec![
+                FieldDiff {
+                    field: "song_info.bpm".to_owned(),
+                    left:  Some("120.0".to_owned()),
+                    right: Some("150.0".to_owned()),
+                },
+                FieldDiff {
+                    field: "song_info.info_text.JA.title".to_owned(),
+                    left:  Some("Title".to_owned()),
+                    right: Some("Title2".to_owned()),
+                },
+                FieldDiff {
+                    field: "song_info.intro_file".to_owned(),
+                    left:  None,
+                    right: Some("intro.wav".to_owned()),
+                },
+            ]
+        );
+        assert_eq!(
+            diff[2].to_string(),
+            "song_info.intro_file: (none) → intro.wav"
+        );
+        assert!(field_diff(&left, &left).is_empty());
+    }
+}
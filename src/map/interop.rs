@@ -6,21 +6,33 @@ use std::{
     os::raw::c_char,
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Mutex, MutexGuard},
 };
 
 use itertools::izip;
+use log::debug;
 use maplit::hashset;
 use memmem::{Searcher, TwoWaySearcher};
 
 use crate::{
-    ffmpeg_helper::convert_file,
-    interop::{ArrayWrapper, DualArrayWrapper, StringWrapper, free_dotnet},
+    ffmpeg_helper::{concat_files, convert_file},
+    interop::{ArrayWrapper, DualArrayWrapper, StringWrapper, free_dotnet, path_to_cstring},
     map::{
         BeatsLayout, BpmChanges, Difficulty, Lang, Map, MapScore, SongInfo, SongInfoText,
         enums::{Area, Music},
     },
 };
 
+/// Songs are patched on several threads, while the helper library is not made
+/// to be called concurrently. Calls into it are serialized, so that only audio
+/// conversion with ffmpeg runs in parallel.
+static HELPER_LOCK: Mutex<()> = Mutex::new(());
+
+pub fn lock_helper() -> MutexGuard<'static, ()> {
+    // A panic while holding the lock leaves no state behind to be poisoned
+    HELPER_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
 #[repr(C)]
 struct SongEntry {
     /// 0 for structs from Rust, 1 for C#
@@ -126,37 +138,62 @@ extern "C" {
     fn get_music_info(romfs_path: *const c_char) -> DualArrayWrapper;
 }
 
+#[allow(clippy::too_many_arguments)]
 pub(super) fn patch_acb_file(
     music_file: &str,
+    intro_file: Option<&str>,
+    volume_db: f32,
     acb_path: &Path,
     out_acb_path: &Path,
     out_awb_path: &Path,
     prev_start_ms: u32,
+    on_converted: impl FnOnce(),
 ) -> std::io::Result<()> {
+    // Songs may be patched in parallel, so the name is made unique by the output
+    // file, which differs for each song
+    let song_name = out_acb_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
     let mut wav_path = temp_dir();
-    wav_path.push("hca_convert_tmp.wav");
+    wav_path.push(format!("hca_convert_tmp_{song_name}.wav"));
 
     let mut i = 0;
     while Path::new(&wav_path).is_file() {
         wav_path.pop();
-        wav_path.push(format!("hca_convert_tmp{i}.wav"));
+        wav_path.push(format!("hca_convert_tmp_{song_name}{i}.wav"));
         i += 1;
     }
 
     let music_file = PathBuf::from(music_file);
-    let wav_path = if let Some("wav") = music_file.extension().and_then(|e| e.to_str()) {
+    let wav_path = if let Some(intro_file) = intro_file {
+        // Both parts are decoded and joined into one PCM stream, so there is no gap
+        // or encoder padding between them
+        concat_files(&[Path::new(intro_file), &music_file], &wav_path, volume_db)?;
+        wav_path
+    } else if volume_db == 0.0 && music_file.extension().and_then(|e| e.to_str()) == Some("wav") {
+        debug!("Using {} without conversion", music_file.display());
         music_file
     } else {
-        convert_file(&music_file, &wav_path)?;
+        convert_file(&music_file, &wav_path, volume_db)?;
         wav_path
     };
 
-    let wav_path_c = CString::new(wav_path.to_string_lossy().to_string()).unwrap();
-    let acb_path_c = CString::new(acb_path.to_string_lossy().to_string()).unwrap();
-    let out_acb_path_c = CString::new(out_acb_path.to_string_lossy().to_string()).unwrap();
-    let out_awb_path_c = CString::new(out_awb_path.to_string_lossy().to_string()).unwrap();
+    on_converted();
 
+    let wav_path_c = path_to_cstring(&wav_path)?;
+    let acb_path_c = path_to_cstring(acb_path)?;
+    let out_acb_path_c = path_to_cstring(out_acb_path)?;
+    let out_awb_path_c = path_to_cstring(out_awb_path)?;
+
+    debug!(
+        "Encoding {} into {} based on {}",
+        wav_path.display(),
+        out_acb_path.display(),
+        acb_path.display()
+    );
     unsafe {
+        let _helper = lock_helper();
         patch_acb(
             wav_path_c.as_ptr(),
             acb_path_c.as_ptr(),
@@ -207,8 +244,8 @@ pub(super) fn patch_score_file(
     song_id: &str,
     scores: &HashMap<Difficulty, MapScore>,
     bpm_changes: &Option<BpmChanges>,
-    replace_existing: bool,
-) {
+    score_donor: Option<&str>,
+) -> std::io::Result<()> {
     let len = scores.iter().next().unwrap().1.scores.0.len();
     let mut scores = scores.to_owned();
     let required_keys = hashset![Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
@@ -245,35 +282,47 @@ pub(super) fn patch_score_file(
 
     let param_ptrs: Vec<*const c_char> = params.iter().map(|s| s.as_ptr()).collect();
 
-    let score_file_c = CString::new(score_file.to_string_lossy().to_string()).unwrap();
-    let out_path_c = CString::new(out_path.to_string_lossy().to_string()).unwrap();
+    let score_file_c = path_to_cstring(score_file)?;
+    let out_path_c = path_to_cstring(out_path)?;
     let song_id_c = CString::new(song_id).unwrap();
 
-    let create_score_base_id = CString::new("Karisuma").unwrap();
-
+    debug!(
+        "{} score {song_id} from {} into {}",
+        if score_donor.is_none() {
+            "Patching"
+        } else {
+            "Creating"
+        },
+        score_file.display(),
+        out_path.display()
+    );
     unsafe {
+        let _helper = lock_helper();
         let param = ArrayWrapper {
             managed: 0,
             size:    param_ptrs.len() as u32,
             array:   mem::transmute::<*const *const i8, *mut c_void>(param_ptrs.as_ptr()),
         };
-        if replace_existing {
-            patch_score(
+        if let Some(score_donor) = score_donor {
+            let score_donor_c = CString::new(score_donor).unwrap();
+            create_score(
                 score_file_c.as_ptr(),
                 out_path_c.as_ptr(),
+                score_donor_c.as_ptr(),
                 song_id_c.as_ptr(),
                 param,
             );
         } else {
-            create_score(
+            patch_score(
                 score_file_c.as_ptr(),
                 out_path_c.as_ptr(),
-                create_score_base_id.as_ptr(),
                 song_id_c.as_ptr(),
                 param,
             );
         }
     }
+
+    Ok(())
 }
 
 pub(super) fn patch_share_data<T, U>(
@@ -281,12 +330,13 @@ pub(super) fn patch_share_data<T, U>(
     out_path: &Path,
     maps: T,
     replace_existing: bool,
-) where
+) -> std::io::Result<()>
+where
     T: IntoIterator<Item = U>,
     U: std::borrow::Borrow<Map>,
 {
-    let share_data_c = CString::new(share_data_file.to_string_lossy().to_string()).unwrap();
-    let out_path_c = CString::new(out_path.to_string_lossy().to_string()).unwrap();
+    let share_data_c = path_to_cstring(share_data_file)?;
+    let out_path_c = path_to_cstring(out_path)?;
 
     let mut plus_1s_cstring: Vec<CString> = vec![]; // +1s for objects created in loop
     let mut plus_1s_vec: Vec<Vec<WordEntry>> = vec![]; // +1s for objects created in loop
@@ -372,6 +422,17 @@ pub(super) fn patch_share_data<T, U>(
         song_entries.push(song_entry);
     }
 
+    debug!(
+        "{} {} songs in {} into {}",
+        if replace_existing {
+            "Patching"
+        } else {
+            "Adding"
+        },
+        song_entries.len(),
+        share_data_file.display(),
+        out_path.display()
+    );
     unsafe {
         let wrapper = ArrayWrapper {
             managed: 0,
@@ -385,6 +446,8 @@ pub(super) fn patch_share_data<T, U>(
             add_share_data_music_data(share_data_c.as_ptr(), out_path_c.as_ptr(), wrapper);
         }
     }
+
+    Ok(())
 }
 
 fn vec_push_idx<T>(vec: &mut Vec<T>, element: T) -> usize {
@@ -392,9 +455,15 @@ fn vec_push_idx<T>(vec: &mut Vec<T>, element: T) -> usize {
     vec.len() - 1
 }
 
-pub fn get_song_info(romfs_path: &Path) -> Vec<(Map, String, String, String)> {
-    let romfs_path_c = CString::new(romfs_path.to_string_lossy().to_string()).unwrap();
+/// A song with its beat script and score scripts of Easy, Normal and Hard
+type SongScripts = (Map, String, String, String, String);
+
+/// Official songs with their beat script and score scripts of Easy, Normal and
+/// Hard, as read from game files
+pub fn get_song_info(romfs_path: &Path) -> std::io::Result<Vec<SongScripts>> {
+    let romfs_path_c = path_to_cstring(romfs_path)?;
 
+    debug!("Reading song info from {}", romfs_path.display());
     let result = unsafe { get_music_info(romfs_path_c.as_ptr()) };
 
     let (song_entries, _score_data_outer, score_data) = unsafe {
@@ -426,7 +495,7 @@ pub fn get_song_info(romfs_path: &Path) -> Vec<(Map, String, String, String)> {
             .collect::<Vec<_>>()
     };
 
-    izip!(song_entries, word_entries_list, score_data)
+    let maps = izip!(song_entries, word_entries_list, score_data)
         .map(|(song_entry, word_entries, score_data)| unsafe {
             let id = CStr::from_ptr(song_entry.id).to_str().unwrap();
             let id = Music::from_str(id).unwrap();
@@ -508,6 +577,8 @@ pub fn get_song_info(romfs_path: &Path) -> Vec<(Map, String, String, String)> {
                 song_info: SongInfo {
                     id: super::MusicID::Existing(id),
                     music_file: "".to_string(),
+                    intro_file: None,
+                    volume_db: None,
                     bpm,
                     offset,
                     length,
@@ -521,7 +592,9 @@ pub fn get_song_info(romfs_path: &Path) -> Vec<(Map, String, String, String)> {
                 map_scores,
             };
 
-            (map, score_easy, score_normal, score_hard)
+            (map, beat, score_easy, score_normal, score_hard)
         })
-        .collect::<Vec<_>>()
+        .collect::<Vec<_>>();
+
+    Ok(maps)
 }
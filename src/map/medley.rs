@@ -0,0 +1,201 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{BpmChanges, Map, MapScore, MusicID, ScoreData, ScoreEntry, SongInfo};
+
+/// Default crossfade between songs in seconds
+pub const DEFAULT_CROSSFADE: f32 = 2.0;
+
+#[derive(thiserror::Error, Debug)]
+pub enum MedleyError {
+    #[error("At least 2 maps are required for a medley")]
+    TooFewMaps,
+    #[error(
+        "Score of {0} runs {1:.2}s into the next song, shorten the crossfade or the score before \
+         joining"
+    )]
+    Overlap(MusicID, f32),
+    #[error("Medley score has {0} entries, too long to address BPM changes")]
+    TooLong(usize),
+}
+
+/// Joins `maps` into one medley map, in which each song starts at the end of
+/// the previous one minus `crossfade` seconds as in the joined audio.
+/// `audio_durations` are the durations of maps' audio in seconds. Blank
+/// entries with a BPM fitting the gap are inserted between songs so each score
+/// stays in sync with its audio. The medley takes area, offset, preview and
+/// texts (with title replaced) from the first map, and has no music file set.
+pub fn build_medley(
+    maps: &[&Map],
+    audio_durations: &[f32],
+    crossfade: f32,
+    id: &str,
+    title: &str,
+) -> Result<Map, MedleyError> {
+    if maps.len() < 2 {
+        return Err(MedleyError::TooFewMaps);
+    }
+    let first = maps[0];
+
+    let difficulties = maps
+        .iter()
+        .flat_map(|map| map.map_scores.keys().copied())
+        .collect::<HashSet<_>>();
+    let mut scores = difficulties
+        .iter()
+        .map(|difficulty| (*difficulty, vec![]))
+        .collect::<HashMap<_, Vec<ScoreEntry>>>();
+    let mut bpm_changes: Vec<(usize, f32)> = vec![];
+
+    let mut audio_start = 0.0;
+    let mut score_end = 0.0;
+    let mut len: usize = 0;
+    for (i, (map, audio_duration)) in maps.iter().zip(audio_durations).enumerate() {
+        let score_start = audio_start + map.song_info.offset;
+
+        if i > 0 {
+            let gap = score_start - score_end;
+            if gap <= 0.0 {
+                return Err(MedleyError::Overlap(maps[i - 1].song_info.id.clone(), -gap));
+            }
+
+            // A BPM change takes effect after its index, so changes for entries from
+            // `len` on are put at `len - 1`
+            let bridge_len = ((gap * map.song_info.bpm / 60.0).round() as usize).max(1);
+            bpm_changes.push((len.saturating_sub(1), bridge_len as f32 * 60.0 / gap));
+            for score in scores.values_mut() {
+                score.extend(std::iter::repeat_n(ScoreEntry::B, bridge_len));
+            }
+            len += bridge_len;
+            bpm_changes.push((len - 1, map.song_info.bpm));
+        }
+
+        // Scores of different difficulties may have different lengths, all are padded
+        // to the longest one and missing difficulties are left blank
+        let map_len = map
+            .map_scores
+            .values()
+            .map(|score| score.scores.0.len())
+            .max()
+            .unwrap_or_default();
+        for (difficulty, score) in scores.iter_mut() {
+            let map_score = map
+                .map_scores
+                .get(difficulty)
+                .map(|score| score.scores.0.as_slice())
+                .unwrap_or_default();
+            score.extend_from_slice(map_score);
+            score.resize(len + map_len, ScoreEntry::B);
+        }
+
+        bpm_changes.extend(
+            map.song_info
+                .bpm_changes
+                .iter()
+                .flat_map(|changes| changes.0.iter())
+                .filter(|(idx, _)| (*idx as usize) < map_len)
+                .map(|(idx, bpm)| (len + *idx as usize, *bpm)),
+        );
+
+        let score_duration: f32 = (0..map_len).map(|idx| 60.0 / map.bpm_at(idx)).sum();
+        score_end = score_start + score_duration;
+        audio_start += audio_duration - crossfade;
+        len += map_len;
+    }
+
+    if len > u16::MAX as usize {
+        return Err(MedleyError::TooLong(len));
+    }
+
+    let mut info_text = first.song_info.info_text.clone();
+    for text in info_text.values_mut() {
+        text.title = title.to_owned();
+        text.title_kana = Default::default();
+        text.sub_title = Default::default();
+    }
+
+    let mut medley = Map {
+        song_info:  SongInfo {
+            id: MusicID::New(id.to_owned()),
+            bpm: first.song_info.bpm,
+            offset: first.song_info.offset,
+            length: len as u16,
+            area: first.song_info.area,
+            info_text,
+            prev_start_ms: first.song_info.prev_start_ms,
+            bpm_changes: Some(BpmChanges(
+                bpm_changes
+                    .into_iter()
+                    .map(|(idx, bpm)| (idx as u16, bpm))
+                    .collect(),
+            )),
+            ..Default::default()
+        },
+        map_scores: scores
+            .into_iter()
+            .map(|(difficulty, score)| {
+                (
+                    difficulty,
+                    MapScore {
+                        scores: ScoreData(score),
+                    },
+                )
+            })
+            .collect(),
+    };
+    medley.collapse_bpm_changes();
+
+    Ok(medley)
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::Difficulty;
+
+    fn map(id: &str, bpm: f32, offset: f32, score: &str) -> Map {
+        Map {
+            song_info:  SongInfo {
+                id: MusicID::New(id.to_owned()),
+                bpm,
+                offset,
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str(score).unwrap(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_build_medley() {
+        // First score spans 1..5s, the second song starts at 8s with its score at 8.5s
+        let a = map("A", 120.0, 1.0, "O-O-O-O-");
+        let b = map("B", 60.0, 0.5, "SOSO");
+
+        let medley = build_medley(&[&a, &b], &[10.0, 8.0], 2.0, "Medley", "Medley").unwrap();
+
+        let score = medley.map_scores[&Difficulty::Hard].scores.to_string();
+        assert_eq!(score, "O-O-O-O-----SOSO");
+        assert_eq!(medley.song_info.length, 16);
+
+        let start_b: f32 =
+            medley.song_info.offset + (0..12).map(|idx| 60.0 / medley.bpm_at(idx)).sum::<f32>();
+        assert!((start_b - 8.5).abs() < 1e-4);
+        assert_eq!(medley.bpm_at(12), 60.0);
+
+        assert!(matches!(
+            build_medley(&[&a, &b], &[10.0, 8.0], 5.5, "Medley", "Medley"),
+            Err(MedleyError::Overlap(..))
+        ));
+        assert!(matches!(
+            build_medley(&[&a], &[10.0], 2.0, "Medley", "Medley"),
+            Err(MedleyError::TooFewMaps)
+        ));
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use serde_with::{DeserializeFromStr, SerializeDisplay};
+
+use super::{InvalidMapError, Map};
+
+/// Length of a display frame at 60Hz, in milliseconds
+const FRAME_MS: f32 = 1000.0 / 60.0;
+
+/// Shift of music offsets compensating display latency, applied to all maps at
+/// patch time. Written as milliseconds ("12ms" or "12") or 60Hz frames ("1f"),
+/// positive values make notes appear later.
+#[derive(Clone, Copy, Debug, PartialEq, SerializeDisplay, DeserializeFromStr)]
+pub enum OffsetNudge {
+    Ms(f32),
+    Frames(f32),
+}
+
+impl OffsetNudge {
+    pub fn seconds(&self) -> f32 {
+        match self {
+            Self::Ms(ms) => ms / 1000.0,
+            Self::Frames(frames) => frames * FRAME_MS / 1000.0,
+        }
+    }
+}
+
+impl Display for OffsetNudge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ms(ms) => write!(f, "{ms}ms"),
+            Self::Frames(frames) => write!(f, "{frames}f"),
+        }
+    }
+}
+
+impl FromStr for OffsetNudge {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (value, frames) = if let Some(value) = s.strip_suffix("ms") {
+            (value, false)
+        } else if let Some(value) = s.strip_suffix('f') {
+            (value, true)
+        } else {
+            (s, false)
+        };
+
+        let value = value.trim().parse::<f32>().map_err(|_| {
+            format!(
+                "Invalid offset nudge {s}, expected milliseconds (e.g. 12ms) or frames (e.g. 1f)"
+            )
+        })?;
+        Ok(if frames {
+            Self::Frames(value)
+        } else {
+            Self::Ms(value)
+        })
+    }
+}
+
+impl Map {
+    /// Shifts the music offset by `nudge`, the offset must stay non-negative
+    pub fn nudge_offset(&mut self, nudge: OffsetNudge) -> Result<(), InvalidMapError> {
+        let offset = self.song_info.offset + nudge.seconds();
+        if offset < 0.0 {
+            return Err(InvalidMapError::NegativeOffset(
+                self.song_info.offset,
+                nudge,
+            ));
+        }
+
+        self.song_info.offset = offset;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_offset_nudge() {
+        assert_eq!("12ms".parse(), Ok(OffsetNudge::Ms(12.0)));
+        assert_eq!("-8".parse(), Ok(OffsetNudge::Ms(-8.0)));
+        assert_eq!("2f".parse(), Ok(OffsetNudge::Frames(2.0)));
+        assert!("2 frames".parse::<OffsetNudge>().is_err());
+        assert!((OffsetNudge::Frames(3.0).seconds() - 0.05).abs() < 1e-6);
+
+        let mut map = Map::default();
+        map.song_info.offset = 0.01;
+        map.nudge_offset(OffsetNudge::Ms(5.0)).unwrap();
+        assert!((map.song_info.offset - 0.015).abs() < 1e-6);
+        assert!(matches!(
+            map.nudge_offset(OffsetNudge::Frames(-1.0)),
+            Err(InvalidMapError::NegativeOffset(..))
+        ));
+    }
+}
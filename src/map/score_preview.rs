@@ -0,0 +1,89 @@
+use std::{collections::HashMap, iter::zip};
+
+use itertools::Itertools;
+
+use super::{Difficulty, Map};
+
+impl Map {
+    /// Score of `difficulty` as the lines of its score script, numbered and
+    /// laid out as in game files, with BPM changes marked on the lines they
+    /// start in. `None` if there is no such score
+    pub fn score_preview(&self, difficulty: Difficulty) -> Option<String> {
+        let score = self.map_scores.get(&difficulty)?;
+        let bpm_changes = self.song_info.bpm_changes.clone().unwrap_or_default();
+        // Official songs keep the layout of their beat script
+        let beats_layout = self
+            .song_info
+            .beats_layout
+            .clone()
+            .unwrap_or_else(|| bpm_changes.beats_layout());
+
+        let mut markers = HashMap::<u16, Vec<String>>::new();
+        let entry_pos = bpm_changes.entry_pos(&Some(beats_layout.clone()));
+        for ((line, pos), (_, bpm)) in zip(entry_pos, &bpm_changes.0) {
+            markers
+                .entry(line)
+                .or_default()
+                .push(format!("{bpm} BPM from entry {}", pos + 1));
+        }
+
+        let script = score.to_script(&beats_layout);
+        let lines = script.lines().map(str::trim_end).collect::<Vec<_>>();
+        let width = lines
+            .iter()
+            .map(|line| line.len())
+            .max()
+            .unwrap_or_default();
+        let number_width = lines.len().to_string().len();
+
+        let header = format!(
+            "{} [{difficulty}], {} BPM, offset {}s, {} entries",
+            self.song_info.id,
+            self.song_info.bpm,
+            self.song_info.offset,
+            score.scores.0.len()
+        );
+        let body = (1..).zip(&lines).map(|(line_id, line)| {
+            let marker = markers
+                .get(&line_id)
+                .map(|markers| format!("  << {}", markers.join(", ")))
+                .unwrap_or_default();
+            format!("{line_id:>number_width$} | {line:width$}{marker}")
+                .trim_end()
+                .to_owned()
+        });
+
+        Some([header].into_iter().chain(body).join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{BpmChanges, MapScore, ScoreData};
+
+    #[test]
+    fn test_score_preview() {
+        let mut map = Map {
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str("O-O-S-S-OOOO").unwrap(),
+                },
+            },
+            ..Default::default()
+        };
+        map.song_info.bpm = 120.0;
+        map.song_info.bpm_changes = Some(BpmChanges(vec![(4, 180.0)]));
+
+        assert_eq!(
+            map.score_preview(Difficulty::Hard).unwrap(),
+            "Alice [Hard], 120 BPM, offset 0s, 12 entries\n1 | O, -, O, -,\n2 | S, -, S, -,  << \
+             180 BPM from entry 1\n3 | O, O, O, O,"
+        );
+        assert!(map.score_preview(Difficulty::Easy).is_none());
+    }
+}
@@ -0,0 +1,151 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::{
+    Deserialize, Deserializer,
+    de::{Error, Visitor},
+};
+
+use super::{Map, MapScore, MapsConfig, SongInfo, SongInfoText};
+use crate::duplicate_check::levenshtein;
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Rejects map configs with unknown keys in `MapsConfig::from_toml`, used by
+/// the `--strict` argument
+pub fn enable_strict() {
+    STRICT.store(true, Ordering::Relaxed);
+}
+
+pub(super) fn is_strict() -> bool {
+    STRICT.load(Ordering::Relaxed)
+}
+
+/// Key of a map config not read by any field, with the field it is most likely
+/// a typo of
+#[derive(Debug, PartialEq)]
+pub struct UnknownKey {
+    /// Dotted path of the key, e.g. `maps.0.song_info.bmp`
+    pub path:       String,
+    pub suggestion: Option<&'static str>,
+}
+
+impl std::fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown key {}", self.path)?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean {suggestion}?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Deserializer capturing the field names a struct asks for, which derived
+/// implementations pass to `deserialize_struct`
+struct FieldRecorder<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldRecorder<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(Error::custom("fields recorded"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+fn fields_of<'de, T: Deserialize<'de>>() -> &'static [&'static str] {
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldRecorder(&mut fields));
+    fields
+}
+
+/// Valid keys beside an unknown key, told by the keys leading to it
+fn sibling_fields(parents: &[&str]) -> &'static [&'static str] {
+    match parents {
+        [] => fields_of::<MapsConfig>(),
+        [.., "maps"] => fields_of::<Map>(),
+        [.., "song_info"] => fields_of::<SongInfo>(),
+        [.., "info_text", _] => fields_of::<SongInfoText>(),
+        [.., "map_scores", _] => fields_of::<MapScore>(),
+        _ => &[],
+    }
+}
+
+/// Describes an ignored key at `path`, suggesting the valid key closest to it
+fn unknown_key(path: String) -> UnknownKey {
+    // Array indices and options do not change which struct a key belongs to
+    let segments = path
+        .split('.')
+        .filter(|segment| *segment != "?" && segment.parse::<usize>().is_err())
+        .collect::<Vec<_>>();
+    let (key, parents) = segments.split_last().unwrap_or((&"", &[]));
+    let key = key.to_lowercase().chars().collect::<Vec<_>>();
+
+    let suggestion = sibling_fields(parents)
+        .iter()
+        .map(|field| {
+            (
+                levenshtein(&key, &field.chars().collect::<Vec<_>>()),
+                *field,
+            )
+        })
+        .filter(|(distance, field)| *distance <= 2.max(field.len() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field);
+
+    UnknownKey { path, suggestion }
+}
+
+/// Deserializes `value`, collecting keys no field reads instead of silently
+/// ignoring them
+pub(super) fn deserialize_strict<'de, T: Deserialize<'de>>(
+    value: toml::Value,
+) -> Result<(T, Vec<UnknownKey>), toml::de::Error> {
+    let mut unknown = vec![];
+    let result =
+        serde_ignored::deserialize(value, |path| unknown.push(unknown_key(path.to_string())))?;
+    Ok((result, unknown))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::EXAMPLE_CONFIG;
+
+    #[test]
+    fn test_unknown_keys() {
+        let config = EXAMPLE_CONFIG
+            .replace("bpm = 150.0", "bmp = 150.0\nbpm = 150.0")
+            .replace("schema_version = 1", "schema_version = 1\nvolume = 2.0");
+        let value = config.parse::<toml::Table>().unwrap();
+
+        let (_, unknown) = deserialize_strict::<MapsConfig>(toml::Value::Table(value)).unwrap();
+        assert_eq!(
+            unknown,
+            vec![
+                UnknownKey {
+                    path:       "maps.0.song_info.bmp".to_owned(),
+                    suggestion: Some("bpm"),
+                },
+                UnknownKey {
+                    path:       "volume".to_owned(),
+                    suggestion: Some("volume_db"),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Map, Music, MusicID, ScoreEntry};
+
+/// Probability of a note changing its kind in random variants, low enough to
+/// keep the feel of the original chart
+const RANDOM_FLIP_PROBABILITY: f64 = 0.25;
+
+/// Variant charts generated from every map, each variant is added as a new
+/// song with its own ID
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ChartVariant {
+    /// Normal and heavy notes swapped, scores carry no note positions so this
+    /// is the only mirroring possible
+    Mirror,
+    /// Some notes switched between normal and heavy, deterministic for the
+    /// same seed and map ID
+    Random { seed: u64 },
+}
+
+impl ChartVariant {
+    fn id_suffix(&self) -> String {
+        match self {
+            Self::Mirror => "Mirror".to_owned(),
+            Self::Random { seed } => format!("Random{seed}"),
+        }
+    }
+
+    fn title_suffix(&self) -> String {
+        match self {
+            Self::Mirror => " (Mirror)".to_owned(),
+            Self::Random { seed } => format!(" (Random #{seed})"),
+        }
+    }
+
+    /// Creates the variant of `map`, timing and blanks are kept as is
+    pub fn apply(&self, map: &Map) -> Map {
+        let mut variant = map.clone();
+        let id = map.song_info.id.to_string();
+        variant.song_info.id = MusicID::New(format!("{id}{}", self.id_suffix()));
+        for text in variant.song_info.info_text.values_mut() {
+            text.sub_title.push_str(&self.title_suffix());
+        }
+
+        let mut rng = match self {
+            Self::Mirror => None,
+            Self::Random { seed } => Some(SplitMix64::new(seed ^ fnv1a(id.as_bytes()))),
+        };
+
+        // Difficulties are visited in a fixed order so the output does not depend on
+        // the hash map order
+        let mut difficulties = variant.map_scores.keys().copied().collect::<Vec<_>>();
+        difficulties.sort_by_key(|d| *d as u8);
+        for difficulty in difficulties {
+            let score = variant.map_scores.get_mut(&difficulty).unwrap();
+            for entry in score.scores.0.iter_mut() {
+                let flip = match rng.as_mut() {
+                    None => true,
+                    Some(rng) => rng.next_f64() < RANDOM_FLIP_PROBABILITY,
+                };
+                if flip {
+                    *entry = match entry {
+                        ScoreEntry::O => ScoreEntry::S,
+                        ScoreEntry::S => ScoreEntry::O,
+                        ScoreEntry::B => ScoreEntry::B,
+                    };
+                }
+            }
+        }
+
+        variant
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("Variant ID {0} is already used by an official song or another map, rename the map")]
+pub struct VariantIDCollision(String);
+
+/// Creates all variants of `maps`, in the order of maps then variants. Fails
+/// if a variant ID is taken by an official song, one of `maps` or another
+/// variant, as it would silently overwrite that song.
+pub fn variant_maps(
+    maps: &[Map],
+    variants: &[ChartVariant],
+) -> Result<Vec<Map>, VariantIDCollision> {
+    let mut ids = maps
+        .iter()
+        .map(|map| map.song_info.id.to_string().to_lowercase())
+        .collect::<HashSet<_>>();
+
+    maps.iter()
+        .flat_map(|map| variants.iter().map(|variant| variant.apply(map)))
+        .map(|variant| {
+            let id = variant.song_info.id.to_string();
+            if Music::try_from(id.as_str()).is_ok() || !ids.insert(id.to_lowercase()) {
+                Err(VariantIDCollision(id))
+            } else {
+                Ok(variant)
+            }
+        })
+        .collect()
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Small PRNG with a stable output sequence, variants must not change between
+/// versions for the same seed
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{Difficulty, MapScore, ScoreData, SongInfo};
+
+    fn map(score: &str) -> Map {
+        Map {
+            song_info:  SongInfo {
+                id: MusicID::New("Newly".to_string()),
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str(score).unwrap(),
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        let original = map("OO-S-OOSO-OOOO-SS-O");
+
+        let mirror = ChartVariant::Mirror.apply(&original);
+        assert_eq!(mirror.song_info.id.to_string(), "NewlyMirror");
+        assert_eq!(
+            mirror.map_scores[&Difficulty::Hard].scores.to_string(),
+            "SS-O-SSOS-SSSS-OO-S"
+        );
+
+        let variant = ChartVariant::Random { seed: 7 };
+        let random = variant.apply(&original);
+        let random_score = random.map_scores[&Difficulty::Hard].scores.to_string();
+        assert_eq!(
+            random_score,
+            variant.apply(&original).map_scores[&Difficulty::Hard]
+                .scores
+                .to_string()
+        );
+        assert_eq!(random_score.len(), 19);
+        for (a, b) in random_score.chars().zip("OO-S-OOSO-OOOO-SS-O".chars()) {
+            assert_eq!(a == '-', b == '-');
+        }
+
+        let mut other = original.clone();
+        other.song_info.id = MusicID::New("Other".to_string());
+        assert_eq!(
+            variant_maps(&[original.clone(), other], &[ChartVariant::Mirror, variant])
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
+    #[test]
+    fn test_variant_id_collisions() {
+        let original = map("OO-S-OOSO");
+
+        let mut taken = original.clone();
+        taken.song_info.id = MusicID::New("newlymirror".to_string());
+        assert!(variant_maps(&[original.clone(), taken], &[ChartVariant::Mirror]).is_err());
+
+        assert!(variant_maps(&[original.clone(), original], &[ChartVariant::Mirror]).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter},
+};
+
+use serde::Serialize;
+use strum::IntoEnumIterator;
+
+use crate::map::{Difficulty, MapStatus, MapsConfig};
+
+/// Overview of a map in a map config, as listed by ListMaps
+#[derive(Serialize)]
+pub struct MapSummary {
+    /// Index of the map in the config, as accepted by `--update`
+    pub index:         usize,
+    pub id:            String,
+    pub title:         String,
+    pub effective_bpm: f32,
+    /// Duration in seconds
+    pub duration:      f32,
+    /// Estimated in-game levels keyed by difficulty, for difficulties with
+    /// scores
+    pub levels:        BTreeMap<String, u8>,
+    pub status:        MapStatus,
+    pub errors:        Vec<String>,
+    pub warnings:      Vec<String>,
+}
+
+impl Display for MapSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let level = |difficulty: Difficulty| {
+            self.levels
+                .get(&difficulty.to_string())
+                .map(|level| level.to_string())
+                .unwrap_or_else(|| "-".to_owned())
+        };
+
+        write!(
+            f,
+            "Map {}: {}, effective BPM: {}, duration: {}, levels (E/N/H): {}/{}/{}, id: {}, \
+             status: {}",
+            self.index,
+            self.title,
+            self.effective_bpm,
+            self.duration,
+            level(Difficulty::Easy),
+            level(Difficulty::Normal),
+            level(Difficulty::Hard),
+            self.id,
+            self.status
+        )?;
+
+        for error in &self.errors {
+            write!(f, "\n  Error: {error}")?;
+        }
+        for warning in &self.warnings {
+            write!(f, "\n  Warning: {warning}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Summarizes maps of `config` with templates expanded, validated for
+/// replacing existing songs if `replace_existing`
+pub fn summarize_maps(config: &MapsConfig, replace_existing: bool) -> Vec<MapSummary> {
+    config
+        .expanded_maps()
+        .iter()
+        .enumerate()
+        .map(|(index, map)| {
+            let title = map
+                .song_info
+                .info_text
+                .values()
+                .next()
+                .map(|text| text.title())
+                .unwrap_or_default();
+
+            let levels = Difficulty::iter()
+                .filter(|difficulty| map.map_scores.contains_key(difficulty))
+                .map(|difficulty| (difficulty.to_string(), map.level(difficulty, None)))
+                .collect();
+
+            let validation = map.validate_with_warnings(replace_existing);
+
+            MapSummary {
+                index,
+                id: map.song_info.id.to_string(),
+                title,
+                effective_bpm: map.effective_bpm(),
+                duration: map.duration(),
+                levels,
+                status: validation.status(),
+                errors: validation.errors,
+                warnings: validation.warnings,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::EXAMPLE_CONFIG;
+
+    #[test]
+    fn test_summarize_maps() {
+        let config: MapsConfig = toml::from_str(EXAMPLE_CONFIG).unwrap();
+        let summaries = summarize_maps(&config, false);
+
+        assert_eq!(summaries.len(), 2);
+        assert_eq!(summaries[1].id, "Newly");
+        assert_eq!(
+            summaries[1].levels.keys().collect::<Vec<_>>(),
+            ["Hard", "Normal"]
+        );
+        // Audio files of the example do not exist, and the first map replaces an
+        // existing song
+        assert_eq!(summaries[0].status, MapStatus::Error);
+        assert!(summaries[1].to_string().contains("levels (E/N/H): -/"));
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    map::Map,
+    output_sink::{EXEFS_PATCHES_DIR, OutputSink, cheats_dir, romfs_path},
+    platform,
+};
+
+/// File at the mod root recording what the mod patched
+pub const MANIFEST_FILE: &str = "mod_tool.toml";
+
+/// Version of mod_tool recorded in generated mods
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Part of a version that changes with incompatible output, the minor version
+/// before 1.0 and the major version after
+fn version_series(version: &str) -> &str {
+    let mut parts = version.splitn(3, '.');
+    let major = parts.next().unwrap_or_default();
+    match (major, parts.next()) {
+        ("0", Some(minor)) => &version[..major.len() + 1 + minor.len()],
+        _ => major,
+    }
+}
+
+/// Game files patched by more than one kind of mod, relative to RomFS root.
+/// They are regenerated from game files when merging instead of copied.
+fn regenerated_files() -> [String; 3] {
+    let platform = platform::current();
+    [
+        platform.share_data(),
+        platform.global_metadata().to_owned(),
+        platform.main_bundle(),
+    ]
+}
+
+/// ExeFS patches depend on the count of all added songs, so they are
+/// regenerated as well
+fn regenerated_dirs() -> [PathBuf; 2] {
+    [PathBuf::from(EXEFS_PATCHES_DIR), cheats_dir()]
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MergeError {
+    #[error(
+        "{0} has no {MANIFEST_FILE}, regenerate it with this version of mod_tool before merging"
+    )]
+    MissingManifest(PathBuf),
+    #[error("Song {0} is patched by more than one mod")]
+    DuplicateSong(String),
+    #[error("{0} differs between merged mods")]
+    ConflictingFile(PathBuf),
+}
+
+/// Options of UnlockFeatures
+#[derive(Default, Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UnlockOptions {
+    pub special_rules: bool,
+    pub musics:        bool,
+    pub characters:    bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude:       Vec<u16>,
+}
+
+/// Patches a generated mod applied to game files shared with other mods,
+/// written to the mod root so that mods can be merged later
+#[derive(Serialize, Deserialize)]
+pub struct ModManifest {
+    /// Version of mod_tool generating the mod, missing in mods generated
+    /// before versions are recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version:  Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unlock:        Option<UnlockOptions>,
+    /// Maps replacing existing songs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replaced_maps: Vec<Map>,
+    /// Maps added as new songs
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added_maps:    Vec<Map>,
+    /// Songs whose score files the scores of new songs are based on, keyed by
+    /// IDs of new songs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub score_donors:  BTreeMap<String, String>,
+}
+
+impl Default for ModManifest {
+    fn default() -> Self {
+        Self {
+            tool_version:  Some(TOOL_VERSION.to_owned()),
+            unlock:        None,
+            replaced_maps: vec![],
+            added_maps:    vec![],
+            score_donors:  BTreeMap::new(),
+        }
+    }
+}
+
+impl ModManifest {
+    /// Describes why files of the mod may not work with ones generated by
+    /// this version of mod_tool, if they may not
+    pub fn version_mismatch(&self) -> Option<String> {
+        match &self.tool_version {
+            None => Some("generated by an older mod_tool recording no version".to_owned()),
+            Some(version) if version_series(version) != version_series(TOOL_VERSION) => Some(
+                format!("generated by mod_tool {version}, incompatible with {TOOL_VERSION}"),
+            ),
+            Some(_) => None,
+        }
+    }
+
+    pub fn read(mod_dir: &Path) -> anyhow::Result<Self> {
+        let path = mod_dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            Err(MergeError::MissingManifest(mod_dir.to_owned()))?
+        }
+
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    /// Writes the manifest with music files reduced to their file names, as
+    /// the mod is shared without them and their local paths are private
+    pub fn write(&self, sink: &dyn OutputSink) -> anyhow::Result<()> {
+        let manifest = Self {
+            tool_version:  self.tool_version.clone(),
+            unlock:        self.unlock.clone(),
+            replaced_maps: self.replaced_maps.iter().map(without_local_paths).collect(),
+            added_maps:    self.added_maps.iter().map(without_local_paths).collect(),
+            score_donors:  self.score_donors.clone(),
+        };
+        sink.write(
+            Path::new(MANIFEST_FILE),
+            toml::to_string_pretty(&manifest)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    /// Records `donor` as the score donor of all added maps
+    pub fn with_score_donor(mut self, donor: Option<&str>) -> Self {
+        if let Some(donor) = donor {
+            self.score_donors = self
+                .added_maps
+                .iter()
+                .map(|map| (map.song_info.id.to_string(), donor.to_owned()))
+                .collect();
+        }
+        self
+    }
+
+    /// Combines manifests of mods merged into one. Unlock options are enabled
+    /// if any mod enables them, and DLCs excluded by any mod stay excluded.
+    pub fn merge(manifests: impl IntoIterator<Item = Self>) -> Result<Self, MergeError> {
+        let mut merged = Self::default();
+        let mut ids = HashSet::new();
+
+        for manifest in manifests {
+            for map in manifest.replaced_maps.iter().chain(&manifest.added_maps) {
+                let id = map.song_info.id.to_string();
+                if !ids.insert(id.to_lowercase()) {
+                    return Err(MergeError::DuplicateSong(id));
+                }
+            }
+
+            if let Some(unlock) = manifest.unlock {
+                let merged_unlock = merged.unlock.get_or_insert_with(Default::default);
+                merged_unlock.special_rules |= unlock.special_rules;
+                merged_unlock.musics |= unlock.musics;
+                merged_unlock.characters |= unlock.characters;
+                for dlc in unlock.exclude {
+                    if !merged_unlock.exclude.contains(&dlc) {
+                        merged_unlock.exclude.push(dlc);
+                    }
+                }
+            }
+
+            merged.replaced_maps.extend(manifest.replaced_maps);
+            merged.added_maps.extend(manifest.added_maps);
+            merged.score_donors.extend(manifest.score_donors);
+        }
+
+        Ok(merged)
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn without_local_paths(map: &Map) -> Map {
+    let mut map = map.clone();
+    map.song_info.music_file = file_name(&map.song_info.music_file);
+    map.song_info.intro_file = map.song_info.intro_file.as_deref().map(file_name);
+    map
+}
+
+fn is_regenerated(rel_path: &Path) -> bool {
+    rel_path == Path::new(MANIFEST_FILE)
+        || regenerated_files()
+            .iter()
+            .any(|file| rel_path == romfs_path(file))
+        || regenerated_dirs()
+            .iter()
+            .any(|dir| rel_path.starts_with(dir))
+}
+
+fn copy_dir_files(
+    mod_root: &Path,
+    dir: &Path,
+    sink: &dyn OutputSink,
+    copied: &mut HashSet<PathBuf>,
+) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            copy_dir_files(mod_root, &path, sink, copied)?;
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(mod_root)?.to_owned();
+        if is_regenerated(&rel_path) {
+            continue;
+        }
+
+        let out_path = sink.file_path(&rel_path)?;
+        if copied.contains(&rel_path) {
+            if std::fs::read(&path)? != std::fs::read(&out_path)? {
+                Err(MergeError::ConflictingFile(rel_path))?
+            }
+        } else {
+            std::fs::copy(&path, &out_path)?;
+            copied.insert(rel_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Copies files of `mod_dirs` that are not regenerated when merging, such as
+/// song audio and scores. Files in more than one mod must be identical.
+pub fn copy_mod_files(mod_dirs: &[PathBuf], sink: &dyn OutputSink) -> anyhow::Result<()> {
+    let mut copied = HashSet::new();
+    for mod_dir in mod_dirs {
+        copy_dir_files(mod_dir, mod_dir, sink, &mut copied)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::{MusicID, SongInfo};
+
+    fn map(id: &str) -> Map {
+        Map {
+            song_info: SongInfo {
+                id: MusicID::New(id.to_owned()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_manifests() {
+        let unlock = ModManifest {
+            unlock: Some(UnlockOptions {
+                musics: true,
+                exclude: vec![3],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let songs = ModManifest {
+            added_maps: vec![map("SongA"), map("SongB")],
+            ..Default::default()
+        }
+        .with_score_donor(Some("Karisuma"));
+
+        let merged = ModManifest::merge([unlock, songs]).unwrap();
+        assert_eq!(
+            merged.unlock,
+            Some(UnlockOptions {
+                musics: true,
+                exclude: vec![3],
+                ..Default::default()
+            })
+        );
+        assert_eq!(merged.added_maps.len(), 2);
+        assert_eq!(merged.score_donors["SongB"], "Karisuma");
+
+        let duplicate = ModManifest {
+            added_maps: vec![map("songa")],
+            ..Default::default()
+        };
+        let songs = ModManifest {
+            added_maps: vec![map("SongA")],
+            ..Default::default()
+        };
+        assert!(matches!(
+            ModManifest::merge([songs, duplicate]),
+            Err(MergeError::DuplicateSong(id)) if id == "songa"
+        ));
+    }
+
+    #[test]
+    fn test_without_local_paths() {
+        let mut song = map("SongA");
+        song.song_info.music_file = "/home/user/music/Song A.flac".to_owned();
+        song.song_info.intro_file = Some("/home/user/music/intro.wav".to_owned());
+
+        let song = without_local_paths(&song);
+        assert_eq!(song.song_info.music_file, "Song A.flac");
+        assert_eq!(song.song_info.intro_file.as_deref(), Some("intro.wav"));
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        assert_eq!(version_series("0.3.1"), "0.3");
+        assert_eq!(version_series("1.2.0"), "1");
+
+        assert!(ModManifest::default().version_mismatch().is_none());
+        let manifest: ModManifest = toml::from_str("").unwrap();
+        assert!(manifest.version_mismatch().is_some());
+        let manifest = ModManifest {
+            tool_version: Some("0.1.0".to_owned()),
+            ..Default::default()
+        };
+        assert!(manifest.version_mismatch().unwrap().contains("0.1.0"));
+    }
+
+    #[test]
+    fn test_is_regenerated() {
+        assert!(is_regenerated(Path::new(MANIFEST_FILE)));
+        assert!(is_regenerated(&romfs_path(
+            "StreamingAssets/Switch/share_data"
+        )));
+        assert!(is_regenerated(Path::new("exefs_patches/mod/ABCD.ips")));
+        assert!(!is_regenerated(&romfs_path(
+            "StreamingAssets/Sounds/BGM_SONGA.acb"
+        )));
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    fs::File,
+    io::Write,
+    path::{Component, Path},
+};
+
+use serde::Serialize;
+use zip::{ZipWriter, write::FileOptions};
+
+use crate::{
+    map::{Lang, Map},
+    mod_merge::ModManifest,
+    output_sink::{EXEFS_PATCHES_DIR, romfs_path},
+    platform,
+};
+
+/// Top-level folders of generated mods in all output layouts, other files in
+/// the output directory are not packaged
+const PACKAGED_DIRS: &[&str] = &["contents", EXEFS_PATCHES_DIR, "mods", "load"];
+/// Describes the packaged mod, at the archive root
+const PACKAGE_INFO_NAME: &str = "mod_info.toml";
+
+#[derive(Serialize)]
+pub struct PackagedSong {
+    pub id:                String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title:             Option<String>,
+    pub replaces_existing: bool,
+}
+
+impl PackagedSong {
+    fn from_map(map: &Map, replaces_existing: bool) -> Self {
+        let info_text = &map.song_info.info_text;
+        let title = info_text
+            .get(&Lang::EN)
+            .or_else(|| info_text.get(&Lang::JA))
+            .or_else(|| info_text.values().next())
+            .map(|text| text.title.clone());
+
+        Self {
+            id: map.song_info.id.to_string(),
+            title,
+            replaces_existing,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct PackageInfo {
+    pub name:         String,
+    /// Version of mod_tool generating the mod, if recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    pub songs:        Vec<PackagedSong>,
+}
+
+/// Songs in the generated mod at `mod_dir`, from its manifest or from audio
+/// files for mods generated before manifests are written
+fn packaged_songs(
+    mod_dir: &Path,
+    manifest: Option<&ModManifest>,
+) -> anyhow::Result<Vec<PackagedSong>> {
+    if let Some(manifest) = manifest {
+        return Ok(manifest
+            .replaced_maps
+            .iter()
+            .map(|map| PackagedSong::from_map(map, true))
+            .chain(
+                manifest
+                    .added_maps
+                    .iter()
+                    .map(|map| PackagedSong::from_map(map, false)),
+            )
+            .collect());
+    }
+
+    let sounds_dir = mod_dir.join(romfs_path(platform::current().sounds_dir()));
+    if !sounds_dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut songs = std::fs::read_dir(sounds_dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            name.strip_prefix("BGM_")
+                .and_then(|name| name.strip_suffix(".acb"))
+                .map(|id| PackagedSong {
+                    id:                id.to_owned(),
+                    title:             None,
+                    replaces_existing: false,
+                })
+        })
+        .collect::<Vec<_>>();
+    songs.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(songs)
+}
+
+/// Path of `rel_path` inside the archive, with the IPS patch folder renamed to
+/// `mod_name`
+fn archive_path(rel_path: &Path, mod_name: &str) -> String {
+    let mut components = rel_path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    if components.len() > 2 && components[0] == EXEFS_PATCHES_DIR {
+        components[1] = mod_name.to_owned();
+    }
+
+    components.join("/")
+}
+
+fn add_dir(
+    writer: &mut ZipWriter<File>,
+    mod_dir: &Path,
+    dir: &Path,
+    mod_name: &str,
+) -> anyhow::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            add_dir(writer, mod_dir, &path, mod_name)?;
+        } else {
+            let name = archive_path(path.strip_prefix(mod_dir)?, mod_name);
+            writer.start_file(name, FileOptions::default())?;
+            std::io::copy(&mut File::open(&path)?, writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Packages the generated mod at `mod_dir` into a zip archive extracted to the
+/// SD card root as is, with a description of the included songs
+pub fn package_mod(mod_dir: &Path, mod_name: &str, out_path: &Path) -> anyhow::Result<PackageInfo> {
+    let manifest = ModManifest::read(mod_dir).ok();
+    let info = PackageInfo {
+        name:         mod_name.to_owned(),
+        tool_version: manifest.as_ref().and_then(|m| m.tool_version.clone()),
+        songs:        packaged_songs(mod_dir, manifest.as_ref())?,
+    };
+
+    let mut writer = ZipWriter::new(File::create(out_path)?);
+    for dir in PACKAGED_DIRS {
+        let dir = mod_dir.join(dir);
+        if dir.is_dir() {
+            add_dir(&mut writer, mod_dir, &dir, mod_name)?;
+        }
+    }
+
+    writer.start_file(PACKAGE_INFO_NAME, FileOptions::default())?;
+    writer.write_all(toml::to_string_pretty(&info)?.as_bytes())?;
+    writer.finish()?;
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_archive_path() {
+        assert_eq!(
+            archive_path(Path::new("exefs_patches/out/ABCD.ips"), "My Mod"),
+            "exefs_patches/My Mod/ABCD.ips"
+        );
+        assert_eq!(
+            archive_path(&romfs_path("StreamingAssets/Sounds/BGM_SONG.acb"), "My Mod"),
+            "contents/0100E9D00D6C2000/romfs/Data/StreamingAssets/Sounds/BGM_SONG.acb"
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use crate::{
+    map::Map,
+    output_sink::{OutputLayout, romfs_path},
+    platform,
+};
+
+/// Encoded audio below this bitrate is flagged, HCA output of a successful
+/// conversion is far above it
+const MIN_BITRATE_KBPS: f32 = 64.0;
+/// Songs with bitrate below this ratio of the median bitrate are flagged
+const MIN_MEDIAN_RATIO: f32 = 0.5;
+
+/// Output files and encode quality of a generated song
+pub struct SongOutput {
+    pub id:       String,
+    pub acb_size: u64,
+    pub awb_size: u64,
+    /// Duration of the source music file in seconds
+    pub duration: Option<f32>,
+    /// Set by `check_outliers`
+    pub flag:     Option<String>,
+}
+
+impl SongOutput {
+    /// Bitrate of the encoded audio in kbps
+    pub fn bitrate(&self) -> Option<f32> {
+        self.duration
+            .filter(|duration| *duration > 0.0)
+            .map(|duration| self.awb_size as f32 * 8.0 / duration / 1000.0)
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or_default()
+}
+
+/// Collects output sizes of generated songs in `out_dir`, which is in `layout`
+pub fn song_outputs(
+    out_dir: &Path,
+    maps: &[Map],
+    layout: OutputLayout,
+    mod_name: &str,
+) -> Vec<SongOutput> {
+    let platform = platform::current();
+    let output_path = |id: &str, extension: &str| {
+        out_dir.join(layout.map_path(&romfs_path(platform.sound_file(id, extension)), mod_name))
+    };
+
+    let mut outputs = maps
+        .iter()
+        .map(|map| {
+            let id = map.song_info.id.to_string();
+
+            SongOutput {
+                acb_size: file_size(&output_path(&id, "acb")),
+                awb_size: file_size(&output_path(&id, "awb")),
+                duration: map.song_info.audio_duration().ok(),
+                flag: None,
+                id,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    check_outliers(&mut outputs);
+    outputs
+}
+
+/// Flags songs with missing or suspiciously small audio output, which usually
+/// means a failed conversion
+pub fn check_outliers(outputs: &mut [SongOutput]) {
+    let mut bitrates = outputs
+        .iter()
+        .filter(|output| output.awb_size > 0)
+        .filter_map(|output| output.bitrate())
+        .collect::<Vec<_>>();
+    bitrates.sort_by(f32::total_cmp);
+    let median = bitrates.get(bitrates.len() / 2).copied();
+
+    for output in outputs.iter_mut() {
+        output.flag = if output.acb_size == 0 || output.awb_size == 0 {
+            Some("missing output file".to_owned())
+        } else {
+            match (output.bitrate(), median) {
+                (Some(bitrate), _) if bitrate < MIN_BITRATE_KBPS => {
+                    Some(format!("bitrate below {MIN_BITRATE_KBPS} kbps"))
+                }
+                (Some(bitrate), Some(median)) if bitrate < median * MIN_MEDIAN_RATIO => {
+                    Some(format!("bitrate far below other songs ({median:.0} kbps)"))
+                }
+                (None, _) => Some("unable to read source duration".to_owned()),
+                _ => None,
+            }
+        };
+    }
+}
+
+/// Whether any song is flagged
+pub fn has_outliers(outputs: &[SongOutput]) -> bool {
+    outputs.iter().any(|output| output.flag.is_some())
+}
+
+/// Table of song outputs
+pub struct OutputReport<'a>(pub &'a [SongOutput]);
+
+impl Display for OutputReport<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let id_width = self
+            .0
+            .iter()
+            .map(|output| output.id.len())
+            .chain([2])
+            .max()
+            .unwrap_or_default();
+
+        writeln!(
+            f,
+            "{:id_width$}  {:>10}  {:>10}  {:>9}  {:>8}",
+            "ID", "ACB (KiB)", "AWB (KiB)", "Duration", "kbps"
+        )?;
+        for output in self.0 {
+            let duration = output
+                .duration
+                .map(|d| format!("{d:.1}s"))
+                .unwrap_or("-".to_owned());
+            let bitrate = output
+                .bitrate()
+                .map(|b| format!("{b:.0}"))
+                .unwrap_or("-".to_owned());
+
+            write!(
+                f,
+                "{:id_width$}  {:>10}  {:>10}  {duration:>9}  {bitrate:>8}",
+                output.id,
+                output.acb_size / 1024,
+                output.awb_size / 1024,
+            )?;
+            if let Some(flag) = &output.flag {
+                write!(f, "  <- {flag}")?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn output(id: &str, awb_size: u64, duration: Option<f32>) -> SongOutput {
+        SongOutput {
+            id: id.to_owned(),
+            acb_size: 1024,
+            awb_size,
+            duration,
+            flag: None,
+        }
+    }
+
+    #[test]
+    fn test_check_outliers() {
+        let mut outputs = vec![
+            output("a", 4_000_000, Some(120.0)),
+            output("b", 3_600_000, Some(110.0)),
+            output("c", 1_200_000, Some(100.0)),
+            output("d", 100_000, Some(120.0)),
+            output("e", 0, Some(120.0)),
+            output("f", 4_000_000, None),
+        ];
+
+        check_outliers(&mut outputs);
+
+        assert!(outputs[0].flag.is_none());
+        assert!(outputs[1].flag.is_none());
+        assert!(outputs[2].flag.as_ref().unwrap().contains("other songs"));
+        assert!(outputs[3].flag.as_ref().unwrap().contains("below"));
+        assert!(outputs[4].flag.as_ref().unwrap().contains("missing"));
+        assert!(outputs[5].flag.as_ref().unwrap().contains("duration"));
+        assert!(has_outliers(&outputs));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Title ID of the game release mods are generated for by default
+pub const DEFAULT_TITLE_ID: &str = "0100E9D00D6C2000";
+
+static TITLE_ID: OnceLock<String> = OnceLock::new();
+
+/// Checks that `title_id` is 16 hex digits, and returns it in uppercase as in
+/// Atmosphère paths
+pub fn parse_title_id(title_id: &str) -> Result<String, String> {
+    if title_id.len() == 16 && title_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(title_id.to_uppercase())
+    } else {
+        Err(format!("{title_id} is not a title ID of 16 hex digits"))
+    }
+}
+
+/// Sets the title ID in output paths, for dumps of other regions or releases.
+/// Only the first call takes effect, so the `--title-id` argument takes
+/// precedence over map configs.
+pub fn set_title_id(title_id: String) {
+    let _ = TITLE_ID.set(title_id);
+}
+
+pub fn title_id() -> &'static str {
+    TITLE_ID
+        .get()
+        .map(|title_id| title_id.as_str())
+        .unwrap_or(DEFAULT_TITLE_ID)
+}
+
+fn contents_dir() -> PathBuf {
+    Path::new("contents").join(title_id())
+}
+
+/// RomFS data directory of the game relative to the mod root, in the layout
+/// loaded by Atmosphère
+pub fn romfs_data_dir() -> PathBuf {
+    contents_dir().join("romfs/Data")
+}
+
+/// Cheat directory of the game relative to the mod root, cheat files in it are
+/// named by build ID
+pub fn cheats_dir() -> PathBuf {
+    contents_dir().join("cheats")
+}
+
+/// Directory of IPS patches relative to the mod root, each mod has a subfolder
+/// in it
+pub const EXEFS_PATCHES_DIR: &str = "exefs_patches";
+
+/// Directory structure of generated mods. Files are generated in the Atmosphère
+/// layout and moved into other layouts by [`OutputLayout::map_path`].
+#[derive(strum::EnumString, strum::Display, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum OutputLayout {
+    /// LayeredFS on the SD card root: `contents/<title ID>` and
+    /// `exefs_patches/<mod name>`
+    #[default]
+    Atmosphere,
+    /// Ryujinx mods directory: `mods/contents/<title ID>/<mod name>`
+    Ryujinx,
+    /// Yuzu load directory: `load/<title ID>/<mod name>`
+    Yuzu,
+}
+
+impl OutputLayout {
+    /// Folder of the mod, containing `romfs`, `exefs` and `cheats`, for
+    /// emulator layouts
+    fn mod_dir(self, mod_name: &str) -> Option<PathBuf> {
+        match self {
+            Self::Atmosphere => None,
+            Self::Ryujinx => Some(PathBuf::from_iter([
+                "mods",
+                "contents",
+                title_id(),
+                mod_name,
+            ])),
+            Self::Yuzu => Some(PathBuf::from_iter(["load", title_id(), mod_name])),
+        }
+    }
+
+    /// Maps `rel_path` in the Atmosphère layout to this layout, paths outside
+    /// of game contents are kept as is
+    pub fn map_path(self, rel_path: &Path, mod_name: &str) -> PathBuf {
+        let Some(mod_dir) = self.mod_dir(mod_name) else {
+            return rel_path.to_owned();
+        };
+
+        if let Ok(rest) = rel_path.strip_prefix(contents_dir()) {
+            // romfs and cheats have the same names in all layouts
+            mod_dir.join(rest)
+        } else if let Ok(rest) = rel_path.strip_prefix(EXEFS_PATCHES_DIR) {
+            // Skips the mod name folder
+            mod_dir
+                .join("exefs")
+                .join(rest.iter().skip(1).collect::<PathBuf>())
+        } else {
+            rel_path.to_owned()
+        }
+    }
+}
+
+/// Path of `rel_path` in the game's RomFS data directory, relative to the mod
+/// root
+pub fn romfs_path(rel_path: impl AsRef<Path>) -> PathBuf {
+    let mut path = romfs_data_dir();
+    path.push(rel_path);
+    path
+}
+
+/// Destination of generated mod files. Paths passed to a sink are relative to
+/// the mod root in the Atmosphère layout, sinks for other layouts or targets
+/// map them as needed.
+pub trait OutputSink {
+    /// Prepares a local file for `rel_path` and returns its path. Most files
+    /// are produced by the native helper or ffmpeg, which can only write to the
+    /// file system, so sinks not writing to a plain directory should hand out
+    /// local files and pick them up in `finish`.
+    fn file_path(&self, rel_path: &Path) -> std::io::Result<PathBuf>;
+
+    /// Writes `content` to `rel_path`
+    fn write(&self, rel_path: &Path, content: &[u8]) -> std::io::Result<()> {
+        std::fs::write(self.file_path(rel_path)?, content)
+    }
+
+    /// Called after all files are generated
+    fn finish(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes files into a plain directory
+pub struct DirSink {
+    root:     PathBuf,
+    layout:   OutputLayout,
+    mod_name: String,
+}
+
+impl DirSink {
+    pub fn new(root: &Path) -> Self {
+        Self::with_layout(root, OutputLayout::default(), "")
+    }
+
+    /// Writes files in `layout`, `mod_name` names the mod folder of emulator
+    /// layouts
+    pub fn with_layout(root: &Path, layout: OutputLayout, mod_name: &str) -> Self {
+        Self {
+            root: root.to_owned(),
+            layout,
+            mod_name: mod_name.to_owned(),
+        }
+    }
+}
+
+impl OutputSink for DirSink {
+    fn file_path(&self, rel_path: &Path) -> std::io::Result<PathBuf> {
+        let path = self
+            .root
+            .join(self.layout.map_path(rel_path, &self.mod_name));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dir_sink() {
+        let root = std::env::temp_dir().join("spell_bubble_dir_sink_test");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let sink = DirSink::new(&root);
+        let rel_path = romfs_path("StreamingAssets/Sounds/BGM_TEST.acb");
+        sink.write(&rel_path, b"acb").unwrap();
+
+        assert_eq!(std::fs::read(root.join(&rel_path)).unwrap(), b"acb");
+        assert!(rel_path.starts_with(romfs_data_dir()));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_output_layout() {
+        let acb = romfs_path("StreamingAssets/Sounds/BGM_TEST.acb");
+        let ips = Path::new("exefs_patches/mod/ABCD.ips");
+
+        assert_eq!(OutputLayout::Atmosphere.map_path(&acb, "mod"), acb);
+        assert_eq!(
+            OutputLayout::Ryujinx.map_path(&acb, "mod"),
+            Path::new(
+                "mods/contents/0100E9D00D6C2000/mod/romfs/Data/StreamingAssets/Sounds/BGM_TEST.acb"
+            )
+        );
+        assert_eq!(
+            OutputLayout::Yuzu.map_path(ips, "mod"),
+            Path::new("load/0100E9D00D6C2000/mod/exefs/ABCD.ips")
+        );
+        assert_eq!(
+            OutputLayout::Yuzu.map_path(Path::new("mod_tool.toml"), "mod"),
+            Path::new("mod_tool.toml")
+        );
+    }
+
+    #[test]
+    fn test_parse_title_id() {
+        assert_eq!(
+            parse_title_id("0100e9d00d6c2000").as_deref(),
+            Ok(DEFAULT_TITLE_ID)
+        );
+        assert!(parse_title_id("0100E9D00D6C200").is_err());
+        assert!(parse_title_id("0100E9D00D6C200G").is_err());
+    }
+}
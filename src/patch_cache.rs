@@ -0,0 +1,134 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    input_check::{InputHashes, hash_file},
+    map::Map,
+    output_sink::{OutputLayout, OutputSink, romfs_path},
+    platform,
+};
+
+/// File in the generated mod recording what its songs were patched from
+pub const CACHE_FILE: &str = "patch_cache.toml";
+
+/// Input hashes of songs in a generated mod, keyed by song ID. Later PatchMap
+/// runs into the same directory skip converting audio and patching scores of
+/// songs whose inputs are unchanged, as their files would come out the same.
+#[derive(Default, Serialize, Deserialize)]
+pub struct PatchCache {
+    songs: BTreeMap<String, String>,
+}
+
+impl PatchCache {
+    /// Reads the cache of the mod in `out_dir`, empty if there is none or it
+    /// cannot be read
+    pub fn read(out_dir: &Path) -> Self {
+        std::fs::read_to_string(out_dir.join(CACHE_FILE))
+            .ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn write(&self, sink: &dyn OutputSink) -> anyhow::Result<()> {
+        sink.write(
+            Path::new(CACHE_FILE),
+            toml::to_string_pretty(self)?.as_bytes(),
+        )?;
+        Ok(())
+    }
+
+    pub fn insert(&mut self, id: String, hash: String) {
+        self.songs.insert(id, hash);
+    }
+
+    pub fn is_unchanged(&self, id: &str, hash: &str) -> bool {
+        self.songs.get(id).is_some_and(|h| h == hash)
+    }
+}
+
+/// Hash of everything audio and score files of `map` are generated from: the
+/// map itself, contents of its audio files, input game files and whether
+/// existing songs are replaced
+pub fn song_hash(
+    map: &Map,
+    game_inputs: &InputHashes,
+    replace_existing: bool,
+) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+
+    // Converted to a value first, whose objects have sorted keys, as hash maps of
+    // the map serialize in random order
+    let map_value = serde_json::to_value(map)?;
+    hasher.update(serde_json::to_vec(&map_value)?);
+    for file in map.song_info.audio_files() {
+        hasher.update(hash_file(Path::new(file))?);
+    }
+    for hash in game_inputs.values() {
+        hasher.update(hash);
+    }
+    hasher.update([replace_existing as u8]);
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Whether audio and score files of song `id` exist in the mod in `out_dir`
+pub fn has_outputs(out_dir: &Path, id: &str, layout: OutputLayout, mod_name: &str) -> bool {
+    let platform = platform::current();
+    [
+        platform.sound_file(id, "acb"),
+        platform.sound_file(id, "awb"),
+        platform.score_file(id),
+    ]
+    .iter()
+    .all(|file| {
+        out_dir
+            .join(layout.map_path(&romfs_path(file), mod_name))
+            .is_file()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{Difficulty, MapScore, ScoreData};
+
+    #[test]
+    fn test_song_hash() {
+        let mut music_file = std::env::temp_dir();
+        music_file.push(format!("patch_cache_test_{}.ogg", std::process::id()));
+        std::fs::write(&music_file, "music").unwrap();
+
+        let mut map = Map {
+            map_scores: hashmap! {
+                Difficulty::Easy => MapScore {
+                    scores: ScoreData::from_str("----O-O-").unwrap(),
+                },
+                Difficulty::Hard => MapScore {
+                    scores: ScoreData::from_str("----SOSO").unwrap(),
+                },
+            },
+            ..Default::default()
+        };
+        map.song_info.music_file = music_file.to_string_lossy().to_string();
+        let inputs = InputHashes::new();
+
+        let hash = song_hash(&map, &inputs, false).unwrap();
+        let mut cache = PatchCache::default();
+        cache.insert("Song".to_owned(), hash.clone());
+        assert!(cache.is_unchanged("Song", &song_hash(&map.clone(), &inputs, false).unwrap()));
+        assert!(!cache.is_unchanged("Other", &hash));
+        assert_ne!(song_hash(&map, &inputs, true).unwrap(), hash);
+
+        std::fs::write(&music_file, "remastered").unwrap();
+        assert_ne!(song_hash(&map, &inputs, false).unwrap(), hash);
+
+        std::fs::remove_file(&music_file).unwrap();
+        assert!(song_hash(&map, &inputs, false).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+
+use crate::{
+    logging,
+    map::{PatchProgress, SongPhase},
+};
+
+/// Progress bars of a PatchMap run: an overall bar counting patched songs with
+/// elapsed and remaining time, and a spinner for each song being patched
+pub struct PatchProgressBars {
+    multi:   MultiProgress,
+    overall: ProgressBar,
+    songs:   Mutex<HashMap<String, ProgressBar>>,
+}
+
+impl PatchProgressBars {
+    pub fn new(song_count: usize) -> Self {
+        let multi = MultiProgress::new();
+        let overall = multi.add(ProgressBar::new(song_count as u64));
+        overall.set_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {bar:40} {pos}/{len} songs, about {eta} left {msg}",
+            )
+            .unwrap(),
+        );
+
+        logging::set_progress(Some(multi.clone()));
+
+        Self {
+            multi,
+            overall,
+            songs: Default::default(),
+        }
+    }
+
+    pub fn update(&self, progress: PatchProgress) {
+        match progress {
+            PatchProgress::Song(id, phase) => {
+                let mut songs = self.songs.lock().unwrap();
+                let bar = songs.entry(id.to_owned()).or_insert_with(|| {
+                    let bar = self.multi.add(ProgressBar::new_spinner());
+                    bar.set_style(
+                        ProgressStyle::with_template("  {spinner} {prefix:20} {msg} [{elapsed}]")
+                            .unwrap(),
+                    );
+                    bar.set_prefix(id.to_owned());
+                    bar.enable_steady_tick(Duration::from_millis(100));
+                    bar
+                });
+
+                match phase {
+                    SongPhase::Converting => bar.set_message("converting audio"),
+                    SongPhase::PatchingAcb => bar.set_message("encoding acb"),
+                    SongPhase::PatchingScore => bar.set_message("patching score"),
+                    SongPhase::Done => {
+                        bar.finish_and_clear();
+                        songs.remove(id);
+                        self.overall.inc(1);
+                    }
+                }
+            }
+            PatchProgress::ShareData => self.overall.set_message("patching share_data"),
+        }
+    }
+
+    /// Shows `message` after the overall bar, for steps after patching songs
+    pub fn set_message(&self, message: &'static str) {
+        self.overall.set_message(message);
+    }
+
+    pub fn finish(&self) {
+        for bar in self.songs.lock().unwrap().values() {
+            bar.finish_and_clear();
+        }
+        self.overall.finish_with_message("done");
+        logging::set_progress(None);
+    }
+
+    /// Stops all bars where they are, songs being patched keep showing the
+    /// step they failed in
+    pub fn abandon(&self) {
+        for bar in self.songs.lock().unwrap().values() {
+            bar.abandon();
+        }
+        self.overall.abandon_with_message("failed");
+        logging::set_progress(None);
+    }
+}
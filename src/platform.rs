@@ -0,0 +1,107 @@
+use std::sync::OnceLock;
+
+/// Layout of game files under the game data directory (RomFS root on Switch),
+/// which differs between platforms as Unity names the asset bundle folder
+/// after the build target
+pub struct PlatformProfile {
+    /// Folder of asset bundles under StreamingAssets, the main asset bundle in
+    /// it has the same name
+    bundle_dir:      &'static str,
+    sounds_dir:      &'static str,
+    global_metadata: &'static str,
+}
+
+impl PlatformProfile {
+    /// Folder of asset bundles, relative to the data directory
+    pub fn bundle_dir(&self) -> String {
+        format!("StreamingAssets/{}", self.bundle_dir)
+    }
+
+    pub fn share_data(&self) -> String {
+        format!("{}/share_data", self.bundle_dir())
+    }
+
+    /// The main asset bundle, patched to load assets of new songs
+    pub fn main_bundle(&self) -> String {
+        format!("{}/{}", self.bundle_dir(), self.bundle_dir)
+    }
+
+    pub fn score_dir(&self) -> String {
+        format!("{}/share_scores", self.bundle_dir())
+    }
+
+    /// Score file of `song_id`, which is lowercased as in game files
+    pub fn score_file(&self, song_id: &str) -> String {
+        format!("{}/score_{}", self.score_dir(), song_id.to_lowercase())
+    }
+
+    pub fn sounds_dir(&self) -> &'static str {
+        self.sounds_dir
+    }
+
+    /// Audio file of a song with the given extension (acb or awb), the song ID
+    /// is uppercased as in game files
+    pub fn sound_file(&self, song_id: &str, extension: &str) -> String {
+        format!(
+            "{}/BGM_{}.{extension}",
+            self.sounds_dir,
+            song_id.to_uppercase()
+        )
+    }
+
+    pub fn global_metadata(&self) -> &'static str {
+        self.global_metadata
+    }
+}
+
+#[derive(strum::EnumString, strum::Display, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum Platform {
+    #[default]
+    Switch,
+}
+
+impl Platform {
+    pub fn profile(self) -> &'static PlatformProfile {
+        match self {
+            Self::Switch => &PlatformProfile {
+                bundle_dir:      "Switch",
+                sounds_dir:      "StreamingAssets/Sounds",
+                global_metadata: "Managed/Metadata/global-metadata.dat",
+            },
+        }
+    }
+}
+
+static PLATFORM: OnceLock<Platform> = OnceLock::new();
+
+/// Selects the platform whose game files are read, used by the `--platform`
+/// argument. Only the first selection takes effect.
+pub fn select(platform: Platform) {
+    let _ = PLATFORM.set(platform);
+}
+
+/// Profile of the selected platform, Switch if none is selected
+pub fn current() -> &'static PlatformProfile {
+    PLATFORM.get().copied().unwrap_or_default().profile()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_switch_profile() {
+        let profile = Platform::Switch.profile();
+        assert_eq!(profile.share_data(), "StreamingAssets/Switch/share_data");
+        assert_eq!(profile.main_bundle(), "StreamingAssets/Switch/Switch");
+        assert_eq!(
+            profile.score_file("Karisuma"),
+            "StreamingAssets/Switch/share_scores/score_karisuma"
+        );
+        assert_eq!(
+            profile.sound_file("karisuma", "acb"),
+            "StreamingAssets/Sounds/BGM_KARISUMA.acb"
+        );
+    }
+}
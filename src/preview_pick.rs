@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use crate::{ffmpeg_helper, map::SongInfo};
+
+/// Sample rate audio is decoded at for analysis, enough for the loudness of
+/// a song
+const ANALYSIS_SAMPLE_RATE: u32 = 8000;
+/// Length of analyzed window in seconds, about the length of previews in the
+/// song selection
+const PREVIEW_WINDOW: f32 = 15.0;
+/// Granularity of picked preview start in seconds
+const BLOCK_DURATION: f32 = 0.1;
+
+/// Returns the start in seconds of the `window` seconds long section with the
+/// highest energy, which is usually the chorus. 0 is returned if the audio is
+/// shorter than `window`
+pub fn loudest_section(samples: &[i16], sample_rate: u32, window: f32) -> f32 {
+    let block_size = ((sample_rate as f32 * BLOCK_DURATION) as usize).max(1);
+    let block_energies = samples
+        .chunks(block_size)
+        .map(|block| block.iter().map(|s| (*s as f64).powi(2)).sum::<f64>())
+        .collect::<Vec<_>>();
+
+    let window_blocks = (window / BLOCK_DURATION).round() as usize;
+    if window_blocks == 0 || block_energies.len() <= window_blocks {
+        return 0.0;
+    }
+
+    let mut energy = block_energies[..window_blocks].iter().sum::<f64>();
+    let mut loudest = (energy, 0);
+    for start in 1..=block_energies.len() - window_blocks {
+        energy += block_energies[start + window_blocks - 1] - block_energies[start - 1];
+        if energy > loudest.0 {
+            loudest = (energy, start);
+        }
+    }
+
+    loudest.1 as f32 * BLOCK_DURATION
+}
+
+/// Picks preview starting point in milliseconds from the loudest section of the
+/// song audio
+pub fn pick_preview_start(song_info: &SongInfo) -> std::io::Result<u32> {
+    let audio_files = song_info.audio_files().map(Path::new).collect::<Vec<_>>();
+    let samples = ffmpeg_helper::decode_mono(&audio_files, ANALYSIS_SAMPLE_RATE)?;
+    if samples.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Unable to decode {}", song_info.music_file),
+        ));
+    }
+
+    let start = loudest_section(&samples, ANALYSIS_SAMPLE_RATE, PREVIEW_WINDOW);
+    Ok((start * 1000.0).round() as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_loudest_section() {
+        // 10s quiet, 5s loud, 10s quiet at 100Hz
+        let samples = [vec![100i16; 1000], vec![10000i16; 500], vec![100i16; 1000]].concat();
+
+        assert_eq!(loudest_section(&samples, 100, 5.0), 10.0);
+        assert_eq!(loudest_section(&samples, 100, 30.0), 0.0);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use zip::{ZipArchive, ZipWriter, write::FileOptions};
+
+use crate::map::{Map, MapsConfig};
+
+const BUNDLE_CONFIG_NAME: &str = "maps.toml";
+const BUNDLE_AUDIO_DIR: &str = "audio";
+
+/// Exports maps along with all referenced audio files into a zip bundle, the
+/// audio file paths inside the bundled config are relative to the bundle root
+pub fn export_bundle<'a>(
+    maps: impl IntoIterator<Item = &'a Map>,
+    out_path: &Path,
+) -> anyhow::Result<()> {
+    let mut writer = ZipWriter::new(File::create(out_path)?);
+    let options = FileOptions::default();
+
+    // Multiple maps may share one audio file, which is only bundled once
+    let mut bundled_files: HashMap<PathBuf, String> = HashMap::new();
+
+    let mut maps = maps.into_iter().cloned().collect::<Vec<_>>();
+    for map in maps.iter_mut() {
+        let song_info = &mut map.song_info;
+        let audio_files =
+            std::iter::once(&mut song_info.music_file).chain(song_info.intro_file.as_mut());
+
+        for audio_file in audio_files {
+            let file_path = PathBuf::from(&*audio_file);
+            if !file_path.is_file() {
+                continue;
+            }
+
+            if let Some(bundled_name) = bundled_files.get(&file_path) {
+                *audio_file = bundled_name.clone();
+                continue;
+            }
+
+            let file_name = file_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let bundled_name = format!("{BUNDLE_AUDIO_DIR}/{}_{file_name}", bundled_files.len());
+
+            writer.start_file(bundled_name.as_str(), options)?;
+            std::io::copy(&mut File::open(&file_path)?, &mut writer)?;
+
+            *audio_file = bundled_name.clone();
+            bundled_files.insert(file_path, bundled_name);
+        }
+    }
+
+    let maps_config = MapsConfig {
+        maps,
+        ..Default::default()
+    };
+    writer.start_file(BUNDLE_CONFIG_NAME, options)?;
+    writer.write_all(toml::to_string_pretty(&maps_config)?.as_bytes())?;
+
+    writer.finish()?;
+
+    Ok(())
+}
+
+/// Extracts a bundle into `dest_dir` and returns the bundled maps, with music
+/// file paths pointing into the extracted files
+pub fn import_bundle(bundle_path: &Path, dest_dir: &Path) -> anyhow::Result<Vec<Map>> {
+    let mut archive = ZipArchive::new(File::open(bundle_path)?)?;
+    std::fs::create_dir_all(dest_dir)?;
+    archive.extract(dest_dir)?;
+
+    let mut config_path = dest_dir.to_owned();
+    config_path.push(BUNDLE_CONFIG_NAME);
+
+    let maps_config: MapsConfig = toml::from_str(&std::fs::read_to_string(config_path)?)?;
+
+    let maps = maps_config
+        .maps
+        .into_iter()
+        .map(|mut map| {
+            let song_info = &mut map.song_info;
+            let audio_files =
+                std::iter::once(&mut song_info.music_file).chain(song_info.intro_file.as_mut());
+
+            for audio_file in audio_files.filter(|file| !file.is_empty()) {
+                let mut file_path = dest_dir.to_owned();
+                file_path.push(&*audio_file);
+                *audio_file = file_path.to_string_lossy().to_string();
+            }
+            map
+        })
+        .collect();
+
+    Ok(maps)
+}
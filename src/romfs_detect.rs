@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::{app_dirs, doctor};
+
+/// How deep to look below a dump location for RomFS roots, e.g.
+/// `dump/<title ID>/romfs/Data` below the dump folder of emulators
+const DETECT_DEPTH: usize = 4;
+const MAX_RECENT_ROOTS: usize = 5;
+
+/// Emulators dumping RomFS to `dump/<title ID>/romfs` in their data folder
+const EMULATORS: [&str; 5] = ["yuzu", "suyu", "sudachi", "citron", "eden"];
+
+/// RomFS roots used before, most recent first
+#[derive(Default, Serialize, Deserialize)]
+struct RecentRoots {
+    roots: Vec<PathBuf>,
+}
+
+fn recent_roots_path() -> Option<PathBuf> {
+    let mut path = app_dirs::state_dir()?;
+    path.push("romfs_roots.toml");
+    Some(path)
+}
+
+fn recent_roots() -> Vec<PathBuf> {
+    recent_roots_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<RecentRoots>(&content).ok())
+        .map(|recent| recent.roots)
+        .unwrap_or_default()
+}
+
+/// Records `romfs_root` as used for later detection if it has the RomFS
+/// layout
+pub fn remember(romfs_root: &Path) {
+    if !has_romfs_layout(romfs_root) {
+        return;
+    }
+    let Ok(romfs_root) = std::path::absolute(romfs_root) else {
+        return;
+    };
+
+    let mut roots = recent_roots();
+    roots.retain(|root| *root != romfs_root);
+    roots.insert(0, romfs_root);
+    roots.truncate(MAX_RECENT_ROOTS);
+
+    if let Some(path) = recent_roots_path() {
+        let _ = std::fs::create_dir_all(path.parent().unwrap());
+        let _ = std::fs::write(
+            path,
+            toml::to_string_pretty(&RecentRoots { roots }).unwrap(),
+        );
+    }
+}
+
+pub fn has_romfs_layout(dir: &Path) -> bool {
+    dir.is_dir() && doctor::missing_files(dir).is_empty()
+}
+
+/// Folders RomFS dumps are usually put in: dump folders of emulators and
+/// nxdumptool output copied from the SD card into user folders
+fn dump_locations() -> Vec<PathBuf> {
+    let emulator_dumps = dirs::data_dir()
+        .into_iter()
+        .flat_map(|data_dir| EMULATORS.map(|emulator| data_dir.join(emulator).join("dump")));
+
+    let user_dirs = [
+        dirs::home_dir(),
+        dirs::desktop_dir(),
+        dirs::download_dir(),
+        dirs::document_dir(),
+    ];
+    let nxdumptool_outputs = user_dirs
+        .into_iter()
+        .flatten()
+        .flat_map(|dir| [dir.join("nxdumptool"), dir.join("switch/nxdumptool")]);
+
+    emulator_dumps
+        .chain(nxdumptool_outputs)
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Folders with the RomFS layout below `dir`, in path order
+fn find_romfs_roots(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    if has_romfs_layout(dir) {
+        return vec![dir.to_owned()];
+    }
+    if depth == 0 {
+        return vec![];
+    }
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .sorted()
+        .flat_map(|subdir| find_romfs_roots(&subdir, depth - 1))
+        .collect()
+}
+
+/// RomFS roots found on this computer, previously used ones first
+pub fn detect_romfs_roots() -> Vec<PathBuf> {
+    let recent = recent_roots()
+        .into_iter()
+        .filter(|root| has_romfs_layout(root));
+    let found = dump_locations()
+        .into_iter()
+        .flat_map(|location| find_romfs_roots(&location, DETECT_DEPTH));
+
+    recent.chain(found).unique().collect()
+}
+
+/// Lists detected RomFS roots for when a given one lacks game files, `None`
+/// if none is found
+pub fn suggestion() -> Option<String> {
+    let roots = detect_romfs_roots();
+    (!roots.is_empty()).then(|| {
+        format!(
+            "RomFS dumps found on this computer: {}",
+            roots.iter().map(|root| root.display()).join(", ")
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_romfs_roots() {
+        let root = std::env::temp_dir().join("spell_bubble_romfs_detect_test");
+        let _ = std::fs::remove_dir_all(&root);
+
+        let romfs_roots = [
+            root.join("dump/0100E9D00D6C2000/romfs/Data"),
+            root.join("dump/0100E9D00D6C2001/romfs/Data"),
+        ];
+        for romfs_root in &romfs_roots {
+            for file in doctor::required_files() {
+                let path = romfs_root.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, b"data").unwrap();
+            }
+        }
+        std::fs::create_dir_all(root.join("dump/other/romfs")).unwrap();
+
+        assert_eq!(find_romfs_roots(&root, DETECT_DEPTH), romfs_roots);
+        assert!(find_romfs_roots(&root, 2).is_empty());
+        assert_eq!(
+            find_romfs_roots(&romfs_roots[0], 0),
+            [romfs_roots[0].clone()]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
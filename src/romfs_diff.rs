@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    fmt::{Display, Formatter},
+};
+
+use strum::IntoEnumIterator;
+
+use crate::{
+    map::{Difficulty, Map},
+    song_info::{SongInfos, song_name},
+};
+
+/// An official song present in both dumps with different timing or scores
+pub struct ChangedSong {
+    pub id:      String,
+    pub name:    String,
+    pub changes: Vec<String>,
+}
+
+/// Differences of official songs between two game dumps, usually before and
+/// after a game update
+#[derive(Default)]
+pub struct RomfsDiff {
+    /// Songs in "id: title - artist" form
+    pub added:   Vec<String>,
+    /// Songs in "id: title - artist" form
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedSong>,
+}
+
+impl RomfsDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl Display for RomfsDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No songs changed");
+        }
+
+        if !self.added.is_empty() {
+            writeln!(f, "Added songs ({}):", self.added.len())?;
+            for song in &self.added {
+                writeln!(f, "  {song}")?;
+            }
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Removed songs ({}):", self.removed.len())?;
+            for song in &self.removed {
+                writeln!(f, "  {song}")?;
+            }
+        }
+        if !self.changed.is_empty() {
+            writeln!(f, "Changed songs ({}):", self.changed.len())?;
+            for song in &self.changed {
+                writeln!(f, "  {}: {}", song.id, song.name)?;
+                for change in &song.changes {
+                    writeln!(f, "    {change}")?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes the change of `difficulty` score, if it changed
+fn score_change(old: &Map, new: &Map, difficulty: Difficulty) -> Option<String> {
+    let old_score = old.map_scores.get(&difficulty).map(|s| &s.scores.0[..]);
+    let new_score = new.map_scores.get(&difficulty).map(|s| &s.scores.0[..]);
+
+    match (old_score, new_score) {
+        (Some(old_score), Some(new_score)) => {
+            let first_diff = old_score
+                .iter()
+                .zip(new_score)
+                .position(|(a, b)| a != b)
+                .unwrap_or(old_score.len().min(new_score.len()));
+            if first_diff == old_score.len() && first_diff == new_score.len() {
+                None
+            } else {
+                Some(format!(
+                    "{difficulty} score changed from entry {first_diff} ({} -> {} entries)",
+                    old_score.len(),
+                    new_score.len()
+                ))
+            }
+        }
+        (None, Some(_)) => Some(format!("{difficulty} score added")),
+        (Some(_), None) => Some(format!("{difficulty} score removed")),
+        (None, None) => None,
+    }
+}
+
+fn song_changes(old: &Map, new: &Map) -> Vec<String> {
+    let (old_info, new_info) = (&old.song_info, &new.song_info);
+    let mut changes = vec![];
+
+    if old_info.bpm != new_info.bpm {
+        changes.push(format!("BPM {} -> {}", old_info.bpm, new_info.bpm));
+    }
+    if old_info.offset != new_info.offset {
+        changes.push(format!("Offset {} -> {}", old_info.offset, new_info.offset));
+    }
+    if old_info.length != new_info.length {
+        changes.push(format!("Length {} -> {}", old_info.length, new_info.length));
+    }
+    let bpm_changes = |map: &Map| {
+        map.song_info
+            .bpm_changes
+            .as_ref()
+            .map(|changes| changes.0.clone())
+            .unwrap_or_default()
+    };
+    if bpm_changes(old) != bpm_changes(new) {
+        changes.push(format!(
+            "BPM changes {:?} -> {:?}",
+            bpm_changes(old),
+            bpm_changes(new)
+        ));
+    }
+    if old_info.area != new_info.area {
+        changes.push(format!("Area {} -> {}", old_info.area, new_info.area));
+    }
+
+    changes.extend(Difficulty::iter().filter_map(|d| score_change(old, new, d)));
+    changes
+}
+
+/// Compares official songs of two dumps by ID
+pub fn diff_song_infos(old: &SongInfos, new: &SongInfos) -> RomfsDiff {
+    let key = |map: &Map| map.song_info.id.to_string().to_lowercase();
+    let old_maps = old
+        .maps
+        .iter()
+        .map(|map_info| (key(&map_info.map), &map_info.map))
+        .collect::<HashMap<_, _>>();
+    let new_maps = new
+        .maps
+        .iter()
+        .map(|map_info| (key(&map_info.map), &map_info.map))
+        .collect::<HashMap<_, _>>();
+
+    let describe = |map: &Map| format!("{}: {}", map.song_info.id, song_name(&map.song_info));
+    let mut diff = RomfsDiff::default();
+
+    for map_info in &new.maps {
+        let new_map = &map_info.map;
+        match old_maps.get(&key(new_map)) {
+            None => diff.added.push(describe(new_map)),
+            Some(old_map) => {
+                let changes = song_changes(old_map, new_map);
+                if !changes.is_empty() {
+                    diff.changed.push(ChangedSong {
+                        id: new_map.song_info.id.to_string(),
+                        name: song_name(&new_map.song_info),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+    diff.removed = old
+        .maps
+        .iter()
+        .map(|map_info| &map_info.map)
+        .filter(|map| !new_maps.contains_key(&key(map)))
+        .map(describe)
+        .collect();
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::{
+        map::{MapScore, MusicID, ScoreData, SongInfo},
+        song_info::MapInfo,
+    };
+
+    fn map_info(id: &str, bpm: f32, hard_score: &str) -> MapInfo {
+        MapInfo {
+            map:     Map {
+                song_info:  SongInfo {
+                    id: MusicID::New(id.to_owned()),
+                    bpm,
+                    ..Default::default()
+                },
+                map_scores: hashmap! {
+                    Difficulty::Hard => MapScore {
+                        scores: ScoreData::from_str(hard_score).unwrap(),
+                    },
+                },
+            },
+            beat:    String::new(),
+            score_e: String::new(),
+            score_n: String::new(),
+            score_h: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_diff_song_infos() {
+        let old = SongInfos {
+            maps: vec![
+                map_info("Kept", 150.0, "----SO"),
+                map_info("Changed", 150.0, "----SO-O"),
+                map_info("Removed", 150.0, "----SO"),
+            ],
+            dlcs: vec![],
+        };
+        let new = SongInfos {
+            maps: vec![
+                map_info("Kept", 150.0, "----SO"),
+                map_info("Changed", 152.0, "----SS-O-"),
+                map_info("Added", 150.0, "----SO"),
+            ],
+            dlcs: vec![],
+        };
+
+        let diff = diff_song_infos(&old, &new);
+        assert_eq!(diff.added, vec!["Added: ".to_owned()]);
+        assert_eq!(diff.removed, vec!["Removed: ".to_owned()]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].id, "Changed");
+        assert_eq!(
+            diff.changed[0].changes,
+            vec![
+                "BPM 150 -> 152".to_owned(),
+                "Hard score changed from entry 5 (8 -> 9 entries)".to_owned(),
+            ]
+        );
+
+        assert!(diff_song_infos(&old, &old).is_empty());
+    }
+}
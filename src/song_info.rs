@@ -1,13 +1,20 @@
 use std::{
-    ffi::{CStr, CString, c_char},
+    collections::HashMap,
+    ffi::{CStr, c_char},
+    fmt::{Display, Formatter},
     fs::File,
     io::{BufWriter, Write},
     path::Path,
 };
 
+use itertools::Itertools;
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    interop::{ArrayWrapper, StringWrapper},
-    map::{Difficulty::*, Lang::JA},
+    interop::{ArrayWrapper, StringWrapper, path_to_cstring},
+    map::{Difficulty, Difficulty::*, Lang, Lang::JA, ScoreEntry, SongInfo, SongInfoText},
+    platform,
 };
 
 extern "C" {
@@ -16,6 +23,8 @@ extern "C" {
 
 pub struct MapInfo {
     pub map:     crate::map::Map,
+    /// Beat script holding BPM changes and the beat layout
+    pub beat:    String,
     pub score_e: String,
     pub score_n: String,
     pub score_h: String,
@@ -26,10 +35,9 @@ pub struct SongInfos {
     pub dlcs: Vec<String>,
 }
 
-pub fn get_song_info(romfs_root: &Path) -> SongInfos {
-    let mut share_data = romfs_root.to_owned();
-    share_data.push("StreamingAssets/Switch/share_data");
-    let share_data_path = CString::new(share_data.to_string_lossy().as_ref()).unwrap();
+/// Names of DLCs in a share_data file, in DLC ID order starting from 1
+pub fn dlc_names(share_data: &Path) -> std::io::Result<Vec<String>> {
+    let share_data_path = path_to_cstring(share_data)?;
 
     let dlcs = unsafe {
         let arr = get_dlc_list(share_data_path.as_ptr());
@@ -43,81 +51,680 @@ pub fn get_song_info(romfs_root: &Path) -> SongInfos {
             .collect::<Vec<_>>()
     };
 
-    let maps = crate::map::get_song_info(romfs_root);
+    Ok(dlcs)
+}
+
+pub fn get_song_info(romfs_root: &Path) -> std::io::Result<SongInfos> {
+    let dlcs = dlc_names(&romfs_root.join(platform::current().share_data()))?;
+    let maps = crate::map::get_song_info(romfs_root)?;
 
     let maps = maps
         .into_iter()
-        .map(|(map, level_e, level_n, level_h)| MapInfo {
+        .map(|(map, beat, level_e, level_n, level_h)| MapInfo {
             map,
+            beat,
             score_e: level_e,
             score_n: level_n,
             score_h: level_h,
         })
         .collect();
 
-    SongInfos { maps, dlcs }
+    Ok(SongInfos { maps, dlcs })
 }
 
-pub fn write_song_info_csv(infos: SongInfos, out_path: &Path) {
-    let mut writer = BufWriter::new(File::create(out_path).unwrap());
-    if cfg!(windows) {
-        // Write BOM for Windows programs to recognize encoding
-        writer.write_all(&[0xEF, 0xBB, 0xBF]).unwrap();
-    }
-    let mut writer = csv::Writer::from_writer(writer);
-
-    writer
-        .write_record([
-            "ID",
-            "Title",
-            "Artist",
-            "Original",
-            "Effective BPM",
-            "Has Tempo Changes",
-            "Levels - Easy",
-            "Levels - Normal",
-            "Levels - Hard",
-            "Length",
-            "Area",
-            "DLC",
-        ])
-        .unwrap();
-
+/// Names of official songs in "title - artist" form keyed by lowercase music
+/// ID, used for showing which song a replacement map overwrites
+pub fn official_song_names(infos: &SongInfos) -> HashMap<String, String> {
     infos
         .maps
         .iter()
         .map(|map_info| {
             let song_info = &map_info.map.song_info;
-            let info_text = song_info.info_text.get(&JA).unwrap();
-            writer.write_record(&[
-                song_info.id.to_string(),
-                info_text.title(),
-                info_text.artist(),
-                info_text.original(),
-                map_info.map.effective_bpm().to_string(),
-                song_info.is_bpm_change().to_string(),
-                map_info
-                    .map
-                    .level(Easy, Some(&map_info.score_e))
-                    .to_string(),
-                map_info
-                    .map
-                    .level(Normal, Some(&map_info.score_n))
-                    .to_string(),
+            (
+                song_info.id.to_string().to_lowercase(),
+                song_name(song_info),
+            )
+        })
+        .collect()
+}
+
+pub fn song_name(song_info: &SongInfo) -> String {
+    song_info
+        .info_text
+        .get(&JA)
+        .or_else(|| song_info.info_text.values().next())
+        .map(|text| format!("{} - {}", text.title(), text.artist()))
+        .unwrap_or_default()
+}
+
+/// A DLC and the songs it contains
+pub struct DlcInfo {
+    /// DLC ID, as accepted by `UnlockFeatures --exclude`
+    pub index: u16,
+    pub name:  String,
+    /// Songs in "id: title - artist" form
+    pub songs: Vec<String>,
+}
+
+/// DLCs in index order, base game songs are not included
+pub fn dlc_list(infos: &SongInfos) -> Vec<DlcInfo> {
+    infos
+        .dlcs
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let index = i as u16 + 1;
+            let songs = infos
+                .maps
+                .iter()
+                .map(|map_info| &map_info.map.song_info)
+                .filter(|song_info| song_info.dlc_index == index)
+                .map(|song_info| format!("{}: {}", song_info.id, song_name(song_info)))
+                .collect();
+
+            DlcInfo {
+                index,
+                name: name.clone(),
+                songs,
+            }
+        })
+        .collect()
+}
+
+/// Options controlling the layout of exported song information CSV
+#[derive(Clone)]
+pub struct CsvOptions {
+    /// Languages to write title, artist and original columns for
+    pub langs:       Vec<Lang>,
+    /// Excel compatible output: BOM, CRLF line endings and quoted text fields
+    pub excel:       bool,
+    /// Use semicolon as delimiter, for regional Excel installs using comma as
+    /// decimal separator
+    pub semicolon:   bool,
+    /// Write note count, heavy note count and max NPS columns of each
+    /// difficulty
+    pub score_stats: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        Self {
+            langs:       vec![JA],
+            excel:       false,
+            semicolon:   false,
+            score_stats: false,
+        }
+    }
+}
+
+pub fn song_info_header(langs: &[Lang], score_stats: bool) -> Vec<String> {
+    let text_columns = langs.iter().flat_map(|lang| {
+        ["Title", "Artist", "Original"]
+            .into_iter()
+            .map(move |column| format!("{column} ({lang})"))
+    });
+
+    std::iter::once("ID".to_owned())
+        .chain(text_columns)
+        .chain(
+            [
+                "Effective BPM",
+                "Has Tempo Changes",
+                "Levels - Easy",
+                "Levels - Normal",
+                "Levels - Hard",
+                "Length",
+                "Area",
+                "DLC",
+            ]
+            .into_iter()
+            .map(str::to_owned),
+        )
+        .chain(score_stats.then(score_stats_header).into_iter().flatten())
+        .collect()
+}
+
+fn score_stats_header() -> impl Iterator<Item = String> {
+    [Easy, Normal, Hard].into_iter().flat_map(|difficulty| {
+        ["Notes", "Heavy Notes", "Max NPS"]
+            .into_iter()
+            .map(move |column| format!("{column} - {difficulty}"))
+    })
+}
+
+/// A typed cell of song information, used to keep numeric columns numeric in
+/// spreadsheet outputs
+pub enum SongInfoCell {
+    Text(String),
+    Float(f32),
+    Int(u32),
+    Bool(bool),
+}
+
+impl Display for SongInfoCell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Text(s) => write!(f, "{s}"),
+            Self::Float(n) => write!(f, "{n}"),
+            Self::Int(n) => write!(f, "{n}"),
+            Self::Bool(b) => write!(f, "{b}"),
+        }
+    }
+}
+
+pub fn song_info_cells(
+    map_info: &MapInfo,
+    dlcs: &[String],
+    langs: &[Lang],
+    score_stats: bool,
+) -> Vec<SongInfoCell> {
+    let song_info = &map_info.map.song_info;
+    let default_text = SongInfoText::default();
+
+    let text_columns = langs.iter().flat_map(|lang| {
+        let info_text = song_info.info_text.get(lang).unwrap_or(&default_text);
+        [info_text.title(), info_text.artist(), info_text.original()].map(SongInfoCell::Text)
+    });
+
+    std::iter::once(SongInfoCell::Text(song_info.id.to_string()))
+        .chain(text_columns)
+        .chain([
+            SongInfoCell::Float(map_info.map.effective_bpm()),
+            SongInfoCell::Bool(song_info.is_bpm_change()),
+            SongInfoCell::Int(map_info.level(Easy)),
+            SongInfoCell::Int(map_info.level(Normal)),
+            SongInfoCell::Int(map_info.level(Hard)),
+            SongInfoCell::Int(song_info.length as u32),
+            SongInfoCell::Text(song_info.area.to_string()),
+            SongInfoCell::Text(dlc_name(song_info, dlcs)),
+        ])
+        .chain(
+            score_stats
+                .then(|| map_info.score_stats_cells())
+                .into_iter()
+                .flatten(),
+        )
+        .collect()
+}
+
+impl MapInfo {
+    fn level(&self, difficulty: Difficulty) -> u32 {
+        let score = match difficulty {
+            Easy => &self.score_e,
+            Normal => &self.score_n,
+            Hard => &self.score_h,
+        };
+        self.map.level(difficulty, Some(score)) as u32
+    }
+
+    fn note_count(&self, difficulty: Difficulty) -> u32 {
+        self.map
+            .map_scores
+            .get(&difficulty)
+            .map(|score| {
+                score
+                    .scores
+                    .0
+                    .iter()
+                    .filter(|entry| **entry != ScoreEntry::B)
+                    .count() as u32
+            })
+            .unwrap_or_default()
+    }
+
+    fn heavy_note_count(&self, difficulty: Difficulty) -> u32 {
+        self.map
+            .map_scores
+            .get(&difficulty)
+            .map(|score| {
+                score
+                    .scores
+                    .0
+                    .iter()
+                    .filter(|entry| **entry == ScoreEntry::S)
+                    .count() as u32
+            })
+            .unwrap_or_default()
+    }
+
+    /// Notes per second in the densest few seconds of the chart, 0 for charts
+    /// with too few notes to measure
+    fn max_nps(&self, difficulty: Difficulty) -> f32 {
+        self.map
+            .chart_stats(difficulty)
+            .map(|stats| stats.peak_density)
+            .unwrap_or_default()
+    }
+
+    fn score_stats_cells(&self) -> Vec<SongInfoCell> {
+        [Easy, Normal, Hard]
+            .into_iter()
+            .flat_map(|difficulty| {
+                [
+                    SongInfoCell::Int(self.note_count(difficulty)),
+                    SongInfoCell::Int(self.heavy_note_count(difficulty)),
+                    SongInfoCell::Float(self.max_nps(difficulty)),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Beat script and score scripts of all difficulties of a song, each after a
+/// `[name]` header line, as written by DumpScores
+pub fn score_scripts_text(map_info: &MapInfo) -> String {
+    [
+        ("beat", &map_info.beat),
+        ("easy", &map_info.score_e),
+        ("normal", &map_info.score_n),
+        ("hard", &map_info.score_h),
+    ]
+    .iter()
+    .map(|(name, script)| format!("[{name}]\n{}\n", script.trim_end()))
+    .join("\n")
+}
+
+fn dlc_name(song_info: &SongInfo, dlcs: &[String]) -> String {
+    if song_info.dlc_index == 0 {
+        "本体".to_owned()
+    } else {
+        dlcs[song_info.dlc_index as usize - 1].clone()
+    }
+}
+
+/// Aggregate song counts shown under the song information table
+pub struct SongSummary {
+    pub total: usize,
+    /// Song counts of the base game and each DLC, in DLC order
+    pub dlcs:  Vec<(String, usize)>,
+    /// Song counts of each area, in name order
+    pub areas: Vec<(String, usize)>,
+}
+
+pub fn song_summary(infos: &SongInfos) -> SongSummary {
+    let song_infos = infos.maps.iter().map(|map_info| &map_info.map.song_info);
+
+    let dlcs = song_infos
+        .clone()
+        .sorted_by_key(|song_info| song_info.dlc_index)
+        .dedup_by_with_count(|a, b| a.dlc_index == b.dlc_index)
+        .map(|(count, song_info)| (dlc_name(song_info, &infos.dlcs), count))
+        .collect();
+
+    let areas = song_infos
+        .map(|song_info| song_info.area.to_string())
+        .sorted()
+        .dedup_with_count()
+        .map(|(count, area)| (area, count))
+        .collect();
+
+    SongSummary {
+        total: infos.maps.len(),
+        dlcs,
+        areas,
+    }
+}
+
+/// Columns available in the song information view, the GUI lists column names
+/// in the same order
+#[derive(Serialize, Deserialize, strum::EnumIter, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SongInfoColumn {
+    Id,
+    Title,
+    TitleKana,
+    SubTitle,
+    Artist,
+    ArtistKana,
+    Original,
+    EffectiveBpm,
+    TempoChanges,
+    LevelEasy,
+    LevelNormal,
+    LevelHard,
+    NotesEasy,
+    NotesNormal,
+    NotesHard,
+    Length,
+    Area,
+    Dlc,
+    DlcIndex,
+}
+
+impl SongInfoColumn {
+    /// Columns shown when none are chosen
+    pub const DEFAULT: [Self; 12] = [
+        Self::Id,
+        Self::Title,
+        Self::Artist,
+        Self::Original,
+        Self::EffectiveBpm,
+        Self::TempoChanges,
+        Self::LevelEasy,
+        Self::LevelNormal,
+        Self::LevelHard,
+        Self::Length,
+        Self::Area,
+        Self::Dlc,
+    ];
+
+    pub fn cell(&self, map_info: &MapInfo, dlcs: &[String], lang: &Lang) -> SongInfoCell {
+        let song_info = &map_info.map.song_info;
+        let default_text = SongInfoText::default();
+        let info_text = song_info.info_text.get(lang).unwrap_or(&default_text);
+
+        match self {
+            Self::Id => SongInfoCell::Text(song_info.id.to_string()),
+            Self::Title => SongInfoCell::Text(info_text.title()),
+            Self::TitleKana => SongInfoCell::Text(info_text.title_kana.clone()),
+            Self::SubTitle => SongInfoCell::Text(info_text.sub_title.clone()),
+            Self::Artist => SongInfoCell::Text(info_text.artist()),
+            Self::ArtistKana => SongInfoCell::Text(info_text.artist_kana.clone()),
+            Self::Original => SongInfoCell::Text(info_text.original()),
+            Self::EffectiveBpm => SongInfoCell::Float(map_info.map.effective_bpm()),
+            Self::TempoChanges => SongInfoCell::Bool(song_info.is_bpm_change()),
+            Self::LevelEasy => SongInfoCell::Int(map_info.level(Easy)),
+            Self::LevelNormal => SongInfoCell::Int(map_info.level(Normal)),
+            Self::LevelHard => SongInfoCell::Int(map_info.level(Hard)),
+            Self::NotesEasy => SongInfoCell::Int(map_info.note_count(Easy)),
+            Self::NotesNormal => SongInfoCell::Int(map_info.note_count(Normal)),
+            Self::NotesHard => SongInfoCell::Int(map_info.note_count(Hard)),
+            Self::Length => SongInfoCell::Int(song_info.length as u32),
+            Self::Area => SongInfoCell::Text(song_info.area.to_string()),
+            Self::Dlc => SongInfoCell::Text(dlc_name(song_info, dlcs)),
+            Self::DlcIndex => SongInfoCell::Int(song_info.dlc_index as u32),
+        }
+    }
+}
+
+/// Row of the song information view with only the chosen columns
+pub fn song_info_view_record(
+    map_info: &MapInfo,
+    dlcs: &[String],
+    lang: &Lang,
+    columns: &[SongInfoColumn],
+) -> Vec<String> {
+    columns
+        .iter()
+        .map(|column| column.cell(map_info, dlcs, lang).to_string())
+        .collect()
+}
+
+pub fn song_info_record(
+    map_info: &MapInfo,
+    dlcs: &[String],
+    langs: &[Lang],
+    score_stats: bool,
+) -> Vec<String> {
+    song_info_cells(map_info, dlcs, langs, score_stats)
+        .into_iter()
+        .map(|cell| cell.to_string())
+        .collect()
+}
+
+pub fn write_song_info_csv(
+    infos: &SongInfos,
+    out_path: &Path,
+    options: &CsvOptions,
+) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(out_path)?);
+    if cfg!(windows) || options.excel {
+        // Write BOM for Windows programs to recognize encoding
+        writer.write_all(&[0xEF, 0xBB, 0xBF])?;
+    }
+
+    let mut builder = csv::WriterBuilder::new();
+    if options.semicolon {
+        builder.delimiter(b';');
+    }
+    if options.excel {
+        // Excel guesses the type of unquoted fields and mangles CJK text and IDs
+        // looking like numbers or dates, so every non-numeric field is quoted
+        builder
+            .terminator(csv::Terminator::CRLF)
+            .quote_style(csv::QuoteStyle::NonNumeric);
+    }
+    let mut writer = builder.from_writer(writer);
+
+    writer.write_record(song_info_header(&options.langs, options.score_stats))?;
+
+    for map_info in infos.maps.iter() {
+        writer.write_record(song_info_record(
+            map_info,
+            &infos.dlcs,
+            &options.langs,
+            options.score_stats,
+        ))?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+pub fn write_song_info_xlsx(
+    infos: &SongInfos,
+    out_path: &Path,
+    langs: &[Lang],
+    score_stats: bool,
+) -> anyhow::Result<()> {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+
+    let header_format = Format::new().set_bold();
+    let header = song_info_header(langs, score_stats);
+    for (col, title) in header.iter().enumerate() {
+        worksheet.write_string_with_format(0, col as u16, title, &header_format)?;
+    }
+
+    for (i, map_info) in infos.maps.iter().enumerate() {
+        let row = i as u32 + 1;
+
+        for (col, cell) in song_info_cells(map_info, &infos.dlcs, langs, score_stats)
+            .into_iter()
+            .enumerate()
+        {
+            let col = col as u16;
+            match cell {
+                SongInfoCell::Text(s) => worksheet.write_string(row, col, s)?,
+                // Going through the shortest decimal representation of f32 avoids values like
+                // 143.3333282470703 showing up in cells
+                SongInfoCell::Float(n) => {
+                    worksheet.write_number(row, col, n.to_string().parse::<f64>()?)?
+                }
+                SongInfoCell::Int(n) => worksheet.write_number(row, col, n)?,
+                SongInfoCell::Bool(b) => worksheet.write_boolean(row, col, b)?,
+            };
+        }
+    }
+
+    worksheet.set_freeze_panes(1, 0)?;
+    worksheet.autofilter(0, 0, infos.maps.len() as u32, header.len() as u16 - 1)?;
+    worksheet.autofit();
+
+    workbook.save(out_path)?;
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct SongInfosJson<'a> {
+    dlcs:  &'a [String],
+    songs: Vec<SongJson<'a>>,
+}
+
+#[derive(Serialize)]
+struct SongJson<'a> {
+    #[serde(flatten)]
+    map:         &'a crate::map::Map,
+    /// 0 for base game songs, otherwise 1-based index into `dlcs`
+    dlc_index:   u16,
+    dlc:         String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score_stats: Option<HashMap<Difficulty, ScoreStatsJson>>,
+}
+
+#[derive(Serialize)]
+struct ScoreStatsJson {
+    notes:       u32,
+    heavy_notes: u32,
+    max_nps:     f32,
+}
+
+/// Writes all information of songs as JSON: song info in all languages, scores
+/// of all difficulties and BPM changes, in the same form as map config files.
+/// With `score_stats`, note counts and max NPS of each difficulty are added.
+pub fn write_song_info_json(
+    infos: &SongInfos,
+    out_path: &Path,
+    score_stats: bool,
+) -> anyhow::Result<()> {
+    let songs = infos
+        .maps
+        .iter()
+        .map(|map_info| SongJson {
+            map:         &map_info.map,
+            dlc_index:   map_info.map.song_info.dlc_index,
+            dlc:         dlc_name(&map_info.map.song_info, &infos.dlcs),
+            score_stats: score_stats.then(|| {
                 map_info
                     .map
-                    .level(Hard, Some(&map_info.score_h))
-                    .to_string(),
-                song_info.length.to_string(),
-                song_info.area.to_string(),
-                if song_info.dlc_index == 0 {
-                    "本体"
-                } else {
-                    &infos.dlcs[song_info.dlc_index as usize - 1]
-                }
-                .to_string(),
-            ])
+                    .map_scores
+                    .keys()
+                    .map(|difficulty| {
+                        let stats = ScoreStatsJson {
+                            notes:       map_info.note_count(*difficulty),
+                            heavy_notes: map_info.heavy_note_count(*difficulty),
+                            max_nps:     map_info.max_nps(*difficulty),
+                        };
+                        (*difficulty, stats)
+                    })
+                    .collect()
+            }),
         })
-        .collect::<Result<Vec<_>, _>>()
-        .unwrap();
+        .collect();
+    let json = SongInfosJson {
+        dlcs: &infos.dlcs,
+        songs,
+    };
+
+    let writer = BufWriter::new(File::create(out_path)?);
+    serde_json::to_writer_pretty(writer, &json)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Read, str::FromStr};
+
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::map::{Area, Map, MapScore, MusicID, ScoreData};
+
+    fn song_infos() -> SongInfos {
+        let map = Map {
+            song_info:  SongInfo {
+                id: MusicID::New("Newly".to_owned()),
+                bpm: 120.0,
+                length: 4,
+                // The default area is not displayable
+                area: Area::KoumaKan,
+                info_text: hashmap! {
+                    JA => SongInfoText {
+                        title: "新しい曲".to_owned(),
+                        artist: "Artist, \"Name\"".to_owned(),
+                        ..Default::default()
+                    },
+                },
+                ..Default::default()
+            },
+            map_scores: hashmap! {
+                Hard => MapScore {
+                    scores: ScoreData::from_str("OO-S").unwrap(),
+                },
+            },
+        };
+
+        SongInfos {
+            maps: vec![MapInfo {
+                map,
+                beat: String::new(),
+                score_e: String::new(),
+                score_n: String::new(),
+                score_h: String::new(),
+            }],
+            dlcs: vec![],
+        }
+    }
+
+    fn read_csv(path: &Path, delimiter: u8) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(delimiter)
+            .from_path(path)
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_owned).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_write_song_info_csv() {
+        let infos = song_infos();
+        let path = std::env::temp_dir().join("spell_bubble_song_info_test.csv");
+        let expected = vec![
+            song_info_header(&[JA], false),
+            song_info_record(&infos.maps[0], &infos.dlcs, &[JA], false),
+        ];
+
+        write_song_info_csv(&infos, &path, &CsvOptions::default()).unwrap();
+        assert_eq!(read_csv(&path, b','), expected);
+
+        let options = CsvOptions {
+            excel: true,
+            semicolon: true,
+            ..Default::default()
+        };
+        write_song_info_csv(&infos, &path, &options).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        assert!(content.starts_with(&[0xEF, 0xBB, 0xBF]));
+        assert!(content.ends_with(b"\r\n"));
+        assert_eq!(read_csv(&path, b';'), expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_write_song_info_xlsx() {
+        let infos = song_infos();
+        let path = std::env::temp_dir().join("spell_bubble_song_info_test.xlsx");
+
+        write_song_info_xlsx(&infos, &path, &[JA], true).unwrap();
+
+        let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut read_entry = |name: &str| {
+            let mut content = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut content)
+                .unwrap();
+            content
+        };
+
+        let strings = read_entry("xl/sharedStrings.xml");
+        for title in song_info_header(&[JA], true) {
+            assert!(strings.contains(&title), "{title} missing in header");
+        }
+        assert!(strings.contains("新しい曲"));
+        assert!(strings.contains("Artist, "));
+
+        let sheet = read_entry("xl/worksheets/sheet1.xml");
+        assert!(sheet.contains("<row r=\"2\""));
+        assert!(!sheet.contains("<row r=\"3\""));
+
+        std::fs::remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,117 @@
+use std::{
+    fmt::{Display, Formatter},
+    path::Path,
+};
+
+use crate::{
+    map::{self, Map, SongInfo},
+    platform,
+};
+
+/// Rough encoded HCA size per second of audio, about a quarter of 48kHz 16-bit
+/// stereo PCM
+const HCA_BYTES_PER_SECOND: u64 = 48_000;
+
+#[derive(Debug)]
+pub struct InsufficientSpaceError {
+    pub required:  u64,
+    pub available: u64,
+}
+
+impl Display for InsufficientSpaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Not enough free space on the output volume: about {} required, {} available, free up \
+             at least {} before generating",
+            format_size(self.required),
+            format_size(self.available),
+            format_size(self.required - self.available)
+        )
+    }
+}
+
+impl std::error::Error for InsufficientSpaceError {}
+
+fn format_size(bytes: u64) -> String {
+    format!("{:.1} MiB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or_default()
+}
+
+fn romfs_file_size(romfs_root: &Path, path: &str) -> u64 {
+    let mut file_path = romfs_root.to_owned();
+    file_path.push(path);
+    file_size(&file_path)
+}
+
+/// Estimated size of the encoded audio, falls back to the size of audio files
+/// if their duration cannot be read
+fn audio_size(song_info: &SongInfo) -> u64 {
+    match song_info.audio_duration() {
+        Ok(duration) => (duration.max(0.0) as f64 * HCA_BYTES_PER_SECOND as f64) as u64,
+        Err(_) => song_info
+            .audio_files()
+            .map(|file| file_size(Path::new(file)))
+            .sum(),
+    }
+}
+
+/// Estimates the size of generated files, which are mostly copies of game files
+/// and encoded audio
+pub fn estimate_output_size(romfs_root: &Path, maps: &[Map], romfs_only: bool) -> u64 {
+    let platform = platform::current();
+    let share_data = romfs_file_size(romfs_root, &platform.share_data());
+    let donor = map::score_donor(romfs_root).ok();
+    let acb = donor
+        .as_ref()
+        .map(|donor| romfs_file_size(romfs_root, &platform.sound_file(donor, "acb")))
+        .unwrap_or_default();
+
+    let maps_size = maps
+        .iter()
+        .map(|map| {
+            let score_size = match (romfs_only, &donor) {
+                (true, _) => romfs_file_size(
+                    romfs_root,
+                    &platform.score_file(&map.song_info.id.to_string()),
+                ),
+                (false, Some(donor)) => romfs_file_size(romfs_root, &platform.score_file(donor)),
+                (false, None) => 0,
+            };
+
+            acb + score_size + audio_size(&map.song_info)
+        })
+        .sum::<u64>();
+
+    let exefs_size = if romfs_only {
+        0
+    } else {
+        romfs_file_size(romfs_root, platform.global_metadata())
+            + romfs_file_size(romfs_root, &platform.main_bundle())
+    };
+
+    share_data + maps_size + exefs_size
+}
+
+/// Checks if the volume of `out_dir` has `required` bytes available
+pub fn check_free_space(out_dir: &Path, required: u64) -> anyhow::Result<()> {
+    // The output directory may not be created yet
+    let existing_dir = std::path::absolute(out_dir)?
+        .ancestors()
+        .find(|dir| dir.exists())
+        .map(|dir| dir.to_owned())
+        .ok_or(anyhow::anyhow!("Invalid output path"))?;
+
+    let available = fs4::available_space(existing_dir)?;
+    if available < required {
+        Err(InsufficientSpaceError {
+            required,
+            available,
+        })?
+    }
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::path::{Path, PathBuf};
+
+/// A temporary directory beside the output directory, generated files are
+/// written here first and only moved into the output directory after all of
+/// them are generated successfully. The staging directory is removed if it is
+/// dropped without being committed, leaving the output directory untouched.
+pub struct StagingDir {
+    staging_dir: PathBuf,
+    out_dir:     PathBuf,
+    committed:   bool,
+}
+
+impl StagingDir {
+    pub fn new(out_dir: &Path) -> std::io::Result<Self> {
+        let out_dir = std::path::absolute(out_dir)?;
+        let name = out_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let staging_dir = out_dir.with_file_name(format!(".{name}.staging"));
+
+        // Leftover from an interrupted run
+        if staging_dir.exists() {
+            std::fs::remove_dir_all(&staging_dir)?;
+        }
+        std::fs::create_dir_all(&staging_dir)?;
+
+        Ok(Self {
+            staging_dir,
+            out_dir,
+            committed: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.staging_dir
+    }
+
+    /// Moves all staged files into the output directory, replacing existing
+    /// ones. Files are renamed within the same volume, so this is fast and
+    /// unlikely to fail halfway.
+    pub fn commit(mut self) -> std::io::Result<()> {
+        self.committed = true;
+        move_tree(&self.staging_dir, &self.out_dir)?;
+        std::fs::remove_dir_all(&self.staging_dir)
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_dir_all(&self.staging_dir);
+        }
+    }
+}
+
+fn move_tree(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let mut target = to.to_owned();
+        target.push(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            move_tree(&entry.path(), &target)?;
+        } else {
+            std::fs::rename(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_staging_dir() {
+        let mut out_dir = std::env::temp_dir();
+        out_dir.push(format!("staging_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        let mut kept_file = out_dir.clone();
+        kept_file.push("kept");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        std::fs::write(&kept_file, "kept").unwrap();
+
+        let staging = StagingDir::new(&out_dir).unwrap();
+        let mut staged_file = staging.path().to_owned();
+        staged_file.push("sub/new");
+        std::fs::create_dir_all(staged_file.parent().unwrap()).unwrap();
+        std::fs::write(&staged_file, "new").unwrap();
+        let staging_path = staging.path().to_owned();
+        drop(staging);
+
+        assert!(!staging_path.exists());
+        assert!(!out_dir.join("sub").exists());
+
+        let staging = StagingDir::new(&out_dir).unwrap();
+        let mut staged_file = staging.path().to_owned();
+        staged_file.push("sub/new");
+        std::fs::create_dir_all(staged_file.parent().unwrap()).unwrap();
+        std::fs::write(&staged_file, "new").unwrap();
+        staging.commit().unwrap();
+
+        assert!(!staging_path.exists());
+        assert_eq!(
+            std::fs::read_to_string(out_dir.join("sub/new")).unwrap(),
+            "new"
+        );
+        assert_eq!(std::fs::read_to_string(&kept_file).unwrap(), "kept");
+
+        std::fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -1,25 +1,48 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     cmp::Ordering,
     collections::HashMap,
-    fs::File,
-    io::{BufWriter, Write},
+    iter::zip,
     path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
+use anyhow::Context;
 use itertools::Itertools;
-use maplit::hashmap;
+use lazy_model::LazyRowModel;
 use rust_decimal::prelude::ToPrimitive;
-use slint::{Model, ModelRc, SharedString, StandardListViewItem, VecModel};
+use serde::{Deserialize, Serialize};
+use slint::{Model, ModelRc, SharedString, StandardListViewItem, TableColumn, VecModel, Weak};
+use strum::IntoEnumIterator;
 
 use crate::{
-    exefs,
-    map::{Area, BpmChanges, Difficulty::*, Lang, Lang::*, Map, MusicID, SongInfo, SongInfoText},
-    song_info::get_song_info,
+    app_dirs, dlc_musics, exefs, fs_check,
+    input_check::{self, InputHashes},
+    map::{
+        Area, BpmChanges, DEFAULT_SNAP_TOLERANCE, Difficulty, Difficulty::*, Lang, Lang::*, Map,
+        MapStatus, MusicID, PatchProgress, SongInfo, SongInfoText, SongPhase, field_diff,
+        format_timestamp, parse_timestamp, snap_bpms,
+    },
+    mod_merge::ModManifest,
+    mod_package, output_report,
+    output_sink::{DirSink, OutputSink},
+    preview_pick, project_bundle, romfs_detect,
+    song_info::{
+        CsvOptions, SongInfoColumn, SongInfos, get_song_info, official_song_names,
+        song_info_view_record, song_summary, write_song_info_csv, write_song_info_xlsx,
+    },
+    space_check,
+    staging::StagingDir,
 };
 
+mod lazy_model;
+
 slint::include_modules!();
 
 pub fn start_gui() -> anyhow::Result<()> {
@@ -27,9 +50,16 @@ pub fn start_gui() -> anyhow::Result<()> {
 
     let main_window = MainWindow::new()?;
     main_window.on_prompt_get_path(|| {
-        let path = rfd::FileDialog::new()
-            .set_title("Select root of dumped RomFS (the Data folder)")
-            .pick_folder();
+        let mut dialog =
+            rfd::FileDialog::new().set_title("Select root of dumped RomFS (the Data folder)");
+        // Starts beside a detected dump so that it only needs to be selected
+        if let Some(parent) = romfs_detect::detect_romfs_roots()
+            .first()
+            .and_then(|root| root.parent())
+        {
+            dialog = dialog.set_directory(parent);
+        }
+        let path = dialog.pick_folder();
         let path = path
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default();
@@ -38,7 +68,7 @@ pub fn start_gui() -> anyhow::Result<()> {
 
     init_utilities(&main_window);
     init_song_info_adapter(&main_window);
-    init_custom_map_adapter(&main_window);
+    let _maps_watcher = init_custom_map_adapter(&main_window);
     init_custom_map_model(&main_window);
 
     main_window.run()?;
@@ -53,12 +83,62 @@ fn init_utilities(main_window: &MainWindow) {
     main_window
         .global::<Utilities>()
         .on_length(|str| str.len() as i32);
+
+    main_window
+        .global::<Utilities>()
+        .on_copy_table(|rows, columns, row, column| {
+            let text = table_text(&rows, &columns, row, column);
+            let result: anyhow::Result<()> = try { arboard::Clipboard::new()?.set_text(text)? };
+            if let Err(e) = result {
+                rfd::MessageDialog::new()
+                    .set_level(rfd::MessageLevel::Error)
+                    .set_title("Unable to copy")
+                    .set_description(e.to_string())
+                    .show();
+            }
+        });
+}
+
+/// Tab separated text of table content for pasting into spreadsheets, `row`
+/// -1 for all rows with the header and `column` -1 for whole rows
+fn table_text(
+    rows: &ModelRc<ModelRc<StandardListViewItem>>,
+    columns: &ModelRc<TableColumn>,
+    row: i32,
+    column: i32,
+) -> String {
+    let cell = |text: &str| text.replace(['\t', '\n', '\r'], " ");
+    let row_text =
+        |items: ModelRc<StandardListViewItem>| items.iter().map(|item| cell(&item.text)).join("\t");
+
+    match (usize::try_from(row), usize::try_from(column)) {
+        (Ok(row), Ok(column)) => rows
+            .row_data(row)
+            .and_then(|items| items.row_data(column))
+            .map(|item| item.text.to_string())
+            .unwrap_or_default(),
+        (Ok(row), Err(_)) => rows.row_data(row).map(row_text).unwrap_or_default(),
+        (Err(_), _) => {
+            let header = columns.iter().map(|column| cell(&column.title)).join("\t");
+            std::iter::once(header)
+                .chain(rows.iter().map(row_text))
+                .join("\n")
+        }
+    }
 }
 
 fn init_song_info_adapter(main_window: &MainWindow) {
     let main_window = main_window.as_weak();
 
-    let row_data = Rc::new(VecModel::default());
+    let row_data = Rc::new(LazyRowModel::default());
+    let song_infos: Rc<RefCell<Option<Rc<SongInfos>>>> = Rc::new(RefCell::new(None));
+    // RomFS root `song_infos` is read from, switching languages or columns
+    // reuses them instead of reading RomFS again
+    let loaded_path = Rc::new(RefCell::new(String::new()));
+    let columns = Rc::new(RefCell::new(load_song_info_columns()));
+    let current_lang_id = Rc::new(Cell::new(0));
+
+    update_song_info_columns(&main_window, &columns.borrow());
 
     main_window
         .unwrap()
@@ -66,8 +146,13 @@ fn init_song_info_adapter(main_window: &MainWindow) {
         .on_load_data({
             let main_window = main_window.clone();
             let row_data = row_data.clone();
+            let song_infos = song_infos.clone();
+            let loaded_path = loaded_path.clone();
+            let columns = columns.clone();
+            let current_lang_id = current_lang_id.clone();
             move |lang_id| {
                 let row_data = row_data.clone();
+                current_lang_id.set(lang_id);
 
                 let lang = match lang_id {
                     0 => JA,
@@ -83,58 +168,43 @@ fn init_song_info_adapter(main_window: &MainWindow) {
                     return;
                 }
 
-                let romfs_root = Path::new(path.as_str());
-                let infos = get_song_info(romfs_root);
-
-                let row_models = infos
-                    .maps
-                    .into_iter()
-                    .map(|map_info| {
-                        let song_info = &map_info.map.song_info;
-                        let info_text = song_info.info_text.get(&lang).unwrap();
-
-                        let row_items = [
-                            song_info.id.to_string(),
-                            info_text.title(),
-                            info_text.artist(),
-                            info_text.original(),
-                            map_info.map.effective_bpm().to_string(),
-                            song_info.is_bpm_change().to_string(),
-                            map_info
-                                .map
-                                .level(Easy, Some(&map_info.score_e))
-                                .to_string(),
-                            map_info
-                                .map
-                                .level(Normal, Some(&map_info.score_n))
-                                .to_string(),
-                            map_info
-                                .map
-                                .level(Hard, Some(&map_info.score_h))
-                                .to_string(),
-                            song_info.length.to_string(),
-                            song_info.area.to_string(),
-                            if song_info.dlc_index == 0 {
-                                "本体"
-                            } else {
-                                &infos.dlcs[song_info.dlc_index as usize - 1]
-                            }
-                            .to_string(),
-                        ]
-                        .into_iter()
-                        .map(|item| StandardListViewItem::from(item.as_ref()))
-                        .collect::<Vec<_>>();
-
-                        ModelRc::new(VecModel::from(row_items))
-                    })
-                    .collect::<Vec<_>>();
-
-                row_data.set_vec(row_models);
+                let cached = song_infos
+                    .borrow()
+                    .clone()
+                    .filter(|_| *loaded_path.borrow() == path.as_str());
+                let infos = match cached {
+                    Some(infos) => infos,
+                    None => match get_song_info(Path::new(path.as_str())) {
+                        Ok(infos) => {
+                            *loaded_path.borrow_mut() = path.to_string();
+                            Rc::new(infos)
+                        }
+                        Err(e) => {
+                            show_error("Unable to read song information", e);
+                            return;
+                        }
+                    },
+                };
 
-                main_window
-                    .unwrap()
-                    .global::<SongInfoAdapter>()
-                    .set_row_data(row_data.into());
+                row_data.set_items((0..infos.maps.len()).collect(), {
+                    let infos = infos.clone();
+                    let columns = columns.borrow().clone();
+                    move |idx| {
+                        song_info_view_record(&infos.maps[*idx], &infos.dlcs, &lang, &columns)
+                            .into_iter()
+                            .map(|item| StandardListViewItem::from(item.as_str()))
+                            .collect()
+                    }
+                });
+                let summary = song_summary(&infos);
+                *song_infos.borrow_mut() = Some(infos);
+
+                let main_window = main_window.unwrap();
+                let adapter = main_window.global::<SongInfoAdapter>();
+                adapter.set_song_count(summary.total as i32);
+                adapter.set_dlc_counts(format_counts(&summary.dlcs).into());
+                adapter.set_area_counts(format_counts(&summary.areas).into());
+                adapter.set_row_data(row_data.into());
             }
         });
 
@@ -142,9 +212,13 @@ fn init_song_info_adapter(main_window: &MainWindow) {
         .unwrap()
         .global::<SongInfoAdapter>()
         .on_generate_csv({
-            let row_data = row_data.clone();
+            let main_window = main_window.clone();
+            let song_infos = song_infos.clone();
             move || {
-                let row_data = row_data.clone();
+                let song_infos = song_infos.borrow();
+                let Some(infos) = song_infos.as_ref() else {
+                    return;
+                };
 
                 let path = rfd::FileDialog::new()
                     .set_title("Path of output CSV")
@@ -153,34 +227,103 @@ fn init_song_info_adapter(main_window: &MainWindow) {
 
                 let Some(path) = path else { return };
 
-                let mut writer = BufWriter::new(File::create(path).unwrap());
-                if cfg!(windows) {
-                    // Write BOM for Windows programs to recognize encoding
-                    writer.write_all(&[0xEF, 0xBB, 0xBF]).unwrap();
+                let main_window = main_window.unwrap();
+                let adapter = main_window.global::<SongInfoAdapter>();
+                let options = CsvOptions {
+                    langs: vec![JA, Chs, Cht, EN, KO],
+                    excel: adapter.get_excel(),
+                    semicolon: adapter.get_semicolon(),
+                    ..Default::default()
+                };
+
+                if let Err(e) = write_song_info_csv(infos, &path, &options) {
+                    show_error("Unable to write CSV", e);
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<SongInfoAdapter>()
+        .on_generate_xlsx({
+            let song_infos = song_infos.clone();
+            move || {
+                let song_infos = song_infos.borrow();
+                let Some(infos) = song_infos.as_ref() else {
+                    return;
+                };
+
+                let path = rfd::FileDialog::new()
+                    .set_title("Path of output XLSX")
+                    .add_filter("Excel Workbook", &["xlsx"])
+                    .save_file();
+
+                let Some(path) = path else { return };
+
+                if let Err(e) = write_song_info_xlsx(infos, &path, &[JA, Chs, Cht, EN, KO], false) {
+                    show_error("Unable to write XLSX", e);
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<SongInfoAdapter>()
+        .on_inspect_unlocks({
+            let main_window = main_window.clone();
+            move || {
+                let path = main_window.unwrap().global::<SongInfoAdapter>().get_path();
+                if path.is_empty() {
+                    return;
+                }
+
+                let (level, description) = match get_song_info(Path::new(path.as_str())) {
+                    Ok(infos) => (
+                        rfd::MessageLevel::Info,
+                        dlc_musics::DlcMusics::from_song_infos(&infos).to_string(),
+                    ),
+                    Err(e) => (rfd::MessageLevel::Error, e.to_string()),
+                };
+
+                rfd::MessageDialog::new()
+                    .set_title("DLC songs")
+                    .set_description(description)
+                    .set_level(level)
+                    .show();
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<SongInfoAdapter>()
+        .on_toggle_column({
+            let main_window = main_window.clone();
+            let columns = columns.clone();
+            move |index, shown| {
+                let Some(toggled) = SongInfoColumn::iter().nth(index as usize) else {
+                    return;
+                };
+
+                let new_columns = SongInfoColumn::iter()
+                    .filter(|column| {
+                        if *column == toggled {
+                            shown
+                        } else {
+                            columns.borrow().contains(column)
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                // The table needs at least one column, the check box is reset instead
+                if !new_columns.is_empty() {
+                    *columns.borrow_mut() = new_columns;
+                    save_song_info_columns(&columns.borrow());
                 }
-                let mut writer = csv::Writer::from_writer(writer);
-
-                writer
-                    .write_record([
-                        "ID",
-                        "Title",
-                        "Artist",
-                        "Original",
-                        "Effective BPM",
-                        "Has Tempo Changes",
-                        "Levels - Easy",
-                        "Levels - Normal",
-                        "Levels - Hard",
-                        "Length",
-                        "Area",
-                        "DLC",
-                    ])
-                    .unwrap();
-
-                for row in row_data.iter() {
-                    let row_text = row.iter().map(|item| item.text).collect::<Vec<_>>();
-                    let row_strs = row_text.iter().map(|text| text.as_str());
-                    writer.write_record(row_strs).unwrap();
+                update_song_info_columns(&main_window, &columns.borrow());
+
+                let main_window = main_window.unwrap();
+                let adapter = main_window.global::<SongInfoAdapter>();
+                if !adapter.get_path().is_empty() {
+                    adapter.invoke_load_data(current_lang_id.get());
                 }
             }
         });
@@ -263,66 +406,153 @@ impl PartialOrd for MapInfoSortKey {
     }
 }
 
-fn get_key_by_column(index: i32, map_model: &MapInfo) -> MapInfoSortKey {
+/// Formats counts as "name: count" joined by commas
+fn format_counts(counts: &[(String, usize)]) -> String {
+    counts
+        .iter()
+        .map(|(name, count)| format!("{name}: {count}"))
+        .join(", ")
+}
+
+/// Updates map counts by area and by validation status under the custom map
+/// table
+fn update_map_summary(main_window: &Weak<MainWindow>) {
+    let main_window = main_window.unwrap();
+    let adapter = main_window.global::<CustomMapAdapter>();
+    let maps = adapter.get_maps();
+
+    let areas = maps
+        .iter()
+        .map(|map| {
+            Area::from(AreaModel {
+                area_idx:   map.area_idx,
+                area_night: map.area_night,
+            })
+            .to_string()
+        })
+        .sorted()
+        .dedup_with_count()
+        .map(|(count, area)| (area, count))
+        .collect::<Vec<_>>();
+    adapter.set_area_counts(format_counts(&areas).into());
+
+    let mut status_counts = [0; 3];
+    for map in maps.iter() {
+        status_counts[map.status.clamp(0, 2) as usize] += 1;
+    }
+    adapter.set_status_counts(ModelRc::new(VecModel::from(status_counts.to_vec())));
+}
+
+fn replaced_song(official_songs: &HashMap<String, String>, id: &str) -> SharedString {
+    official_songs
+        .get(&id.to_lowercase())
+        .cloned()
+        .unwrap_or_default()
+        .into()
+}
+
+fn get_key_by_column(
+    index: i32,
+    map_model: &MapInfo,
+    official_songs: &HashMap<String, String>,
+) -> MapInfoSortKey {
     match index {
-        0 => MapInfoSortKey::String(map_model.id.to_owned()),
-        1 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, title).to_owned()),
-        2 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, artist).to_owned()),
-        3 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, original).to_owned()),
-        4 => MapInfoSortKey::Float(map_model.bpm),
-        5 => MapInfoSortKey::String(SharedString::from(format!(
+        0 => MapInfoSortKey::Int(map_model.status),
+        1 => MapInfoSortKey::String(map_model.id.to_owned()),
+        2 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, title).to_owned()),
+        3 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, artist).to_owned()),
+        4 => MapInfoSortKey::String(obtain_text_field!(map_model.info_text, original).to_owned()),
+        5 => MapInfoSortKey::Float(map_model.bpm),
+        6 => MapInfoSortKey::String(SharedString::from(format!(
             "{}",
             Area::from(AreaModel {
                 area_idx:   map_model.area_idx,
                 area_night: map_model.area_night,
             })
         ))),
-        6 => MapInfoSortKey::Int(map_model.level),
-        7 => MapInfoSortKey::String(map_model.music_file.to_owned()),
-        8 => MapInfoSortKey::Int(map_model.prev_start_ms),
+        7 => MapInfoSortKey::Int(map_model.level),
+        8 => MapInfoSortKey::String(map_model.music_file.to_owned()),
+        9 => MapInfoSortKey::Int(map_model.prev_start_ms),
+        10 => MapInfoSortKey::String(map_model.issues.to_owned()),
+        11 => MapInfoSortKey::String(replaced_song(official_songs, &map_model.id)),
         _ => unreachable!(),
     }
 }
 
-fn init_custom_map_adapter(main_window: &MainWindow) {
+/// Table row of a custom map, with the official song it replaces in the last
+/// column
+fn map_row_items(
+    map: MapInfo,
+    official_songs: &HashMap<String, String>,
+) -> Vec<StandardListViewItem> {
+    let replaces = replaced_song(official_songs, &map.id);
+    let status: SharedString = match map.status {
+        0 => "🟢",
+        1 => "🟡",
+        _ => "🔴",
+    }
+    .into();
+    let id = map.id;
+    let title = obtain_text_field!(map.info_text, title);
+    let artist = obtain_text_field!(map.info_text, artist);
+    let original = obtain_text_field!(map.info_text, original);
+    let bpm: SharedString = map.bpm.to_string().into();
+    let area: SharedString = format!(
+        "{}",
+        Area::from(AreaModel {
+            area_idx:   map.area_idx,
+            area_night: map.area_night,
+        })
+    )
+    .into();
+    let level: SharedString = map.level.to_string().into();
+    let music_file = map.music_file;
+    let preview_start: SharedString = map.prev_start_ms.to_string().into();
+    let issues = map.issues;
+
+    vec![
+        status,
+        id,
+        title,
+        artist,
+        original,
+        bpm,
+        area,
+        level,
+        music_file,
+        preview_start,
+        issues,
+        replaces,
+    ]
+    .into_iter()
+    .map(StandardListViewItem::from)
+    .collect()
+}
+
+/// Parses a map in its toml form in map config files, validated as a new song
+/// like maps generated in GUI
+fn parse_map_toml(text: &str) -> anyhow::Result<Map> {
+    let map: Map = toml::from_str(text)?;
+    map.validate(false)?;
+    Ok(map)
+}
+
+/// Sets up the custom map page, the returned timer watches the maps file for
+/// changes by other instances and must be kept alive
+fn init_custom_map_adapter(main_window: &MainWindow) -> slint::Timer {
     let main_window = main_window.as_weak();
 
+    // "title - artist" of official songs in the chosen RomFS, keyed by lowercase
+    // music ID
+    let official_songs: Rc<RefCell<HashMap<String, String>>> = Default::default();
+
     main_window
         .unwrap()
         .global::<CustomMapAdapter>()
         .on_to_row_data({
-            |map| {
-                let id = map.id;
-                let title = obtain_text_field!(map.info_text, title);
-                let artist = obtain_text_field!(map.info_text, artist);
-                let original = obtain_text_field!(map.info_text, original);
-                let bpm: SharedString = map.bpm.to_string().into();
-                let area: SharedString = format!(
-                    "{}",
-                    Area::from(AreaModel {
-                        area_idx:   map.area_idx,
-                        area_night: map.area_night,
-                    })
-                )
-                .into();
-                let level: SharedString = map.level.to_string().into();
-                let music_file = map.music_file;
-                let preview_start: SharedString = map.prev_start_ms.to_string().into();
-
-                let row = vec![
-                    id,
-                    title,
-                    artist,
-                    original,
-                    bpm,
-                    area,
-                    level,
-                    music_file,
-                    preview_start,
-                ]
-                .into_iter()
-                .map(StandardListViewItem::from)
-                .collect::<Vec<_>>();
+            let official_songs = official_songs.clone();
+            move |map| {
+                let row = map_row_items(map, &official_songs.borrow());
                 ModelRc::new(VecModel::from(row))
             }
         });
@@ -331,19 +561,37 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
         .unwrap()
         .global::<CustomMapAdapter>()
         .on_generate_row_data({
-            let main_window = main_window.clone();
+            let official_songs = official_songs.clone();
             move |maps| {
-                let row_data = maps
-                    .iter()
-                    .map(|m| {
-                        main_window
-                            .unwrap()
-                            .global::<CustomMapAdapter>()
-                            .invoke_to_row_data(m)
-                    })
-                    .collect::<Vec<_>>();
+                let official_songs = official_songs.clone();
+                let row_data = LazyRowModel::new(maps.iter().collect(), move |map: &MapInfo| {
+                    map_row_items(map.clone(), &official_songs.borrow())
+                });
+
+                ModelRc::new(row_data)
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_romfs_path_changed({
+            let main_window = main_window.clone();
+            let official_songs = official_songs.clone();
+            move || {
+                let romfs_root = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .get_romfs_path();
+                romfs_detect::remember(Path::new(romfs_root.as_str()));
+                *official_songs.borrow_mut() = get_song_info(Path::new(romfs_root.as_str()))
+                    .map(|infos| official_song_names(&infos))
+                    .unwrap_or_default();
 
-                ModelRc::new(VecModel::from(row_data))
+                main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .invoke_update_row_data();
             }
         });
 
@@ -362,10 +610,23 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
                     .unwrap()
                     .global::<CustomMapAdapter>()
                     .set_row_data(row_data);
+
+                update_map_summary(&main_window);
             }
         });
 
-    let maps = load_local_config().unwrap_or_default();
+    let maps = match load_local_config() {
+        Ok(maps) => maps,
+        // No maps saved yet
+        Err(_) if !local_config_path().is_some_and(|path| path.exists()) => HashMap::new(),
+        Err(e) => {
+            show_error(
+                "Unable to load maps, the maps file is overwritten once maps are changed",
+                e,
+            );
+            HashMap::new()
+        }
+    };
     let maps = Rc::new(RefCell::new(maps));
 
     let maps_model = maps
@@ -376,6 +637,9 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
         .collect::<Vec<_>>();
     let maps_model: Rc<VecModel<MapInfo>> = Rc::new(VecModel::from(maps_model));
 
+    // Imported maps waiting for merge choices of conflicting IDs
+    let pending_import: Rc<RefCell<Vec<Map>>> = Default::default();
+
     {
         let maps_model = maps_model.clone();
         main_window
@@ -389,18 +653,23 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
             .invoke_update_row_data();
     }
 
+    init_projects(&main_window, &maps, &maps_model);
+
     main_window
         .unwrap()
         .global::<CustomMapAdapter>()
         .on_sort_ascending({
             let main_window = main_window.clone();
             let maps_model = maps_model.clone();
+            let official_songs = official_songs.clone();
 
             move |index| {
                 let maps_model = maps_model.clone();
+                let official_songs = official_songs.clone();
                 let sort_model = Rc::new(maps_model.sort_by(move |a, b| {
-                    let k_a = get_key_by_column(index, a);
-                    let k_b = get_key_by_column(index, b);
+                    let official_songs = official_songs.borrow();
+                    let k_a = get_key_by_column(index, a, &official_songs);
+                    let k_b = get_key_by_column(index, b, &official_songs);
 
                     k_a.partial_cmp(&k_b).unwrap()
                 }));
@@ -423,12 +692,15 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
         .on_sort_descending({
             let main_window = main_window.clone();
             let maps_model = maps_model.clone();
+            let official_songs = official_songs.clone();
 
             move |index| {
                 let maps_model = maps_model.clone();
+                let official_songs = official_songs.clone();
                 let sort_model = Rc::new(maps_model.sort_by(move |a, b| {
-                    let k_a = get_key_by_column(index, a);
-                    let k_b = get_key_by_column(index, b);
+                    let official_songs = official_songs.borrow();
+                    let k_a = get_key_by_column(index, a, &official_songs);
+                    let k_b = get_key_by_column(index, b, &official_songs);
 
                     k_b.partial_cmp(&k_a).unwrap()
                 }));
@@ -535,6 +807,70 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
             }
         });
 
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_get_selected_map_toml({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            move || {
+                let map_model = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .invoke_get_selected_map();
+
+                // The stored map has fields the table row doesn't carry
+                let maps = maps.borrow();
+                let Some(map) = maps.get(map_model.id.as_str()) else {
+                    return Default::default();
+                };
+                toml::to_string_pretty(map).unwrap_or_default().into()
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_validate_map_toml(|text| match parse_map_toml(&text) {
+            Ok(_) => Default::default(),
+            Err(e) => e.to_string().into(),
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_apply_map_toml({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+            move |text| {
+                let Ok(mut map) = parse_map_toml(&text) else {
+                    return false;
+                };
+
+                let window = main_window.unwrap();
+                let adapter = window.global::<CustomMapAdapter>();
+                let old_id = adapter.invoke_get_selected_map().id;
+                let Some(model_idx) = maps_model.iter().position(|m| m.id == old_id) else {
+                    return false;
+                };
+
+                // The parsed map is stored as is, going through the table row
+                // would drop fields it doesn't carry
+                maps.borrow_mut().remove(old_id.as_str());
+                let is_taken = |id: &str| maps.borrow().contains_key(id);
+                let new_id = MusicID::dedup(&map.song_info.id.to_string(), is_taken);
+                map.song_info.id = MusicID::New(new_id.clone());
+
+                maps_model.set_row_data(model_idx, MapInfo::from(&map));
+                maps.borrow_mut().insert(new_id, map);
+                save_local_config(&maps.borrow());
+
+                adapter.invoke_update_row_data();
+                true
+            }
+        });
+
     main_window
         .unwrap()
         .global::<CustomMapAdapter>()
@@ -555,19 +891,20 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
                 let old_map = maps_model.remove(model_idx);
                 let _old_map = maps.borrow_mut().remove(old_map.id.as_str()).unwrap();
 
-                let mut new_id = map_model.id.as_str().to_owned();
-
-                let mut append_idx = 1;
-                while maps.borrow().contains_key(&new_id) {
-                    new_id = format!("{new_id}{append_idx}");
-                    append_idx += 1;
-                }
+                let mut map = Map::from(&map_model);
+                let is_taken = |id: &str| maps.borrow().contains_key(id);
+                let new_id = if map_model.id.is_empty() {
+                    map.song_info.suggest_id(is_taken)
+                } else {
+                    MusicID::dedup(map_model.id.as_str(), is_taken)
+                };
+                map.song_info.id = MusicID::New(new_id.clone());
 
                 let mut map_model = map_model;
                 map_model.id = new_id.clone().into();
 
-                let map = Map::from(&map_model);
                 map_model.level = map.level(Hard, None) as i32;
+                (map_model.status, map_model.status_text) = validation_status(&map);
 
                 maps.borrow_mut().insert(new_id, map);
                 maps_model.insert(model_idx, map_model);
@@ -593,50 +930,76 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
             let main_window = main_window.clone();
             let maps = maps.clone();
             let maps_model = maps_model.clone();
+            let pending_import = pending_import.clone();
 
             move || {
-                let maps_model = maps_model.clone();
                 let file = rfd::FileDialog::new()
                     .set_title("Maps config toml")
                     .add_filter("Config file", &["toml"])
                     .pick_file();
                 if let Some(file) = file {
-                    if let Ok(new_maps) = load_config(&file) {
-                        let mut new_maps = new_maps.into_values().collect::<Vec<_>>();
-
-                        for map in new_maps.iter_mut() {
-                            let mut id = 1;
-                            let music_id = map.song_info.id.to_string();
-                            while maps.borrow().contains_key(&music_id) {
-                                map.song_info.id = MusicID::New(format!("{music_id}{id}"));
-                                id += 1;
-                            }
-                        }
-
-                        let new_map_models = new_maps.iter().map(MapInfo::from);
-                        maps_model.extend(new_map_models);
-                        maps.borrow_mut().extend(
-                            new_maps
-                                .into_iter()
-                                .map(|m| (m.song_info.id.to_string(), m)),
-                        );
-
-                        save_local_config(&maps.borrow());
-
-                        main_window
-                            .unwrap()
-                            .global::<CustomMapAdapter>()
-                            .set_maps(maps_model.into());
-
-                        main_window
-                            .unwrap()
-                            .global::<CustomMapAdapter>()
-                            .invoke_update_row_data();
+                    match load_config(&file) {
+                        Ok(new_maps) => import_maps(
+                            &main_window,
+                            &maps,
+                            &maps_model,
+                            &pending_import,
+                            new_maps.into_values().collect(),
+                        ),
+                        Err(e) => show_error(
+                            "Unable to import maps",
+                            e.context(format!("reading {}", file.display())),
+                        ),
                     }
                 }
             }
         });
 
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_set_conflict_choice({
+            let main_window = main_window.clone();
+            move |idx, choice| {
+                let conflicts = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .get_import_conflicts();
+                if let Some(mut conflict) = conflicts.row_data(idx as usize) {
+                    conflict.choice = choice;
+                    conflicts.set_row_data(idx as usize, conflict);
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_finish_import({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+            let pending_import = pending_import.clone();
+
+            move |confirmed| {
+                let window = main_window.unwrap();
+                let adapter = window.global::<CustomMapAdapter>();
+                adapter.set_merging_import(false);
+
+                let new_maps = pending_import.take();
+                if !confirmed {
+                    return;
+                }
+
+                let choices = adapter
+                    .get_import_conflicts()
+                    .iter()
+                    .map(|conflict| (conflict.id.to_string(), conflict.choice))
+                    .collect::<HashMap<_, _>>();
+                merge_maps(&main_window, &maps, &maps_model, new_maps, &choices);
+            }
+        });
+
     main_window
         .unwrap()
         .global::<CustomMapAdapter>()
@@ -645,12 +1008,996 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
 
             move || {
                 let file = rfd::FileDialog::new()
-                    .set_title("Maps config toml")
+                    .set_title("Maps config toml")
+                    .add_filter("Config file", &["toml"])
+                    .save_file();
+
+                if let Some(file) = file {
+                    if let Err(e) = save_config(&maps.borrow(), &file) {
+                        show_error("Unable to export maps", e);
+                    }
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_toggle_export({
+            let main_window = main_window.clone();
+            move |idx, checked| {
+                let export_checked = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .get_export_checked();
+                export_checked.set_row_data(idx as usize, checked);
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_select_export({
+            let main_window = main_window.clone();
+            let maps_model = maps_model.clone();
+            move |mode| {
+                let export_checked = maps_model
+                    .iter()
+                    .map(|map| match mode {
+                        0 => false,
+                        1 => true,
+                        _ => map.status != MapStatus::Error as i32,
+                    })
+                    .collect::<Vec<_>>();
+
+                main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .set_export_checked(ModelRc::new(VecModel::from(export_checked)));
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_export_selected_to_file({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+
+            move || {
+                let export_checked = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .get_export_checked();
+                let selected_maps = maps_model
+                    .iter()
+                    .zip(export_checked.iter())
+                    .filter(|(_, checked)| *checked)
+                    .filter_map(|(map, _)| {
+                        let id = map.id.to_string();
+                        let map = maps.borrow().get(&id)?.clone();
+                        Some((id, map))
+                    })
+                    .collect::<HashMap<_, _>>();
+
+                let file = rfd::FileDialog::new()
+                    .set_title("Maps config toml")
+                    .add_filter("Config file", &["toml"])
+                    .save_file();
+
+                if let Some(file) = file {
+                    if let Err(e) = save_config(&selected_maps, &file) {
+                        show_error("Unable to export maps", e);
+                    }
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_export_bundle({
+            let maps = maps.clone();
+
+            move || {
+                let file = rfd::FileDialog::new()
+                    .set_title("Project bundle")
+                    .add_filter("Project bundle", &["zip"])
+                    .save_file();
+
+                if let Some(file) = file {
+                    if let Err(e) = project_bundle::export_bundle(maps.borrow().values(), &file) {
+                        show_error("Unable to export bundle", e);
+                    }
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_package_mod({
+            let main_window = main_window.clone();
+
+            move || {
+                let Some(mod_dir) = rfd::FileDialog::new()
+                    .set_title("Generated mod folder")
+                    .pick_folder()
+                else {
+                    return;
+                };
+
+                let mod_name = main_window
+                    .unwrap()
+                    .global::<CustomMapAdapter>()
+                    .get_mod_name();
+                let mod_name = if mod_name.trim().is_empty() {
+                    exefs::default_mod_name(&mod_dir)
+                } else {
+                    mod_name.trim().to_owned()
+                };
+
+                let Some(archive) = rfd::FileDialog::new()
+                    .set_title("Mod archive")
+                    .add_filter("Zip archive", &["zip"])
+                    .set_file_name(format!("{mod_name}.zip"))
+                    .save_file()
+                else {
+                    return;
+                };
+
+                let (level, description) =
+                    match mod_package::package_mod(&mod_dir, &mod_name, &archive) {
+                        Ok(info) => (
+                            rfd::MessageLevel::Info,
+                            format!(
+                                "Packaged {mod_name} with {} songs into {}",
+                                info.songs.len(),
+                                archive.display()
+                            ),
+                        ),
+                        Err(e) => (rfd::MessageLevel::Error, e.to_string()),
+                    };
+
+                rfd::MessageDialog::new()
+                    .set_title("Package mod")
+                    .set_description(description)
+                    .set_level(level)
+                    .show();
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_import_bundle({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+            let pending_import = pending_import.clone();
+
+            move || {
+                let file = rfd::FileDialog::new()
+                    .set_title("Project bundle")
+                    .add_filter("Project bundle", &["zip"])
+                    .pick_file();
+
+                let Some(file) = file else { return };
+                let Some(mut dest_dir) = app_dirs::config_dir() else {
+                    show_error(
+                        "Unable to import bundle",
+                        anyhow::anyhow!("No config directory to extract the bundle into"),
+                    );
+                    return;
+                };
+                dest_dir.push("bundles");
+                dest_dir.push(file.file_stem().unwrap_or_default());
+
+                match project_bundle::import_bundle(&file, &dest_dir) {
+                    Ok(new_maps) => {
+                        import_maps(&main_window, &maps, &maps_model, &pending_import, new_maps)
+                    }
+                    Err(e) => show_error("Unable to import bundle", e),
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_generate_mod({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+
+            move || {
+                let out_dir = rfd::FileDialog::new()
+                    .set_title("Mod output path")
+                    .pick_folder();
+
+                if let Some(out_dir) = out_dir {
+                    let started = Instant::now();
+                    let romfs_root = main_window
+                        .unwrap()
+                        .global::<CustomMapAdapter>()
+                        .get_romfs_path();
+                    let romfs_root = Path::new(romfs_root.as_str());
+                    let exefs_root = main_window
+                        .unwrap()
+                        .global::<CustomMapAdapter>()
+                        .get_exefs_path();
+                    let mut main_exe_path = PathBuf::from(exefs_root.as_str());
+                    main_exe_path.push("main");
+
+                    match exefs::check_build(&main_exe_path) {
+                        Err(e) if e.is::<exefs::UnsupportedBuildError>() => {
+                            let confirmed = rfd::MessageDialog::new()
+                                .set_title("Unsupported game build")
+                                .set_description(format!("{e}\n\nGenerate anyway?"))
+                                .set_level(rfd::MessageLevel::Warning)
+                                .set_buttons(rfd::MessageButtons::YesNo)
+                                .show()
+                                == rfd::MessageDialogResult::Yes;
+                            if !confirmed {
+                                return;
+                            }
+                        }
+                        Err(e) => {
+                            show_generation_error(&e, &out_dir, false);
+                            return;
+                        }
+                        Ok(()) => {}
+                    }
+
+                    let maps = maps.borrow();
+                    let names = maps
+                        .values()
+                        .map(|m| m.song_info.id.to_string())
+                        .collect::<Vec<_>>();
+
+                    let mod_name = main_window
+                        .unwrap()
+                        .global::<CustomMapAdapter>()
+                        .get_mod_name();
+                    let mod_name = if mod_name.trim().is_empty() {
+                        exefs::default_mod_name(&out_dir)
+                    } else {
+                        mod_name.trim().to_owned()
+                    };
+
+                    let mut warnings = vec![];
+                    let maps = maps
+                        .values()
+                        .cloned()
+                        .map(|mut map| {
+                            for adjustment in map.collapse_bpm_changes() {
+                                warnings.push(format!("{}: {adjustment}", map.song_info.id));
+                            }
+                            map
+                        })
+                        .collect::<Vec<_>>();
+
+                    if let Some(mod_root) = input_check::generated_mod_root(romfs_root) {
+                        let confirmed = rfd::MessageDialog::new()
+                            .set_title("Patched game files")
+                            .set_description(format!(
+                                "The RomFS path is inside mod {} generated by this tool. Patching \
+                                 its files again usually corrupts them, select the original game \
+                                 dump instead.\n\nContinue anyway?",
+                                mod_root.display()
+                            ))
+                            .set_level(rfd::MessageLevel::Warning)
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show()
+                            == rfd::MessageDialogResult::Yes;
+                        if !confirmed {
+                            return;
+                        }
+                    }
+
+                    let input_hashes =
+                        input_check::hash_inputs(romfs_root, Some(&main_exe_path)).ok();
+                    let changed_inputs = input_hashes
+                        .as_ref()
+                        .map(|hashes| input_check::changed_inputs(&load_input_hashes(), hashes))
+                        .unwrap_or_default();
+                    if !changed_inputs.is_empty() {
+                        let confirmed = rfd::MessageDialog::new()
+                            .set_title("Game files changed")
+                            .set_description(format!(
+                                "Game files changed since the last generation (game updated or \
+                                 dump replaced): {}.\n\nMods generated from different game \
+                                 versions should not be mixed, re-validate maps and install the \
+                                 regenerated mod as a whole. Continue?",
+                                changed_inputs.join(", ")
+                            ))
+                            .set_level(rfd::MessageLevel::Warning)
+                            .set_buttons(rfd::MessageButtons::YesNo)
+                            .show()
+                            == rfd::MessageDialogResult::Yes;
+                        if !confirmed {
+                            return;
+                        }
+                    }
+
+                    let required = space_check::estimate_output_size(romfs_root, &maps, false);
+                    if let Err(e) = space_check::check_free_space(&out_dir, required) {
+                        show_generation_error(&e, &out_dir, false);
+                        return;
+                    }
+
+                    let staging = match StagingDir::new(&out_dir) {
+                        Ok(staging) => staging,
+                        Err(e) => {
+                            show_generation_error(&e.into(), &out_dir, false);
+                            return;
+                        }
+                    };
+
+                    let window = main_window.unwrap();
+                    let adapter = window.global::<CustomMapAdapter>();
+                    adapter.set_generation_progress(0.0);
+                    adapter.set_generation_status("Starting".into());
+                    adapter.set_generating(true);
+
+                    let main_window = main_window.clone();
+                    let romfs_root = romfs_root.to_owned();
+                    std::thread::spawn(move || {
+                        let song_count = maps.len();
+                        let done_songs = AtomicUsize::new(0);
+                        // Progress is reported from the patching threads, the window handle
+                        // can only be shared between them behind a lock
+                        let progress_window = Mutex::new(main_window.clone());
+                        let report_progress = |done: usize, status: String| {
+                            let main_window = progress_window.lock().unwrap().clone();
+                            let _ = slint::invoke_from_event_loop(move || {
+                                let window = main_window.unwrap();
+                                let adapter = window.global::<CustomMapAdapter>();
+                                // Songs are half of the work, ExeFS patching the other half
+                                adapter.set_generation_progress(
+                                    done as f32 / song_count.max(1) as f32 * 0.5,
+                                );
+                                adapter.set_generation_status(status.into());
+                            });
+                        };
+
+                        let result: anyhow::Result<_> = try {
+                            let mut sink = DirSink::new(staging.path());
+                            let score_donor = Map::patch_files(
+                                &romfs_root,
+                                &sink,
+                                &maps,
+                                false,
+                                &Default::default(),
+                                1,
+                                &|progress| {
+                                    let status = match progress {
+                                        PatchProgress::Song(id, SongPhase::Converting) => {
+                                            format!("{id}: converting audio")
+                                        }
+                                        PatchProgress::Song(id, SongPhase::PatchingAcb) => {
+                                            format!("{id}: encoding acb")
+                                        }
+                                        PatchProgress::Song(id, SongPhase::PatchingScore) => {
+                                            format!("{id}: patching score")
+                                        }
+                                        PatchProgress::Song(id, SongPhase::Done) => {
+                                            done_songs.fetch_add(1, AtomicOrdering::Relaxed);
+                                            format!("{id}: done")
+                                        }
+                                        PatchProgress::ShareData => {
+                                            "Patching share_data".to_owned()
+                                        }
+                                    };
+                                    let done = done_songs.load(AtomicOrdering::Relaxed);
+                                    report_progress(done, status);
+                                },
+                            )?;
+                            report_progress(song_count, "Patching ExeFS".to_owned());
+                            let asset_bundle = exefs::patch_files(
+                                &romfs_root,
+                                &main_exe_path,
+                                &sink,
+                                &mod_name,
+                                &names,
+                                Default::default(),
+                            )?;
+                            ModManifest {
+                                added_maps: maps.clone(),
+                                ..Default::default()
+                            }
+                            .with_score_donor(score_donor.as_deref())
+                            .write(&sink)?;
+                            sink.finish()?;
+                            (score_donor, asset_bundle)
+                        };
+                        // Errors are paired with whether the output folder was partially updated
+                        let result = result.map_err(|e| (e, false)).and_then(|outputs| {
+                            report_progress(song_count * 2, "Moving files into place".to_owned());
+                            staging.commit().map_err(|e| (e.into(), true))?;
+                            Ok(outputs)
+                        });
+
+                        let _ = slint::invoke_from_event_loop(move || {
+                            main_window
+                                .unwrap()
+                                .global::<CustomMapAdapter>()
+                                .set_generating(false);
+
+                            match result {
+                                Ok((score_donor, asset_bundle)) => {
+                                    if let Some(input_hashes) = input_hashes {
+                                        save_input_hashes(&input_hashes);
+                                    }
+                                    if let Ok(fs_warnings) = fs_check::check_output_tree(&out_dir) {
+                                        warnings
+                                            .extend(fs_warnings.iter().map(ToString::to_string));
+                                    }
+                                    show_output_report(
+                                        &out_dir,
+                                        &maps,
+                                        score_donor.as_deref(),
+                                        &asset_bundle,
+                                        started.elapsed(),
+                                        &warnings,
+                                    );
+                                }
+                                Err((e, partially_updated)) => {
+                                    show_generation_error(&e, &out_dir, partially_updated)
+                                }
+                            }
+                        });
+                    });
+                }
+            }
+        });
+
+    watch_local_config(&main_window, &maps, &maps_model)
+}
+
+const OPEN_FOLDER_BUTTON: &str = "Open output folder";
+
+/// Shows per-song outputs and warnings of a finished generation, offering to
+/// open the output folder
+fn show_output_report(
+    out_dir: &Path,
+    maps: &[Map],
+    score_donor: Option<&str>,
+    asset_bundle: &exefs::AssetBundlePatch,
+    elapsed: Duration,
+    warnings: &[String],
+) {
+    let outputs = output_report::song_outputs(out_dir, maps, Default::default(), "");
+    let has_outliers = output_report::has_outliers(&outputs);
+    let level = if has_outliers || !warnings.is_empty() {
+        rfd::MessageLevel::Warning
+    } else {
+        rfd::MessageLevel::Info
+    };
+    let note = if has_outliers {
+        "\n\nSome songs have missing or suspiciously small audio output, check their music files \
+         before installing the mod."
+    } else {
+        ""
+    };
+    let donor_note = score_donor
+        .map(|donor| format!("\n\nScores of new songs are based on the score of {donor}"))
+        .unwrap_or_default();
+    let warning_note = if warnings.is_empty() {
+        String::new()
+    } else {
+        format!("\n\nWarnings:\n{}", warnings.join("\n"))
+    };
+
+    let open_folder = rfd::MessageDialog::new()
+        .set_title("Mod generated")
+        .set_description(format!(
+            "Generated {} songs into {} in \
+             {:.1}s\n\n{}{note}{warning_note}{donor_note}\n\n{asset_bundle}",
+            maps.len(),
+            out_dir.display(),
+            elapsed.as_secs_f32(),
+            output_report::OutputReport(&outputs)
+        ))
+        .set_level(level)
+        .set_buttons(rfd::MessageButtons::OkCancelCustom(
+            OPEN_FOLDER_BUTTON.to_owned(),
+            "Close".to_owned(),
+        ))
+        .show()
+        == rfd::MessageDialogResult::Custom(OPEN_FOLDER_BUTTON.to_owned());
+    if open_folder {
+        if let Err(e) = open_in_file_manager(out_dir) {
+            rfd::MessageDialog::new()
+                .set_title("Failed to open output folder")
+                .set_description(format!("{e}\n\n{}", out_dir.display()))
+                .set_level(rfd::MessageLevel::Error)
+                .show();
+        }
+    }
+}
+
+/// Opens `dir` in the file manager of the OS
+fn open_in_file_manager(dir: &Path) -> std::io::Result<()> {
+    let opener = if cfg!(target_os = "windows") {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    std::process::Command::new(opener).arg(dir).spawn()?;
+    Ok(())
+}
+
+/// Shows `error` with the chain of its causes in an error dialog
+fn show_error(title: &str, error: impl Into<anyhow::Error>) {
+    rfd::MessageDialog::new()
+        .set_title(title)
+        .set_description(format!("{:#}", error.into()))
+        .set_level(rfd::MessageLevel::Error)
+        .show();
+}
+
+fn show_generation_error(error: &anyhow::Error, out_dir: &Path, partially_updated: bool) {
+    let state = if partially_updated {
+        "may be partially updated"
+    } else {
+        "is left unchanged"
+    };
+
+    rfd::MessageDialog::new()
+        .set_title("Failed to generate mod")
+        .set_description(format!("{error:#}\n\n{} {state}", out_dir.display()))
+        .set_level(rfd::MessageLevel::Error)
+        .show();
+}
+
+/// Imports maps directly if none of them has the ID of an existing map,
+/// otherwise keeps them in `pending_import` and shows the merge dialog with
+/// field differences of each conflicting map
+fn import_maps(
+    main_window: &Weak<MainWindow>,
+    maps: &RefCell<HashMap<String, Map>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+    pending_import: &RefCell<Vec<Map>>,
+    new_maps: Vec<Map>,
+) {
+    let conflicts = new_maps
+        .iter()
+        .filter_map(|map| {
+            let id = map.song_info.id.to_string();
+            let local = maps.borrow().get(&id)?.clone();
+            let diff = field_diff(&local, map).iter().join("\n");
+            Some(ImportConflict {
+                id:     id.into(),
+                diff:   diff.into(),
+                choice: 0,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if conflicts.is_empty() {
+        merge_maps(main_window, maps, maps_model, new_maps, &HashMap::new());
+        return;
+    }
+
+    *pending_import.borrow_mut() = new_maps;
+
+    let main_window = main_window.unwrap();
+    let adapter = main_window.global::<CustomMapAdapter>();
+    adapter.set_import_conflicts(ModelRc::new(VecModel::from(conflicts)));
+    adapter.set_merging_import(true);
+}
+
+/// Adds imported maps, maps with IDs of existing ones are merged by `choices`
+/// keyed by ID: 0 keeps the local map, 1 replaces it with the imported map and
+/// 2 keeps both with the imported one renamed
+fn merge_maps(
+    main_window: &Weak<MainWindow>,
+    maps: &RefCell<HashMap<String, Map>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+    new_maps: Vec<Map>,
+    choices: &HashMap<String, i32>,
+) {
+    for mut map in new_maps {
+        let id = map.song_info.id.to_string();
+        if maps.borrow().contains_key(&id) {
+            match choices.get(&id).copied().unwrap_or(2) {
+                0 => continue,
+                1 => {
+                    if let Some(idx) = maps_model.iter().position(|m| m.id == id.as_str()) {
+                        maps_model.set_row_data(idx, MapInfo::from(&map));
+                    }
+                    maps.borrow_mut().insert(id, map);
+                    continue;
+                }
+                _ => {
+                    let id = MusicID::dedup(&id, |id| maps.borrow().contains_key(id));
+                    map.song_info.id = MusicID::New(id);
+                }
+            }
+        }
+
+        maps_model.push(MapInfo::from(&map));
+        maps.borrow_mut().insert(map.song_info.id.to_string(), map);
+    }
+
+    save_local_config(&maps.borrow());
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .set_maps(maps_model.clone().into());
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .invoke_update_row_data();
+}
+
+/// Columns chosen for the song information view
+#[derive(Serialize, Deserialize)]
+struct SongInfoColumnConfig {
+    columns: Vec<SongInfoColumn>,
+}
+
+fn song_info_columns_path() -> Option<PathBuf> {
+    let mut path = app_dirs::config_dir()?;
+    path.push("song_info_columns.toml");
+    Some(path)
+}
+
+fn load_song_info_columns() -> Vec<SongInfoColumn> {
+    song_info_columns_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<SongInfoColumnConfig>(&content).ok())
+        .map(|config| config.columns)
+        .filter(|columns| !columns.is_empty())
+        .unwrap_or(SongInfoColumn::DEFAULT.to_vec())
+}
+
+fn save_song_info_columns(columns: &[SongInfoColumn]) {
+    if let Some(path) = song_info_columns_path() {
+        let config = SongInfoColumnConfig {
+            columns: columns.to_vec(),
+        };
+        let _ = std::fs::create_dir_all(path.parent().unwrap());
+        let _ = std::fs::write(path, toml::to_string_pretty(&config).unwrap());
+    }
+}
+
+/// Sets table columns and check boxes of the song information view to
+/// `columns`
+fn update_song_info_columns(main_window: &Weak<MainWindow>, columns: &[SongInfoColumn]) {
+    let main_window = main_window.unwrap();
+    let adapter = main_window.global::<SongInfoAdapter>();
+    let names = adapter.get_column_names();
+
+    let (shown, table_columns): (Vec<_>, Vec<_>) = SongInfoColumn::iter()
+        .zip(names.iter())
+        .map(|(column, name)| {
+            let shown = columns.contains(&column);
+            // TableColumn is non-exhaustive and can't be built with a struct expression
+            let table_column = shown.then(|| {
+                let mut column = TableColumn::default();
+                column.title = name;
+                column
+            });
+            (shown, table_column)
+        })
+        .unzip();
+    let table_columns = table_columns.into_iter().flatten().collect::<Vec<_>>();
+
+    adapter.set_shown_columns(ModelRc::new(VecModel::from(shown)));
+    adapter.set_columns(ModelRc::new(VecModel::from(table_columns)));
+}
+
+/// Maps file used before projects were introduced, kept as the default project
+fn default_maps_path() -> Option<PathBuf> {
+    let mut path = app_dirs::config_dir()?;
+    path.push("maps.toml");
+    Some(path)
+}
+
+/// Maps file of the current project
+fn local_config_path() -> Option<PathBuf> {
+    load_projects()
+        .current()
+        .map(|project| project.maps_path.clone())
+}
+
+/// A pack of maps with its own maps file and game dump paths
+#[derive(Clone, Serialize, Deserialize)]
+struct Project {
+    name:       String,
+    maps_path:  PathBuf,
+    #[serde(default)]
+    romfs_path: String,
+    #[serde(default)]
+    exefs_path: String,
+    #[serde(default)]
+    mod_name:   String,
+}
+
+impl Project {
+    /// Project of `maps_path` named by its file name, taking other settings
+    /// from `base`
+    fn with_maps_path(maps_path: PathBuf, base: Option<&Project>) -> Self {
+        let name = maps_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let (romfs_path, exefs_path) = base
+            .map(|base| (base.romfs_path.clone(), base.exefs_path.clone()))
+            .unwrap_or_default();
+
+        Self {
+            name,
+            maps_path,
+            romfs_path,
+            exefs_path,
+            mod_name: String::new(),
+        }
+    }
+}
+
+/// Known projects, the current one first and the rest by last use
+#[derive(Default, Serialize, Deserialize)]
+struct ProjectsConfig {
+    projects: Vec<Project>,
+}
+
+const MAX_RECENT_PROJECTS: usize = 10;
+
+impl ProjectsConfig {
+    fn current(&self) -> Option<&Project> {
+        self.projects.first()
+    }
+
+    /// Makes `project` the current one, replacing the known project with the
+    /// same maps file
+    fn open(&mut self, project: Project) {
+        self.projects
+            .retain(|known| known.maps_path != project.maps_path);
+        self.projects.insert(0, project);
+        self.projects.truncate(MAX_RECENT_PROJECTS);
+    }
+
+    fn find(&self, maps_path: &Path) -> Option<&Project> {
+        self.projects
+            .iter()
+            .find(|project| project.maps_path == maps_path)
+    }
+}
+
+fn projects_path() -> Option<PathBuf> {
+    let mut path = app_dirs::config_dir()?;
+    path.push("projects.toml");
+    Some(path)
+}
+
+fn load_projects() -> ProjectsConfig {
+    let mut config = projects_path()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str::<ProjectsConfig>(&content).ok())
+        .unwrap_or_default();
+
+    if config.projects.is_empty() {
+        config
+            .projects
+            .extend(default_maps_path().map(|maps_path| Project {
+                name: "Default".to_owned(),
+                ..Project::with_maps_path(maps_path, None)
+            }));
+    }
+    config
+}
+
+fn save_projects(config: &ProjectsConfig) {
+    if let Some(path) = projects_path() {
+        let _ = std::fs::create_dir_all(path.parent().unwrap());
+        let _ = std::fs::write(path, toml::to_string_pretty(config).unwrap());
+    }
+}
+
+/// Stores paths and mod name shown in the window into the current project
+fn store_project_settings(main_window: &Weak<MainWindow>) {
+    let mut config = load_projects();
+    if let Some(project) = config.projects.first_mut() {
+        let main_window = main_window.unwrap();
+        let adapter = main_window.global::<CustomMapAdapter>();
+        project.romfs_path = adapter.get_romfs_path().to_string();
+        project.exefs_path = adapter.get_exefs_path().to_string();
+        project.mod_name = adapter.get_mod_name().to_string();
+        save_projects(&config);
+    }
+}
+
+/// Shows settings of the current project and the recent projects list
+fn show_current_project(main_window: &Weak<MainWindow>, config: &ProjectsConfig) {
+    let main_window = main_window.unwrap();
+    let adapter = main_window.global::<CustomMapAdapter>();
+
+    let names = config
+        .projects
+        .iter()
+        .map(|project| SharedString::from(project.name.as_str()))
+        .collect::<Vec<_>>();
+    adapter.set_recent_projects(ModelRc::new(VecModel::from(names)));
+    adapter.set_project_index(0);
+
+    if let Some(project) = config.current() {
+        adapter.set_romfs_path(project.romfs_path.as_str().into());
+        adapter.set_exefs_path(project.exefs_path.as_str().into());
+        adapter.set_mod_name(project.mod_name.as_str().into());
+    }
+    adapter.invoke_romfs_path_changed();
+}
+
+/// Switches to `project` with `project_maps` as its maps, saving settings of
+/// the project being left
+fn switch_project(
+    main_window: &Weak<MainWindow>,
+    maps: &RefCell<HashMap<String, Map>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+    project: Project,
+    project_maps: HashMap<String, Map>,
+) {
+    store_project_settings(main_window);
+
+    LOCAL_CONFIG_MODIFIED.set(modified_time(&project.maps_path));
+    let mut config = load_projects();
+    config.open(project);
+    save_projects(&config);
+
+    replace_maps(main_window, maps, maps_model, project_maps);
+    show_current_project(main_window, &config);
+}
+
+/// Replaces all maps shown with `new_maps`
+fn replace_maps(
+    main_window: &Weak<MainWindow>,
+    maps: &RefCell<HashMap<String, Map>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+    new_maps: HashMap<String, Map>,
+) {
+    let models = new_maps
+        .iter()
+        .sorted_by_key(|(k, _)| *k)
+        .map(|(_, m)| MapInfo::from(m))
+        .collect::<Vec<_>>();
+    maps_model.set_vec(models);
+    *maps.borrow_mut() = new_maps;
+
+    let window = main_window.unwrap();
+    let adapter = window.global::<CustomMapAdapter>();
+    adapter.set_current_row(-1);
+    adapter.set_maps(maps_model.clone().into());
+    adapter.invoke_update_row_data();
+}
+
+fn init_projects(
+    main_window: &Weak<MainWindow>,
+    maps: &Rc<RefCell<HashMap<String, Map>>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+) {
+    show_current_project(main_window, &load_projects());
+
+    let detected_romfs = romfs_detect::detect_romfs_roots()
+        .iter()
+        .map(|root| SharedString::from(root.to_string_lossy().as_ref()))
+        .collect::<Vec<_>>();
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .set_detected_romfs(ModelRc::new(VecModel::from(detected_romfs)));
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_project_settings_changed({
+            let main_window = main_window.clone();
+            move || store_project_settings(&main_window)
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_new_project({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+
+            move || {
+                let file = rfd::FileDialog::new()
+                    .set_title("New project maps file")
+                    .add_filter("Config file", &["toml"])
+                    .set_file_name("maps.toml")
+                    .save_file();
+
+                if let Some(file) = file {
+                    let project_maps = HashMap::new();
+                    if let Err(e) = save_config(&project_maps, &file) {
+                        show_error("Unable to create project", e);
+                        return;
+                    }
+
+                    let project = Project::with_maps_path(file, load_projects().current());
+                    switch_project(&main_window, &maps, &maps_model, project, project_maps);
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_open_project({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+
+            move || {
+                let file = rfd::FileDialog::new()
+                    .set_title("Project maps file")
+                    .add_filter("Config file", &["toml"])
+                    .pick_file();
+
+                if let Some(file) = file {
+                    let project_maps = match load_config(&file) {
+                        Ok(project_maps) => project_maps,
+                        Err(e) => {
+                            rfd::MessageDialog::new()
+                                .set_level(rfd::MessageLevel::Error)
+                                .set_title("Unable to open project")
+                                .set_description(format!("{}: {e}", file.display()))
+                                .show();
+                            return;
+                        }
+                    };
+
+                    let config = load_projects();
+                    let project = config
+                        .find(&file)
+                        .cloned()
+                        .unwrap_or_else(|| Project::with_maps_path(file, config.current()));
+                    switch_project(&main_window, &maps, &maps_model, project, project_maps);
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapAdapter>()
+        .on_save_project_as({
+            let main_window = main_window.clone();
+            let maps = maps.clone();
+            let maps_model = maps_model.clone();
+
+            move || {
+                let file = rfd::FileDialog::new()
+                    .set_title("Save project maps file as")
                     .add_filter("Config file", &["toml"])
                     .save_file();
 
                 if let Some(file) = file {
-                    save_config(&maps.borrow(), &file);
+                    store_project_settings(&main_window);
+                    let project_maps = maps.borrow().clone();
+                    if let Err(e) = save_config(&project_maps, &file) {
+                        show_error("Unable to save project", e);
+                        return;
+                    }
+
+                    let config = load_projects();
+                    let project = Project {
+                        mod_name: config
+                            .current()
+                            .map(|current| current.mod_name.clone())
+                            .unwrap_or_default(),
+                        ..Project::with_maps_path(file, config.current())
+                    };
+                    switch_project(&main_window, &maps, &maps_model, project, project_maps);
                 }
             }
         });
@@ -658,61 +2005,125 @@ fn init_custom_map_adapter(main_window: &MainWindow) {
     main_window
         .unwrap()
         .global::<CustomMapAdapter>()
-        .on_generate_mod({
+        .on_switch_to_recent_project({
             let main_window = main_window.clone();
             let maps = maps.clone();
+            let maps_model = maps_model.clone();
 
-            move || {
-                let out_dir = rfd::FileDialog::new()
-                    .set_title("Mod output path")
-                    .pick_folder();
-
-                if let Some(out_dir) = out_dir {
-                    let romfs_root = main_window
-                        .unwrap()
-                        .global::<CustomMapAdapter>()
-                        .get_romfs_path();
-                    let romfs_root = Path::new(romfs_root.as_str());
-                    let exefs_root = main_window
-                        .unwrap()
-                        .global::<CustomMapAdapter>()
-                        .get_exefs_path();
-                    let mut main_exe_path = PathBuf::from(exefs_root.as_str());
-                    main_exe_path.push("main");
-
-                    let maps = maps.borrow();
-                    let names = maps
-                        .values()
-                        .map(|m| m.song_info.id.to_string())
-                        .collect::<Vec<_>>();
+            move |idx| {
+                let config = load_projects();
+                // The current project is the first one
+                let Some(project) = config.projects.get(idx as usize).filter(|_| idx != 0) else {
+                    return;
+                };
+                let project = project.clone();
 
-                    let _ = Map::patch_files(romfs_root, &out_dir, maps.values(), false);
-                    exefs::patch_files(romfs_root, &main_exe_path, &out_dir, &names);
+                match load_config(&project.maps_path) {
+                    Ok(project_maps) => {
+                        switch_project(&main_window, &maps, &maps_model, project, project_maps)
+                    }
+                    // Maps file of a project that has no maps yet
+                    Err(_) if !project.maps_path.exists() => {
+                        switch_project(&main_window, &maps, &maps_model, project, HashMap::new())
+                    }
+                    Err(e) => {
+                        rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Error)
+                            .set_title("Unable to open project")
+                            .set_description(format!("{}: {e}", project.maps_path.display()))
+                            .show();
+                        show_current_project(&main_window, &config);
+                    }
                 }
             }
-        })
+        });
 }
 
-fn local_config_path() -> Option<PathBuf> {
-    let mut path = dirs::config_local_dir()?;
-    path.push("spell_bubble_mod_tool");
-    path.push("maps.toml");
+fn input_hashes_path() -> Option<PathBuf> {
+    let mut path = app_dirs::state_dir()?;
+    path.push("input_hashes.toml");
+    Some(path)
+}
+
+/// Location of input hashes in older versions, which kept them with settings
+fn legacy_input_hashes_path() -> Option<PathBuf> {
+    let mut path = app_dirs::config_dir()?;
+    path.push("input_hashes.toml");
     Some(path)
 }
 
+fn load_input_hashes() -> InputHashes {
+    input_hashes_path()
+        .filter(|path| path.is_file())
+        .or_else(legacy_input_hashes_path)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|content| toml::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_input_hashes(hashes: &InputHashes) {
+    if let Some(path) = input_hashes_path() {
+        let _ = std::fs::create_dir_all(path.parent().unwrap());
+        let _ = std::fs::write(path, toml::to_string_pretty(hashes).unwrap());
+        if let Some(legacy_path) = legacy_input_hashes_path() {
+            let _ = std::fs::remove_file(legacy_path);
+        }
+    }
+}
+
+thread_local! {
+    /// Modification time of the maps file of the current project when it was
+    /// last loaded or saved here, a different time on disk means another
+    /// instance changed it
+    static LOCAL_CONFIG_MODIFIED: Cell<Option<SystemTime>> = const { Cell::new(None) };
+    /// Modification time of the maps file when reloading it was declined, so
+    /// that the reload is offered once per change
+    static RELOAD_DECLINED: Cell<Option<SystemTime>> = const { Cell::new(None) };
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+fn local_config_changed(path: &Path) -> bool {
+    let modified = modified_time(path);
+    modified.is_some() && modified != LOCAL_CONFIG_MODIFIED.get()
+}
+
+/// Takes the advisory lock of the maps file at `path`, held until the returned
+/// file is dropped. Instances reading or writing the maps file take the lock so
+/// that none of them sees a partially written file
+fn lock_local_config(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(lock_path)?;
+    fs4::FileExt::lock_exclusive(&file)?;
+    Ok(file)
+}
+
 fn load_local_config() -> anyhow::Result<HashMap<String, Map>> {
-    load_config(&local_config_path().ok_or(anyhow::anyhow!(""))?)
+    let path = local_config_path().ok_or(anyhow::anyhow!(""))?;
+    let _lock = lock_local_config(&path)?;
+    LOCAL_CONFIG_MODIFIED.set(modified_time(&path));
+    load_config(&path)
 }
 
 fn load_config(path: &Path) -> anyhow::Result<HashMap<String, Map>> {
     let maps: crate::map::MapsConfig = {
         let content = std::fs::read_to_string(path)?;
-        toml::from_str(&content)?
+        crate::map::MapsConfig::from_toml(&content)?
     };
-
-    for map in maps.maps.iter() {
-        map.validate(false)?
-    }
+    maps.validate_maps()?;
 
     Ok(maps
         .maps
@@ -722,23 +2133,113 @@ fn load_config(path: &Path) -> anyhow::Result<HashMap<String, Map>> {
 }
 
 fn save_local_config(maps: &HashMap<String, Map>) {
-    if let Some(local_config) = local_config_path() {
-        save_config(maps, &local_config)
+    let Some(local_config) = local_config_path() else {
+        return;
+    };
+
+    // Asked before locking, so that other instances are not blocked while the
+    // dialog is open
+    if local_config_changed(&local_config) {
+        let overwrite = rfd::MessageDialog::new()
+            .set_title("Maps changed by another instance")
+            .set_description(format!(
+                "{} was changed outside this window since it was loaded, probably by another \
+                 instance of this tool. Overwrite it with the maps shown here?\n\nChoose No to \
+                 keep the file on disk, it can then be reloaded.",
+                local_config.display()
+            ))
+            .set_level(rfd::MessageLevel::Warning)
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show()
+            == rfd::MessageDialogResult::Yes;
+        if !overwrite {
+            return;
+        }
     }
-}
 
-fn save_config(maps: &HashMap<String, Map>, path: &Path) {
-    let maps_config = crate::map::MapsConfig {
-        maps: maps.values().cloned().collect(),
+    let _lock = match lock_local_config(&local_config) {
+        Ok(lock) => lock,
+        Err(e) => {
+            show_error("Unable to lock maps", e);
+            return;
+        }
     };
+    match save_config(maps, &local_config) {
+        Ok(()) => LOCAL_CONFIG_MODIFIED.set(modified_time(&local_config)),
+        Err(e) => show_error("Unable to save maps", e),
+    }
+}
+
+/// Checks the maps file of the current project every few seconds, offering to
+/// reload it when another instance changed it
+fn watch_local_config(
+    main_window: &Weak<MainWindow>,
+    maps: &Rc<RefCell<HashMap<String, Map>>>,
+    maps_model: &Rc<VecModel<MapInfo>>,
+) -> slint::Timer {
+    let timer = slint::Timer::default();
+    timer.start(slint::TimerMode::Repeated, Duration::from_secs(2), {
+        let main_window = main_window.clone();
+        let maps = maps.clone();
+        let maps_model = maps_model.clone();
+
+        move || {
+            let Some(local_config) = local_config_path() else {
+                return;
+            };
+            let modified = modified_time(&local_config);
+            if !local_config_changed(&local_config) || modified == RELOAD_DECLINED.get() {
+                return;
+            }
+
+            let reload = rfd::MessageDialog::new()
+                .set_title("Maps changed by another instance")
+                .set_description(format!(
+                    "{} was changed outside this window, probably by another instance of this \
+                     tool. Reload it? Maps shown here are replaced by the ones on disk.",
+                    local_config.display()
+                ))
+                .set_level(rfd::MessageLevel::Warning)
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                == rfd::MessageDialogResult::Yes;
+            if !reload {
+                RELOAD_DECLINED.set(modified);
+                return;
+            }
+
+            match load_local_config() {
+                Ok(new_maps) => replace_maps(&main_window, &maps, &maps_model, new_maps),
+                Err(e) => {
+                    rfd::MessageDialog::new()
+                        .set_level(rfd::MessageLevel::Error)
+                        .set_title("Unable to reload maps")
+                        .set_description(format!("{}: {e}", local_config.display()))
+                        .show();
+                }
+            }
+        }
+    });
+    timer
+}
+
+/// Writes `maps` into the config at `path`, keeping the order of its maps and
+/// its other settings
+fn save_config(maps: &HashMap<String, Map>, path: &Path) -> anyhow::Result<()> {
+    let mut maps_config = crate::map::MapsConfig::read_for_update(path)?;
+    maps_config.update_maps(maps);
 
     let mut config_path = path.to_owned();
     config_path.pop();
 
-    let _ = std::fs::create_dir_all(config_path);
-    let _ = std::fs::write(path, toml::to_string_pretty(&maps_config).unwrap());
+    std::fs::create_dir_all(config_path)?;
+    Ok(std::fs::write(
+        path,
+        toml::to_string_pretty(&maps_config).unwrap(),
+    )?)
 }
 
+#[derive(Clone, Copy)]
 struct AreaModel {
     area_idx:   i32,
     area_night: bool,
@@ -803,7 +2304,11 @@ impl From<Area> for AreaModel {
                 area_idx:   9,
                 area_night: true,
             },
-            _ => unreachable!(),
+            // Areas not selectable in the editor are shown as Arena
+            _ => AreaModel {
+                area_idx:   0,
+                area_night: false,
+            },
         }
     }
 }
@@ -845,7 +2350,7 @@ impl From<AreaModel> for Area {
                     Area::YoukaiNoYama
                 }
             }
-            _ => unreachable!(),
+            _ => Area::Arena,
         }
     }
 }
@@ -940,11 +2445,51 @@ impl From<&Map> for MapInfo {
             .as_ref()
             .unwrap_or(&bpm_changes_default)
             .into();
-        let score = map.map_scores.get(&Hard).unwrap();
+        let mut issues = vec![];
+        let (status, status_text) = validation_status(map);
+
+        if map.song_info.info_text.is_empty() {
+            issues.push("No song info text".to_owned());
+        }
+
+        if Area::from(area_model) != map.song_info.area {
+            issues.push(format!(
+                "Area {} is not supported in editor, shown as Arena",
+                map.song_info.area
+            ));
+        }
+
+        // Hard is shown first and rated, the longest score of other difficulties
+        // is used for partial maps
+        let difficulty = if map.map_scores.contains_key(&Hard) {
+            Some(Hard)
+        } else {
+            map.map_scores
+                .iter()
+                .max_by_key(|(_, score)| score.scores.0.len())
+                .map(|(difficulty, _)| *difficulty)
+        };
+        if difficulty.is_none() {
+            issues.push("No map score".to_owned());
+        }
 
+        let scores = EDITOR_DIFFICULTIES
+            .iter()
+            .map(|d| {
+                map.map_scores
+                    .get(d)
+                    .map(|score| score.scores.to_string().into())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<SharedString>>();
+        let difficulty_idx = difficulty
+            .and_then(|d| EDITOR_DIFFICULTIES.iter().position(|&e| e == d))
+            .unwrap_or(EDITOR_DIFFICULTIES.len() - 1);
         let score = MapScore {
             bpm_changes: ModelRc::new(VecModel::from(bpm_changes)),
-            score:       score.scores.to_string().into(),
+            score:       scores[difficulty_idx].clone(),
+            scores:      ModelRc::new(VecModel::from(scores)),
+            difficulty:  difficulty_idx as i32,
         };
 
         Self {
@@ -954,15 +2499,50 @@ impl From<&Map> for MapInfo {
             id: map.song_info.id.to_string().into(),
             info_text,
             length: map.song_info.length as i32,
-            level: map.level(Hard, None) as i32,
+            level: difficulty.map_or(0, |d| map.level(d, None)) as i32,
             music_file: map.song_info.music_file.as_str().into(),
+            intro_file: map
+                .song_info
+                .intro_file
+                .as_deref()
+                .unwrap_or_default()
+                .into(),
+            volume_db: map
+                .song_info
+                .volume_db
+                .map(|volume_db| volume_db.to_string())
+                .unwrap_or_default()
+                .into(),
             offset: map.song_info.offset,
             prev_start_ms: map.song_info.prev_start_ms as i32,
             score,
+            issues: issues.join("; ").into(),
+            status,
+            status_text,
         }
     }
 }
 
+/// Validation status of map shown in the map table, as the status code and
+/// all problems found
+fn validation_status(map: &Map) -> (i32, SharedString) {
+    // Maps generated in GUI are always added as new songs
+    let validation = map.validate_with_warnings(false);
+    let status = match validation.status() {
+        MapStatus::Ok => 0,
+        MapStatus::Warning => 1,
+        MapStatus::Error => 2,
+    };
+    let text = validation
+        .errors
+        .iter()
+        .map(|e| format!("Error: {e}"))
+        .chain(validation.warnings.iter().map(|w| format!("Warning: {w}")))
+        .join("\n");
+
+    (status, text.into())
+}
+
 impl From<&MapInfo> for Map {
     fn from(map: &MapInfo) -> Self {
         let area_model = AreaModel {
@@ -971,21 +2551,21 @@ impl From<&MapInfo> for Map {
         };
 
         let id_to_lang = |id: usize| match id {
-            0 => JA,
-            1 => Chs,
-            2 => Cht,
-            3 => EN,
-            4 => KO,
-            _ => unreachable!(),
+            0 => Some(JA),
+            1 => Some(Chs),
+            2 => Some(Cht),
+            3 => Some(EN),
+            4 => Some(KO),
+            _ => None,
         };
         let info_text = map
             .info_text
             .iter()
             .enumerate()
-            .map(|(i, t)| {
-                let lang = id_to_lang(i);
+            .filter_map(|(i, t)| {
+                let lang = id_to_lang(i)?;
                 let text: SongInfoText = t.into();
-                (lang, text)
+                Some((lang, text))
             })
             .filter(|(_, t)| *t != SongInfoText::default())
             .collect::<HashMap<_, _>>();
@@ -997,18 +2577,29 @@ impl From<&MapInfo> for Map {
         } else {
             Some(bpm_changes)
         };
-        let map_score = crate::map::ScoreData::from_str(map_score.score.as_str()).unwrap();
-        let map_score = crate::map::MapScore {
-            scores: map_score.clone(),
-        };
+        let map_scores = zip(EDITOR_DIFFICULTIES, difficulty_scores(map_score))
+            .filter_map(|(difficulty, score)| {
+                let scores = crate::map::ScoreData::from_str(score.as_str()).ok()?;
+                (!scores.0.is_empty()).then_some((difficulty, crate::map::MapScore { scores }))
+            })
+            .collect::<HashMap<_, _>>();
+        let length = map_scores
+            .values()
+            .map(|score| score.scores.0.len())
+            .max()
+            .unwrap_or_default();
 
         Self {
-            song_info:  SongInfo {
+            song_info: SongInfo {
                 id: MusicID::New(map.id.as_str().to_owned()),
                 music_file: map.music_file.as_str().into(),
+                intro_file: Some(map.intro_file.as_str())
+                    .filter(|file| !file.is_empty())
+                    .map(|file| file.to_owned()),
+                volume_db: map.volume_db.as_str().parse().ok(),
                 bpm: map.bpm,
                 offset: map.offset,
-                length: map.score.score.len() as u16,
+                length: length as u16,
                 area: area_model.into(),
                 info_text,
                 prev_start_ms: map.prev_start_ms as u32,
@@ -1016,11 +2607,141 @@ impl From<&MapInfo> for Map {
                 beats_layout: None,
                 dlc_index: 0,
             },
-            map_scores: hashmap! { Hard => map_score },
+            map_scores,
         }
     }
 }
 
+/// Difficulties in the order of the editor's difficulty selector
+const EDITOR_DIFFICULTIES: [Difficulty; 3] = [Easy, Normal, Hard];
+
+/// Score strings of the edited map in [`EDITOR_DIFFICULTIES`] order, with the
+/// score being edited put in place of the selected difficulty
+fn difficulty_scores(score: &MapScore) -> Vec<SharedString> {
+    let mut scores = (0..EDITOR_DIFFICULTIES.len())
+        .map(|i| score.scores.row_data(i).unwrap_or_default())
+        .collect::<Vec<_>>();
+    if let Some(selected) = usize::try_from(score.difficulty)
+        .ok()
+        .and_then(|i| scores.get_mut(i))
+    {
+        *selected = score.score.clone();
+    }
+    scores
+}
+
+/// A map carrying only the timing of the map being edited, for beat and time
+/// conversions
+fn timing_map(score: &MapScore, bpm: &str, offset: &str) -> Option<Map> {
+    let bpm_changes: BpmChanges = (&score.bpm_changes).into();
+    Some(Map {
+        song_info:  SongInfo {
+            bpm: bpm.parse().ok()?,
+            offset: offset.parse().ok()?,
+            bpm_changes: Some(bpm_changes).filter(|changes| !changes.0.is_empty()),
+            ..Default::default()
+        },
+        map_scores: Default::default(),
+    })
+}
+
+/// Splits the score into constant tempo sections for the BPM timeline, with
+/// beat positions and BPM levels normalized into 0..1
+fn timeline_segments(bpm: f32, bpm_changes: &BpmChanges, length: usize) -> Vec<TimelineSegment> {
+    let changes = bpm_changes
+        .0
+        .iter()
+        .enumerate()
+        .sorted_by_key(|(_, (idx, _))| *idx)
+        .map(|(i, (idx, bpm))| (i as i32, *idx as usize, *bpm))
+        .collect::<Vec<_>>();
+
+    let length = changes
+        .iter()
+        .map(|(_, idx, _)| idx + 1)
+        .chain([length, 1])
+        .max()
+        .unwrap() as f32;
+    let max_bpm = changes
+        .iter()
+        .map(|(_, _, bpm)| *bpm)
+        .fold(bpm, f32::max)
+        .max(f32::EPSILON);
+
+    let starts = [(-1, 0, bpm)]
+        .into_iter()
+        .chain(changes)
+        .collect::<Vec<_>>();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, (change_index, beat, bpm))| {
+            let end = starts.get(i + 1).map_or(length, |(_, idx, _)| *idx as f32);
+            TimelineSegment {
+                start:        *beat as f32 / length,
+                end:          end / length,
+                level:        (bpm / max_bpm).clamp(0.0, 1.0),
+                beat:         *beat as i32,
+                bpm:          *bpm,
+                change_index: *change_index,
+            }
+        })
+        .collect()
+}
+
+/// Rows of `beats_per_row` score entries for the score grid, grouped into
+/// measures of 4 beats as score scripts are, noting BPM changes in each row
+fn score_rows(score: &str, bpm_changes: &BpmChanges, beats_per_row: usize) -> Vec<ScoreRow> {
+    let entries = score.chars().collect::<Vec<_>>();
+    let beats_per_row = beats_per_row.max(4);
+
+    entries
+        .chunks(beats_per_row)
+        .enumerate()
+        .map(|(i, row)| {
+            let first_beat = i * beats_per_row;
+            let beats = first_beat..first_beat + row.len();
+            let bpm_note = bpm_changes
+                .0
+                .iter()
+                .filter(|(idx, _)| beats.contains(&(*idx as usize)))
+                .sorted_by_key(|(idx, _)| *idx)
+                .map(|(idx, bpm)| format!("{idx}: {bpm} BPM"))
+                .join(", ");
+
+            ScoreRow {
+                measure:  (first_beat / 4 + 1) as i32,
+                entries:  row.chunks(4).map(String::from_iter).join(" ").into(),
+                bpm_note: bpm_note.into(),
+            }
+        })
+        .collect()
+}
+
+/// Snaps BPMs of imported maps and reports the adjustments made, the initial
+/// BPM is set to the editor directly
+fn snap_imported_bpms(
+    main_window: &Weak<MainWindow>,
+    mut bpm: f32,
+    mut bpm_changes: Option<BpmChanges>,
+) -> ModelRc<BpmChange> {
+    let adjustments = snap_bpms(&mut bpm, &mut bpm_changes, DEFAULT_SNAP_TOLERANCE);
+    if !adjustments.is_empty() {
+        rfd::MessageDialog::new()
+            .set_title("Imported BPMs adjusted")
+            .set_description(adjustments.iter().join("\n"))
+            .show();
+    }
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .set_bpm(bpm.to_string().into());
+
+    let bpm_changes: Vec<BpmChange> = bpm_changes.unwrap_or_default().into();
+    ModelRc::new(VecModel::from(bpm_changes))
+}
+
 fn init_custom_map_model(main_window: &MainWindow) {
     let main_window = main_window.as_weak();
 
@@ -1051,9 +2772,17 @@ fn init_custom_map_model(main_window: &MainWindow) {
                     length: 0,
                     level: 0,
                     music_file: Default::default(),
+                    intro_file: Default::default(),
+                    volume_db: Default::default(),
                     offset: 0.0,
                     prev_start_ms: 0,
-                    score: Default::default(),
+                    score: MapScore {
+                        difficulty: EDITOR_DIFFICULTIES.len() as i32 - 1,
+                        ..Default::default()
+                    },
+                    issues: Default::default(),
+                    status: Default::default(),
+                    status_text: Default::default(),
                 }
             }
         });
@@ -1063,6 +2792,93 @@ fn init_custom_map_model(main_window: &MainWindow) {
         .global::<CustomMapModel>()
         .on_get_text(|map, index| map.info_text.row_data(index as usize).unwrap_or_default());
 
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_timeline_segments(|score, bpm| {
+            let bpm = bpm.parse().unwrap_or_default();
+            let bpm_changes: BpmChanges = (&score.bpm_changes).into();
+            let segments = timeline_segments(bpm, &bpm_changes, score.score.len());
+            ModelRc::new(VecModel::from(segments))
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_score_rows(|score, beats_per_row| {
+            let bpm_changes: BpmChanges = (&score.bpm_changes).into();
+            let rows = score_rows(&score.score, &bpm_changes, beats_per_row as usize);
+            ModelRc::new(VecModel::from(rows))
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_beat_to_time(|score, bpm, offset, idx| {
+            let time: Option<String> = try {
+                let map = timing_map(&score, &bpm, &offset)?;
+                format_timestamp(map.beat_time(idx.parse().ok()?))
+            };
+            time.unwrap_or_default().into()
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_time_to_beat(|score, bpm, offset, time| {
+            let beat: Option<String> = try {
+                let map = timing_map(&score, &bpm, &offset)?;
+                map.beat_index(parse_timestamp(&time)?)?.to_string()
+            };
+            beat.unwrap_or_default().into()
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_select_difficulty(|score, difficulty| {
+            let scores = difficulty_scores(&score);
+            MapScore {
+                score: scores.get(difficulty as usize).cloned().unwrap_or_default(),
+                scores: ModelRc::new(VecModel::from(scores)),
+                difficulty,
+                ..score
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_update_bpm_change({
+            let main_window = main_window.clone();
+            move |score, change_index, idx, bpm| {
+                let (Ok(idx), Ok(bpm)) = (idx.parse::<u16>(), bpm.parse::<f32>()) else {
+                    return score;
+                };
+
+                // The initial BPM always starts from beat 0
+                if change_index < 0 {
+                    main_window
+                        .unwrap()
+                        .global::<CustomMapModel>()
+                        .set_bpm(bpm.to_string().into());
+                    return score;
+                }
+
+                let mut bpm_changes: BpmChanges = (&score.bpm_changes).into();
+                if let Some(change) = bpm_changes.0.get_mut(change_index as usize) {
+                    *change = (idx, bpm);
+                }
+                bpm_changes.0.sort_by_key(|(i, _)| *i);
+
+                let bpm_changes: Vec<BpmChange> = bpm_changes.into();
+                MapScore {
+                    bpm_changes: ModelRc::new(VecModel::from(bpm_changes)),
+                    ..score
+                }
+            }
+        });
+
     main_window
         .unwrap()
         .global::<CustomMapModel>()
@@ -1107,6 +2923,18 @@ fn init_custom_map_model(main_window: &MainWindow) {
             }
         });
 
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_suggest_id({
+            let main_window = main_window.clone();
+            move |map_model| {
+                let maps = main_window.unwrap().global::<CustomMapAdapter>().get_maps();
+                let is_taken = |id: &str| maps.iter().any(|m| m.id != map_model.id && m.id == id);
+                Map::from(&map_model).song_info.suggest_id(is_taken).into()
+            }
+        });
+
     main_window
         .unwrap()
         .global::<CustomMapModel>()
@@ -1118,20 +2946,31 @@ fn init_custom_map_model(main_window: &MainWindow) {
                     .global::<CustomMapModel>()
                     .get_current_map();
 
-                let m_id = MusicID::from(id.as_str());
-                let id = if let MusicID::Existing(_) = m_id {
-                    format!("{id}1").into()
-                } else {
-                    id
+                let numbers: anyhow::Result<_> = try {
+                    (
+                        bpm.as_str().parse().context("invalid BPM")?,
+                        offset.as_str().parse().context("invalid offset")?,
+                        prev_start_ms
+                            .as_str()
+                            .parse()
+                            .context("invalid preview start")?,
+                    )
+                };
+                let (bpm, offset, prev_start_ms) = match numbers {
+                    Ok(numbers) => numbers,
+                    Err(e) => {
+                        show_error("Unable to update map", e);
+                        return;
+                    }
                 };
 
                 map.id = id;
                 map.music_file = music_file;
-                map.bpm = bpm.as_str().parse().unwrap();
-                map.offset = offset.as_str().parse().unwrap();
+                map.bpm = bpm;
+                map.offset = offset;
                 map.area_idx = area_idx;
                 map.area_night = area_night;
-                map.prev_start_ms = prev_start_ms.as_str().parse().unwrap();
+                map.prev_start_ms = prev_start_ms;
                 map.score = score;
 
                 main_window
@@ -1146,43 +2985,43 @@ fn init_custom_map_model(main_window: &MainWindow) {
         .global::<CustomMapModel>()
         .on_from_osu({
             let main_window = main_window.clone();
-            move || {
+            move |current| {
                 let file = rfd::FileDialog::new()
                     .set_title("Choose Osu map")
                     .add_filter("Osu Map", &["osu"])
                     .pick_file();
+                let Some(file) = file else {
+                    return current;
+                };
 
                 let osu: anyhow::Result<crate::external_map::Osu> = try {
-                    let content = std::fs::read_to_string(file.as_ref().unwrap())?;
+                    let content = std::fs::read_to_string(&file)?;
                     crate::external_map::Osu::new(&content)?
                 };
-                let osu = osu.unwrap();
+                let osu = match osu {
+                    Ok(osu) => osu,
+                    Err(e) => {
+                        let e = e.context(file.display().to_string());
+                        show_error("Unable to import osu map", e);
+                        return current;
+                    }
+                };
 
-                let bpm = osu.initial_bpm().to_f32().unwrap();
-                main_window
-                    .unwrap()
-                    .global::<CustomMapModel>()
-                    .set_bpm(bpm.to_string().into());
-                let offset = osu.offset().to_f32().unwrap() / 1000.0;
+                let offset = osu.offset().to_f32().unwrap_or_default() / 1000.0;
                 main_window
                     .unwrap()
                     .global::<CustomMapModel>()
                     .set_offset(offset.to_string().into());
 
-                let bpm_changes = osu
-                    .bpm_changes()
-                    .unwrap_or_default()
-                    .0
-                    .into_iter()
-                    .map(|(idx, bpm)| BpmChange {
-                        idx: idx as i32,
-                        bpm,
-                    })
-                    .collect::<Vec<_>>();
-                let bpm_changes = ModelRc::new(VecModel::from(bpm_changes));
+                let bpm = osu.initial_bpm().to_f32().unwrap_or_default();
+                let bpm_changes = snap_imported_bpms(&main_window, bpm, osu.bpm_changes());
 
                 let score = osu.score().to_string().into();
-                MapScore { bpm_changes, score }
+                MapScore {
+                    bpm_changes,
+                    score,
+                    ..current
+                }
             }
         });
 
@@ -1191,41 +3030,157 @@ fn init_custom_map_model(main_window: &MainWindow) {
         .global::<CustomMapModel>()
         .on_from_adofai({
             let main_window = main_window.clone();
-            move || {
+            move |current| {
                 let file = rfd::FileDialog::new()
                     .set_title("Choose ADoFaI map")
                     .add_filter("ADoFaI Map", &["adofai"])
                     .pick_file();
+                let Some(file) = file else {
+                    return current;
+                };
 
                 let adofai: anyhow::Result<crate::external_map::ADoFaIMap> = try {
-                    let content = std::fs::read_to_string(file.as_ref().unwrap())?;
+                    let content = std::fs::read_to_string(&file)?;
                     serde_json::from_str(content.trim_start_matches('\u{feff}'))?
                 };
-                let mut adofai = adofai.unwrap();
+                let mut adofai = match adofai {
+                    Ok(adofai) => adofai,
+                    Err(e) => {
+                        let e = e.context(file.display().to_string());
+                        show_error("Unable to import ADoFaI map", e);
+                        return current;
+                    }
+                };
 
-                let bpm = adofai.bpm();
-                main_window
-                    .unwrap()
-                    .global::<CustomMapModel>()
-                    .set_bpm(bpm.to_string().into());
                 let offset = adofai.offset();
                 main_window
                     .unwrap()
                     .global::<CustomMapModel>()
                     .set_offset(offset.to_string().into());
 
-                let bpm_changes = adofai
-                    .bpm_changes()
-                    .into_iter()
-                    .map(|(idx, bpm)| BpmChange {
-                        idx: idx as i32,
-                        bpm,
-                    })
-                    .collect::<Vec<_>>();
-                let bpm_changes = ModelRc::new(VecModel::from(bpm_changes));
+                let bpm_changes = adofai.bpm_changes();
+                let bpm_changes = (!bpm_changes.is_empty()).then_some(BpmChanges(bpm_changes));
+                let bpm_changes = snap_imported_bpms(&main_window, adofai.bpm(), bpm_changes);
 
                 let score = crate::map::ScoreData(adofai.scores()).to_string().into();
-                MapScore { bpm_changes, score }
+                MapScore {
+                    bpm_changes,
+                    score,
+                    ..current
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_from_clipboard({
+            let main_window = main_window.clone();
+            move |score| {
+                let window = main_window.unwrap();
+                let model = window.global::<CustomMapModel>();
+                let current_timing = model
+                    .get_bpm()
+                    .parse()
+                    .ok()
+                    .zip(model.get_offset().parse().ok());
+                let chart: anyhow::Result<crate::external_map::PastedChart> = try {
+                    let text = arboard::Clipboard::new()?.get_text()?;
+                    crate::external_map::parse_pasted_chart(&text, current_timing)?
+                };
+                let chart = match chart {
+                    Ok(chart) => chart,
+                    Err(e) => {
+                        rfd::MessageDialog::new()
+                            .set_level(rfd::MessageLevel::Error)
+                            .set_title("Unable to paste chart")
+                            .set_description(e.to_string())
+                            .show();
+                        return score;
+                    }
+                };
+
+                if let Some(offset) = chart.offset {
+                    main_window
+                        .unwrap()
+                        .global::<CustomMapModel>()
+                        .set_offset(offset.to_string().into());
+                }
+
+                // Raw score strings carry no timing, so the current BPM settings are kept
+                let bpm_changes = match chart.bpm {
+                    Some(bpm) => snap_imported_bpms(&main_window, bpm, chart.bpm_changes),
+                    None => score.bpm_changes,
+                };
+
+                MapScore {
+                    bpm_changes,
+                    score: chart.score.to_string().into(),
+                    ..score
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_from_tempo_map({
+            let main_window = main_window.clone();
+            move |score| {
+                let file = rfd::FileDialog::new()
+                    .set_title("Choose DAW tempo map")
+                    .add_filter("Tempo Map", &["mid", "midi", "csv"])
+                    .pick_file();
+                let Some(file) = file else {
+                    return score;
+                };
+
+                let tempo_map = match crate::external_map::TempoMap::load(&file) {
+                    Ok(tempo_map) => tempo_map,
+                    Err(e) => {
+                        show_error("Unable to import tempo map", e);
+                        return score;
+                    }
+                };
+
+                main_window
+                    .unwrap()
+                    .global::<CustomMapModel>()
+                    .set_offset(tempo_map.offset().to_string().into());
+
+                let bpm_changes = snap_imported_bpms(
+                    &main_window,
+                    tempo_map.initial_bpm(),
+                    tempo_map.bpm_changes(),
+                );
+
+                MapScore {
+                    bpm_changes,
+                    ..score
+                }
+            }
+        });
+
+    main_window
+        .unwrap()
+        .global::<CustomMapModel>()
+        .on_pick_preview(|intro_file, music_file, prev_start_ms| {
+            let song_info = SongInfo {
+                music_file: music_file.as_str().to_owned(),
+                intro_file: Some(intro_file.as_str().to_owned()).filter(|file| !file.is_empty()),
+                ..Default::default()
+            };
+
+            match preview_pick::pick_preview_start(&song_info) {
+                Ok(prev_start_ms) => prev_start_ms.to_string().into(),
+                Err(e) => {
+                    rfd::MessageDialog::new()
+                        .set_title("Failed to pick preview")
+                        .set_description(e.to_string())
+                        .set_level(rfd::MessageLevel::Error)
+                        .show();
+                    prev_start_ms
+                }
             }
         });
 }
@@ -0,0 +1,74 @@
+use std::{any::Any, cell::RefCell};
+
+use slint::{Model, ModelNotify, ModelRc, ModelTracker, StandardListViewItem, VecModel};
+
+type Row = ModelRc<StandardListViewItem>;
+type RowBuilder<T> = Box<dyn Fn(&T) -> Vec<StandardListViewItem>>;
+
+/// Table rows built from `items` only when the table asks for them, so only
+/// visible rows are materialized. Built rows are kept until the items are
+/// replaced, as sorting and scrolling back request the same rows repeatedly.
+pub struct LazyRowModel<T> {
+    items:  RefCell<Vec<T>>,
+    to_row: RefCell<RowBuilder<T>>,
+    rows:   RefCell<Vec<Option<Row>>>,
+    notify: ModelNotify,
+}
+
+impl<T> Default for LazyRowModel<T> {
+    fn default() -> Self {
+        Self {
+            items:  Default::default(),
+            to_row: RefCell::new(Box::new(|_| vec![])),
+            rows:   Default::default(),
+            notify: Default::default(),
+        }
+    }
+}
+
+impl<T> LazyRowModel<T> {
+    pub fn new(items: Vec<T>, to_row: impl Fn(&T) -> Vec<StandardListViewItem> + 'static) -> Self {
+        let model = Self::default();
+        model.set_items(items, to_row);
+        model
+    }
+
+    /// Replaces all items and the way rows are built from them
+    pub fn set_items(
+        &self,
+        items: Vec<T>,
+        to_row: impl Fn(&T) -> Vec<StandardListViewItem> + 'static,
+    ) {
+        *self.rows.borrow_mut() = vec![None; items.len()];
+        *self.items.borrow_mut() = items;
+        *self.to_row.borrow_mut() = Box::new(to_row);
+        self.notify.reset();
+    }
+}
+
+impl<T: 'static> Model for LazyRowModel<T> {
+    type Data = Row;
+
+    fn row_count(&self) -> usize {
+        self.items.borrow().len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        if let Some(built) = self.rows.borrow().get(row)? {
+            return Some(built.clone());
+        }
+
+        let items = self.items.borrow();
+        let built = ModelRc::new(VecModel::from((self.to_row.borrow())(items.get(row)?)));
+        self.rows.borrow_mut()[row] = Some(built.clone());
+        Some(built)
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,93 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info};
+
+use crate::map::MapsConfig;
+
+/// Interval watched files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The map config and audio files of its maps. Only the config is watched if
+/// it cannot be parsed, so fixing it triggers a rebuild.
+fn watched_files(maps_path: &Path) -> Vec<PathBuf> {
+    let maps_config = fs::read_to_string(maps_path)
+        .ok()
+        .and_then(|content| MapsConfig::from_toml(&content).ok());
+
+    let mut files = vec![maps_path.to_owned()];
+    if let Some(maps_config) = maps_config {
+        for map in maps_config.expanded_maps() {
+            files.extend(map.song_info.audio_files().map(PathBuf::from));
+        }
+    }
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Modification times of watched files, `None` for missing ones
+fn snapshot(maps_path: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    watched_files(maps_path)
+        .into_iter()
+        .map(|file| {
+            let modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+            (file, modified)
+        })
+        .collect()
+}
+
+/// Runs `patch`, then runs it again whenever the map config at `maps_path` or
+/// audio files of its maps change, until the process is interrupted. Failed
+/// runs are reported and retried on the next change. Unchanged songs are
+/// skipped by PatchMap, so only affected outputs are regenerated.
+pub fn watch_maps(maps_path: &Path, patch: impl Fn() -> anyhow::Result<()>) -> anyhow::Result<()> {
+    loop {
+        // Taken before patching so that changes made meanwhile are not missed
+        let state = snapshot(maps_path);
+        if let Err(e) = patch() {
+            error!("{e:#}");
+        }
+
+        info!(
+            "Watching {} and its audio files for changes, press Ctrl+C to stop",
+            maps_path.display()
+        );
+        while snapshot(maps_path) == state {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        // Editors may save a file in several writes
+        std::thread::sleep(POLL_INTERVAL);
+        info!("Changes detected, patching again");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::map::EXAMPLE_CONFIG;
+
+    #[test]
+    fn test_watched_files() {
+        let mut maps_path = std::env::temp_dir();
+        maps_path.push(format!("watch_test_{}.toml", std::process::id()));
+
+        fs::write(&maps_path, EXAMPLE_CONFIG).unwrap();
+        assert_eq!(
+            watched_files(&maps_path),
+            vec![
+                maps_path.clone(),
+                PathBuf::from("songs/new_song.ogg"),
+                PathBuf::from("songs/replacement.ogg"),
+            ]
+        );
+
+        fs::write(&maps_path, "[[maps]").unwrap();
+        assert_eq!(watched_files(&maps_path), vec![maps_path.clone()]);
+
+        fs::remove_file(&maps_path).unwrap();
+    }
+}